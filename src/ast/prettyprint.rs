@@ -1,38 +1,234 @@
 use std::fmt::Display;
 
-use crate::semantics::types::{Size, Type};
+use crate::semantics::types::{builtin_enum, Size, Type, TypeParam};
 
-use super::tree::{Expr, Expression, Function, Operator, Program, Sequence, TypeBound, Value};
+use super::tree::{
+    is_tuple, Alias, Arm, Enum, Expr, Expression, FuncKind, Function, Global, Impl, Item, Module,
+    Operator, Pattern, Program, Sequence, Signature, Struct, Trait, TypeBound, Value,
+};
 
 impl<T: TypeBound> Display for Program<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.0.iter().try_for_each(|func| write!(f, "{}", func))
+        self.0.iter().try_for_each(|item| write!(f, "{}", item))
     }
 }
 
-impl<T: TypeBound> Display for Function<T> {
+impl<T: TypeBound> Display for Item<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Item::Func(func) => write!(f, "{}", func),
+            Item::Struct(def) => write!(f, "{}", def),
+            Item::Enum(def) => write!(f, "{}", def),
+            Item::Alias(def) => write!(f, "{}", def),
+            Item::Trait(def) => write!(f, "{}", def),
+            Item::Impl(def) => write!(f, "{}", def),
+            Item::Const(def) => fmt_global(f, "const", def),
+            Item::Static(def) => fmt_global(f, "static", def),
+            Item::Mod(def) => write!(f, "{}", def),
+            Item::Use(path) => write!(f, "use {};\n\n", path),
+        }
+    }
+}
+
+impl<T: TypeBound> Display for Module<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}mod {}", fmt_pub(self.public), self.name)?;
+        match &self.items {
+            None => write!(f, ";\n\n"),
+            Some(items) => {
+                let items = items
+                    .iter()
+                    .map(|item| item.to_string())
+                    .collect::<String>();
+                write!(f, " {{\n{}}}\n\n", apply_indent(items, 2))
+            }
+        }
+    }
+}
+
+fn fmt_global<T: TypeBound>(
+    f: &mut std::fmt::Formatter<'_>,
+    keyword: &str,
+    def: &Global<T>,
+) -> std::fmt::Result {
+    write!(
+        f,
+        "{}{} {}: {} = {};\n\n",
+        fmt_pub(def.public),
+        keyword,
+        def.name,
+        def.ty,
+        def.value
+    )
+}
+
+fn fmt_pub(public: bool) -> &'static str {
+    if public {
+        "pub "
+    } else {
+        ""
+    }
+}
+
+impl Display for Trait {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let args = self
-            .args
+        let methods = self
+            .methods
             .iter()
-            .map(|(name, ty)| format!("{}: {}", name, ty))
-            .collect::<Vec<String>>()
-            .join(", ");
-        let body = apply_indent(format!("{}", self.body), 2);
+            .map(|sig| format!("{};\n", sig))
+            .collect::<String>();
+        write!(
+            f,
+            "{}trait {} {{\n{}}}\n\n",
+            fmt_pub(self.public),
+            self.name,
+            apply_indent(methods, 2)
+        )
+    }
+}
+
+impl Display for Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "fn {}({}) -> {}",
+            self.name,
+            fmt_params(&self.args),
+            self.ret
+        )
+    }
+}
+
+impl<T: TypeBound> Display for Impl<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "impl ")?;
+        if let Some(trait_) = &self.trait_ {
+            write!(f, "{} for ", trait_)?;
+        }
+        let methods = self
+            .methods
+            .iter()
+            .map(|method| method.to_string())
+            .collect::<String>();
+        write!(f, "{} {{\n{}}}\n\n", self.ty, apply_indent(methods, 2))
+    }
+}
+
+impl Display for Enum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let variants = self
+            .variants
+            .iter()
+            .map(|(name, tys)| match tys.is_empty() {
+                true => format!("{},\n", name),
+                false => format!("{}{},\n", name, Type::Tuple(tys.to_vec())),
+            })
+            .collect::<String>();
+        write!(
+            f,
+            "{}enum {} {{\n{}}}\n\n",
+            fmt_pub(self.public),
+            self.name,
+            apply_indent(variants, 2)
+        )
+    }
+}
+
+impl Display for Alias {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let public = fmt_pub(self.public);
+        write!(f, "{}type {} = {};\n\n", public, self.name, self.ty)
+    }
+}
+
+impl Display for Struct {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if is_tuple(&self.fields) {
+            let tys = self.fields.iter().map(|(_, ty)| ty.clone()).collect();
+            let public = fmt_pub(self.public);
+            return write!(f, "{}struct {}{};\n\n", public, self.name, Type::Tuple(tys));
+        }
+        let fields = self
+            .fields
+            .iter()
+            .map(|(name, ty)| format!("{}: {},\n", name, ty))
+            .collect::<String>();
         write!(
             f,
-            "fn {}({}) -> {} {{\n{}}}\n\n",
-            self.name, args, self.ret, body
+            "{}struct {} {{\n{}}}\n\n",
+            fmt_pub(self.public),
+            self.name,
+            apply_indent(fields, 2)
         )
     }
 }
 
+impl<T: TypeBound> Display for Function<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let args = fmt_params(&self.args);
+        // Only generic functions have angle brackets after their name
+        let name = match &self.generics[..] {
+            [] => self.name.clone(),
+            generics => format!("{}{}", self.name, fmt_angled(generics)),
+        };
+        let body = apply_indent(format!("{}", self.body), 2);
+        write!(f, "{}", fmt_pub(self.public))?;
+        match &self.kind {
+            FuncKind::Func => write!(
+                f,
+                "fn {}({}) -> {} {{\n{}}}\n\n",
+                name, args, self.ret, body
+            ),
+            FuncKind::Yields(frame) => write!(
+                f,
+                "fn {}({}) -> {} yield {} {{\n{}}}\n\n",
+                name, args, self.ret, frame, body
+            ),
+            FuncKind::Coro(resume) => write!(
+                f,
+                "coro {}({})({}) -> {} {{\n{}}}\n\n",
+                name,
+                args,
+                fmt_params(resume),
+                self.ret,
+                body
+            ),
+        }
+    }
+}
+
+fn fmt_params(params: &[(String, Type)]) -> String {
+    params
+        .iter()
+        .map(|(name, ty)| format!("{}: {}", name, ty))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+impl Display for TypeParam {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.bounds.is_empty() {
+            write!(f, ": {}", self.bounds.join(" + "))?;
+        }
+        Ok(())
+    }
+}
+
+fn fmt_angled(items: &[impl Display]) -> String {
+    let items = items
+        .iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<String>>();
+    format!("<{}>", items.join(", "))
+}
+
 impl<T: TypeBound> Display for Sequence<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut it = self.0.iter().peekable();
         while let Some(expr) = it.next() {
             if it.peek().is_none() {
-                write!(f, "{}", &expr)?;
+                write!(f, "{}", expr)?;
             } else {
                 writeln!(f, "{};", expr)?;
             }
@@ -52,6 +248,7 @@ impl<T: TypeBound> Display for Expr<T> {
         match self {
             Self::Value(v) => write!(f, "{}", v),
             Self::Reference(s) => write!(f, "{}", s),
+            Self::Instance { func, types } => write!(f, "{}::{}", func, fmt_angled(types)),
             Self::Unary {
                 op: Operator::TupleIndex(i),
                 rhs,
@@ -64,8 +261,18 @@ impl<T: TypeBound> Display for Expr<T> {
                 Operator::ArrayIndex => {
                     write!(f, "{}[{}]", lhs, rhs)
                 }
+                // Otherwise the `|` would open a closure as the value of the `return` or `break`
+                Operator::BitOr
+                    if matches!(
+                        lhs.expr,
+                        Expr::Return(None) | Expr::Break { value: None, .. }
+                    ) =>
+                {
+                    write!(f, "(({}) {} {})", lhs, op, rhs)
+                }
                 _ => write!(f, "({} {} {})", lhs, op, rhs),
             },
+            Self::CompoundAssign { lhs, op, rhs } => write!(f, "{} {}= {}", lhs, op, rhs),
             Self::Tuple(v) => {
                 // If it's one element, then we need to add a comma to make it a tuple
                 if v.len() == 1 {
@@ -95,6 +302,42 @@ impl<T: TypeBound> Display for Expr<T> {
                 }
                 write!(f, "]")
             }
+            Self::Struct { name, fields } if fields.is_empty() => write!(f, "{} {{}}", name),
+            // A tuple struct is built just like a call
+            Self::Struct { name, fields } if is_tuple(fields) => {
+                let values = fields.iter().map(|(_, value)| value.to_string());
+                write!(f, "{}({})", name, values.collect::<Vec<_>>().join(", "))
+            }
+            Self::Struct { name, fields } => {
+                let fields = fields
+                    .iter()
+                    .map(|(field, value)| format!("{}: {}", field, value))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(f, "{} {{ {} }}", name, fields)
+            }
+            Self::Field { expr, field } => write!(f, "{}.{}", expr, field),
+            Self::Method {
+                receiver,
+                method,
+                args,
+                ..
+            } => write!(f, "{}.{}({})", receiver, method, fmt_list(args)),
+            Self::Variant {
+                enum_,
+                variant,
+                args,
+            } => {
+                write!(f, "{}", fmt_variant(enum_, variant))?;
+                if let Some(args) = args {
+                    write!(f, "({})", fmt_list(args))?;
+                }
+                Ok(())
+            }
+            Self::Match { expr, arms } => {
+                let arms = arms.iter().map(|arm| arm.to_string()).collect::<String>();
+                write!(f, "match {} {{\n{}}}", expr, apply_indent(arms, 2))
+            }
             Self::Let {
                 name,
                 ty,
@@ -102,10 +345,14 @@ impl<T: TypeBound> Display for Expr<T> {
                 mutable,
             } => {
                 let var = if *mutable { "var" } else { "let" };
-                match ty {
-                    Some(ty) => write!(f, "{} {}: {} = {}", var, name, ty, value),
-                    None => write!(f, "{} {} = {}", var, name, value),
+                write!(f, "{} {}", var, name)?;
+                if let Some(ty) = ty {
+                    write!(f, ": {}", ty)?;
+                }
+                if let Some(value) = value {
+                    write!(f, " = {}", value)?;
                 }
+                Ok(())
             }
             Self::If { cond, then, else_ } => {
                 let then = apply_indent(format!("{}", then), 2);
@@ -127,19 +374,142 @@ impl<T: TypeBound> Display for Expr<T> {
                 }
                 write!(f, ")")
             }
-            Self::While { cond, body } => {
+            Self::While { cond, body, label } => {
                 let body = apply_indent(format!("{}", body), 2);
-                write!(f, "while {} {{\n{}}}", cond, body)
+                write!(f, "{}while {} {{\n{}}}", fmt_label(label), cond, body)
+            }
+            Self::For {
+                var,
+                iter,
+                body,
+                label,
+            } => {
+                let body = apply_indent(format!("{}", body), 2);
+                let label = fmt_label(label);
+                write!(f, "{}for {} in {} {{\n{}}}", label, var, iter, body)
+            }
+            Self::Loop { body, label } => {
+                let body = apply_indent(format!("{}", body), 2);
+                write!(f, "{}loop {{\n{}}}", fmt_label(label), body)
+            }
+            Self::Break { label, value } => {
+                write!(f, "break")?;
+                if let Some(label) = label {
+                    write!(f, " '{}", label)?;
+                }
+                if let Some(value) = value {
+                    write!(f, " {}", value)?;
+                }
+                Ok(())
+            }
+            Self::Continue(Some(label)) => write!(f, "continue '{}", label),
+            Self::Continue(None) => write!(f, "continue"),
+            Self::Return(Some(value)) => write!(f, "return {}", value),
+            Self::Return(None) => write!(f, "return"),
+            Self::Yield(value) => write!(f, "yield {}", value),
+            Self::Try(value) => write!(f, "{}?", value),
+            Self::Closure {
+                params,
+                ret,
+                body,
+                moves,
+                ..
+            } => {
+                if *moves {
+                    write!(f, "move ")?;
+                }
+                let params = params
+                    .iter()
+                    .map(|(name, ty)| match ty {
+                        Some(ty) => format!("{}: {}", name, ty),
+                        None => name.clone(),
+                    })
+                    .collect::<Vec<_>>();
+                write!(f, "|{}| ", params.join(", "))?;
+                if let Some(ret) = ret {
+                    write!(f, "-> {} ", ret)?;
+                }
+                write!(f, "{{\n{}}}", apply_indent(body.to_string(), 2))
+            }
+        }
+    }
+}
+
+impl<T: TypeBound> Display for Arm<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.pattern)?;
+        if let Some(guard) = &self.guard {
+            write!(f, " if {}", guard)?;
+        }
+        let body = apply_indent(format!("{}", self.body), 2);
+        writeln!(f, " => {{\n{}}},", body)
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Wildcard => write!(f, "_"),
+            Pattern::Binding(name) => write!(f, "{}", name),
+            // Patterns can't be parenthesized, unlike negative numbers in expressions
+            Pattern::Value(Value::Number(x, suffix)) => write!(f, "{}{}", x, fmt_suffix(suffix)),
+            Pattern::Value(value) => write!(f, "{}", value),
+            Pattern::Variant {
+                enum_,
+                variant,
+                args,
+            } => {
+                write!(f, "{}", fmt_variant(enum_, variant))?;
+                if !args.is_empty() {
+                    write!(f, "({})", fmt_list(args))?;
+                }
+                Ok(())
             }
         }
     }
 }
 
+// The variants of the builtin enums are written without their enum
+fn fmt_variant(enum_: &str, variant: &str) -> String {
+    if builtin_enum(variant) == Some(enum_) {
+        variant.to_owned()
+    } else {
+        format!("{}::{}", enum_, variant)
+    }
+}
+
+fn fmt_label(label: &Option<String>) -> String {
+    label
+        .as_ref()
+        .map_or(String::new(), |label| format!("'{}: ", label))
+}
+
+// A literal whose type isn't known yet is written without a suffix, like it was in the source
+fn fmt_suffix(suffix: &Option<Type>) -> String {
+    match suffix {
+        Some(Type::Var(_)) | None => String::new(),
+        Some(ty) => ty.to_string(),
+    }
+}
+
+fn fmt_list<D: Display>(items: &[D]) -> String {
+    items
+        .iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
 impl Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Value::Number(x) if x < &0 => write!(f, "({})", x),
-            Value::Number(x) => write!(f, "{}", x),
+            Value::Number(x, suffix) if x < &0 => write!(f, "({}{})", x, fmt_suffix(suffix)),
+            Value::Number(x, suffix) => write!(f, "{}{}", x, fmt_suffix(suffix)),
+            // Debug formatting always keeps a fraction or an exponent, so it reads back as a float
+            Value::Float(x, suffix) if x.is_sign_negative() => {
+                write!(f, "({:?}{})", x, fmt_suffix(suffix))
+            }
+            Value::Float(x, suffix) => write!(f, "{:?}{}", x, fmt_suffix(suffix)),
             Value::Bool(x) => write!(f, "{}", x),
             Value::Char(x) => write!(f, "'{}'", x.escape_default()),
             Value::String(x) => write!(f, "\"{}\"", x.escape_default()),
@@ -154,6 +524,7 @@ impl Display for Operator {
             Operator::Sub => write!(f, "-"),
             Operator::Mul => write!(f, "*"),
             Operator::Div => write!(f, "/"),
+            Operator::Rem => write!(f, "%"),
             Operator::Assign => write!(f, "="),
             Operator::Gt => write!(f, ">"),
             Operator::Lt => write!(f, "<"),
@@ -164,8 +535,15 @@ impl Display for Operator {
             Operator::And => write!(f, "&&"),
             Operator::Or => write!(f, "||"),
             Operator::Not => write!(f, "!"),
+            Operator::BitAnd => write!(f, "&"),
+            Operator::BitOr => write!(f, "|"),
+            Operator::BitXor => write!(f, "^"),
+            Operator::Shl => write!(f, "<<"),
+            Operator::Shr => write!(f, ">>"),
             Operator::Ref => write!(f, "&"),
+            Operator::RefMut => write!(f, "&mut "),
             Operator::ArrayIndex => write!(f, "[]"),
+            Operator::Range => write!(f, ".."),
             Operator::TupleIndex(i) => write!(f, ".{}", i),
         }
     }
@@ -173,9 +551,7 @@ impl Display for Operator {
 
 impl Display for Size {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Size::ThirtyTwo => write!(f, "32"),
-        }
+        write!(f, "{}", self.bits())
     }
 }
 
@@ -183,8 +559,14 @@ impl Display for Type {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Type::Signed(size) => write!(f, "i{}", size),
+            Type::Unsigned(size) => write!(f, "u{}", size),
+            Type::Float(size) => write!(f, "f{}", size),
             Type::Bool => write!(f, "bool"),
             Type::Char => write!(f, "char"),
+            Type::Alias(name, _) => write!(f, "{}", name),
+            Type::String => write!(f, "String"),
+            Type::Option(ty) => write!(f, "Option<{}>", ty),
+            Type::Result(ty, err) => write!(f, "Result<{}, {}>", ty, err),
             Type::Tuple(tys) => {
                 let mut s = String::new();
                 for ty in tys {
@@ -195,11 +577,33 @@ impl Display for Type {
                 write!(f, "({})", s)
             }
             Type::Array(ty, size) => write!(f, "[{}; {}]", ty, size),
+            Type::ArrayLen(ty, len) => write!(f, "[{}; {}]", ty, len),
             Type::Function { args, ret } => {
                 write!(f, "{} -> {}", Type::Tuple(args.to_vec()), ret)
             }
-            Type::Reference(ty) => write!(f, "&{}", ty),
-            Type::Mutable(ty) => write!(f, "mut {}", ty),
+            Type::Coro {
+                args,
+                resume,
+                yields,
+            } => write!(
+                f,
+                "coro{}{} -> {}",
+                Type::Tuple(args.to_vec()),
+                Type::Tuple(resume.to_vec()),
+                yields
+            ),
+            Type::Frame { resume, yields } => {
+                write!(f, "frame{} -> {}", Type::Tuple(resume.to_vec()), yields)
+            }
+            Type::Reference(ty, false) => write!(f, "&{}", ty),
+            Type::Reference(ty, true) => write!(f, "&mut {}", ty),
+            Type::Named(name) => write!(f, "{}", name),
+            Type::Range(ty) => write!(f, "range({})", ty),
+            Type::Never => write!(f, "!"),
+            Type::Param(name) => write!(f, "{}", name),
+            Type::Generic { params, ty } => write!(f, "{}{}", fmt_angled(params), ty),
+            // Only seen in errors, before the type is known
+            Type::Var(_) => write!(f, "_"),
         }
     }
 }
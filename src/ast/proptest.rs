@@ -1,8 +1,11 @@
 use proptest::{option, prelude::*};
 
 use crate::{
-    ast::tree::{Expr, Expression, Operator, Sequence, Value},
-    semantics::types::{Size, Type},
+    ast::tree::{
+        Alias, Arm, Enum, Expr, Expression, FuncKind, Global, Impl, Item, Module, Operator,
+        Pattern, Sequence, Signature, Struct, Trait, Value,
+    },
+    semantics::types::{builtin_enum, Size, Type, TypeParam},
 };
 
 use super::tree::{Function, Program};
@@ -13,6 +16,7 @@ fn arb_binary() -> impl Strategy<Value = Operator> {
         Just(Operator::Sub),
         Just(Operator::Mul),
         Just(Operator::Div),
+        Just(Operator::Rem),
         Just(Operator::And),
         Just(Operator::Or),
         Just(Operator::Assign),
@@ -22,7 +26,29 @@ fn arb_binary() -> impl Strategy<Value = Operator> {
         Just(Operator::Lte),
         Just(Operator::Gt),
         Just(Operator::Gte),
+        Just(Operator::BitAnd),
+        Just(Operator::BitOr),
+        Just(Operator::BitXor),
+        Just(Operator::Shl),
+        Just(Operator::Shr),
         Just(Operator::ArrayIndex),
+        Just(Operator::Range),
+    ]
+}
+
+// The operators that can be used in a compound assignment
+fn arb_compound() -> impl Strategy<Value = Operator> {
+    prop_oneof![
+        Just(Operator::Add),
+        Just(Operator::Sub),
+        Just(Operator::Mul),
+        Just(Operator::Div),
+        Just(Operator::Rem),
+        Just(Operator::BitAnd),
+        Just(Operator::BitOr),
+        Just(Operator::BitXor),
+        Just(Operator::Shl),
+        Just(Operator::Shr),
     ]
 }
 
@@ -31,44 +57,155 @@ fn arb_unary() -> impl Strategy<Value = Operator> {
         Just(Operator::Not),
         Just(Operator::Sub),
         Just(Operator::Ref),
+        Just(Operator::RefMut),
         Just(Operator::Mul),
         any::<usize>().prop_map(Operator::TupleIndex),
     ]
 }
 
 fn arb_size() -> impl Strategy<Value = Size> {
-    prop_oneof![Just(Size::ThirtyTwo),]
+    prop_oneof![
+        Just(Size::Eight),
+        Just(Size::Sixteen),
+        Just(Size::ThirtyTwo),
+        Just(Size::SixtyFour),
+    ]
+}
+
+fn arb_int_type() -> impl Strategy<Value = Type> {
+    prop_oneof![
+        arb_size().prop_map(Type::Signed),
+        arb_size().prop_map(Type::Unsigned),
+    ]
+}
+
+fn arb_float_type() -> impl Strategy<Value = Type> {
+    prop_oneof![
+        Just(Type::Float(Size::ThirtyTwo)),
+        Just(Type::Float(Size::SixtyFour)),
+    ]
+}
+
+fn arb_number() -> impl Strategy<Value = Value> {
+    (any::<u64>(), option::of(arb_int_type())).prop_map(|(x, ty)| Value::Number(x.into(), ty))
+}
+
+// Literals can't be negative, infinite or NaN
+fn arb_float() -> impl Strategy<Value = Value> {
+    use prop::num::f64::{NORMAL, POSITIVE, ZERO};
+    (POSITIVE | NORMAL | ZERO, option::of(arb_float_type())).prop_map(|(x, ty)| Value::Float(x, ty))
 }
 
 fn arb_type() -> impl Strategy<Value = Type> {
     let leaf = prop_oneof![
-        arb_size().prop_map(Type::Signed),
+        arb_int_type(),
+        arb_float_type(),
         Just(Type::Bool),
         Just(Type::Char),
+        Just(Type::String),
+        arb_path().prop_map(Type::Named),
     ];
     leaf.prop_recursive(5, 22, 12, |inner| {
         prop_oneof![
             prop::collection::vec(inner.clone(), 1..4).prop_map(Type::Tuple),
             (inner.clone(), any::<usize>()).prop_map(|(ty, size)| Type::Array(Box::new(ty), size)),
-            inner.clone().prop_map(|ty| Type::Reference(Box::new(ty))),
-            (prop::collection::vec(inner.clone(), 1..4), inner).prop_map(|(args, ret)| {
+            (inner.clone(), arb_path()).prop_map(|(ty, len)| Type::ArrayLen(Box::new(ty), len)),
+            inner.clone().prop_map(|ty| Type::Range(Box::new(ty))),
+            inner.clone().prop_map(|ty| Type::Option(Box::new(ty))),
+            (inner.clone(), inner.clone())
+                .prop_map(|(ty, err)| Type::Result(Box::new(ty), Box::new(err))),
+            (inner.clone(), any::<bool>())
+                .prop_map(|(ty, mutable)| Type::Reference(Box::new(ty), mutable)),
+            (prop::collection::vec(inner.clone(), 1..4), inner.clone()).prop_map(|(args, ret)| {
                 Type::Function {
                     args,
                     ret: Box::new(ret),
                 }
             }),
+            (
+                prop::collection::vec(inner.clone(), 0..4),
+                prop::collection::vec(inner.clone(), 0..4),
+                inner.clone()
+            )
+                .prop_map(|(args, resume, yields)| Type::Coro {
+                    args,
+                    resume,
+                    yields: Box::new(yields),
+                }),
+            (prop::collection::vec(inner.clone(), 0..4), inner).prop_map(|(resume, yields)| {
+                Type::Frame {
+                    resume,
+                    yields: Box::new(yields),
+                }
+            }),
         ]
     })
 }
 
+fn arb_frame() -> impl Strategy<Value = Type> {
+    (prop::collection::vec(arb_type(), 0..4), arb_type()).prop_map(|(resume, yields)| Type::Frame {
+        resume,
+        yields: Box::new(yields),
+    })
+}
+
+fn arb_params() -> impl Strategy<Value = Vec<(String, Type)>> {
+    prop::collection::vec(("[A-Z][a-zA-Z0-9]*", arb_type()), 0..4)
+}
+
+fn arb_kind() -> impl Strategy<Value = FuncKind> {
+    prop_oneof![
+        Just(FuncKind::Func),
+        arb_frame().prop_map(FuncKind::Yields),
+        arb_params().prop_map(FuncKind::Coro),
+    ]
+}
+
 fn arb_opt_type() -> impl Strategy<Value = Option<Type>> {
     option::weighted(0.8, arb_type())
 }
 
+fn arb_label() -> impl Strategy<Value = Option<String>> {
+    option::of("[a-z][a-z0-9]+")
+}
+
+// An enum along with one of its variants, which can also be one of the builtin enums
+fn arb_variant() -> impl Strategy<Value = (String, String)> {
+    prop_oneof![
+        (arb_path(), "[A-Z][a-zA-Z0-9]*"),
+        prop_oneof![Just("None"), Just("Some"), Just("Ok"), Just("Err")].prop_map(|variant| (
+            builtin_enum(variant).unwrap().to_owned(),
+            variant.to_owned()
+        )),
+    ]
+}
+
+fn arb_pattern() -> impl Strategy<Value = Pattern> {
+    let leaf = prop_oneof![
+        Just(Pattern::Wildcard),
+        "[A-Z][a-zA-Z0-9]*".prop_map(Pattern::Binding),
+        arb_number().prop_map(Pattern::Value),
+        any::<bool>().prop_map(|x| Pattern::Value(x.into())),
+        "[A-Z]".prop_map(|x| Pattern::Value(Value::Char(x.chars().next().unwrap()))),
+    ];
+    leaf.prop_recursive(4, 16, 4, |inner| {
+        (arb_variant(), prop::collection::vec(inner, 0..4)).prop_map(|((enum_, variant), args)| {
+            Pattern::Variant {
+                enum_,
+                variant,
+                args,
+            }
+        })
+    })
+}
+
 fn arb_expr() -> impl Strategy<Value = Expression<()>> {
     let leaf = prop_oneof![
-        any::<u64>().prop_map(|x| Expr::Value(x.into())),
+        arb_number().prop_map(Expr::Value),
+        arb_float().prop_map(Expr::Value),
         "[A-Z][a-zA-Z0-9]*".prop_map(Expr::Reference),
+        (arb_path(), prop::collection::vec(arb_type(), 0..3))
+            .prop_map(|(func, types)| Expr::Instance { func, types }),
         any::<bool>().prop_map(|x| Expr::Value(x.into())),
         "[A-Z][a-zA-Z0-9]*".prop_map(|x| Expr::Value(Value::String(x))),
         "[A-Z][a-zA-Z0-9]+".prop_map(|x| Expr::Value(Value::Char(x.chars().next().unwrap()))),
@@ -87,17 +224,24 @@ fn arb_expr() -> impl Strategy<Value = Expression<()>> {
                     rhs: Box::new(rhs),
                 }
             }),
+            (inner.clone(), arb_compound(), inner.clone()).prop_map(|(lhs, op, rhs)| {
+                Expr::CompoundAssign {
+                    lhs: Box::new(lhs),
+                    op,
+                    rhs: Box::new(rhs),
+                }
+            }),
             (
                 "[A-Z][a-zA-Z0-9]*",
                 arb_opt_type(),
-                inner.clone(),
+                option::of(inner.clone()),
                 any::<bool>()
             )
                 .prop_map(|(name, ty, value, mutable)| {
                     Expr::Let {
                         name,
                         ty,
-                        value: Box::new(value),
+                        value: value.map(Box::new),
                         mutable,
                     }
                 }),
@@ -125,14 +269,105 @@ fn arb_expr() -> impl Strategy<Value = Expression<()>> {
             ),
             (
                 inner.clone(),
-                prop::collection::vec(inner, 1..10).prop_map(Sequence)
+                prop::collection::vec(inner.clone(), 1..10).prop_map(Sequence),
+                arb_label()
             )
-                .prop_map(|(cond, body)| {
+                .prop_map(|(cond, body, label)| {
                     Expr::While {
                         cond: Box::new(cond),
                         body,
+                        label,
                     }
                 }),
+            (
+                prop::collection::vec(inner.clone(), 1..10).prop_map(Sequence),
+                arb_label()
+            )
+                .prop_map(|(body, label)| Expr::Loop { body, label }),
+            (arb_label(), option::of(inner.clone())).prop_map(|(label, value)| Expr::Break {
+                label,
+                value: value.map(Box::new)
+            }),
+            arb_label().prop_map(Expr::Continue),
+            option::of(inner.clone()).prop_map(|value| Expr::Return(value.map(Box::new))),
+            (
+                arb_path(),
+                prop::collection::vec(("[A-Z][a-zA-Z0-9]*", inner.clone()), 0..4)
+            )
+                .prop_map(|(name, fields)| Expr::Struct { name, fields }),
+            (inner.clone(), "[A-Z][a-zA-Z0-9]*").prop_map(|(expr, field)| Expr::Field {
+                expr: Box::new(expr),
+                field
+            }),
+            (
+                inner.clone(),
+                "[A-Z][a-zA-Z0-9]*",
+                prop::collection::vec(inner.clone(), 0..4)
+            )
+                .prop_map(|(receiver, method, args)| Expr::Method {
+                    receiver: Box::new(receiver),
+                    method,
+                    args,
+                    trait_: None
+                }),
+            (
+                arb_variant(),
+                option::of(prop::collection::vec(inner.clone(), 0..4))
+            )
+                .prop_map(|((enum_, variant), args)| Expr::Variant {
+                    enum_,
+                    variant,
+                    args
+                }),
+            (
+                inner.clone(),
+                prop::collection::vec(
+                    (
+                        arb_pattern(),
+                        option::weighted(0.3, inner.clone()),
+                        prop::collection::vec(inner.clone(), 1..4).prop_map(Sequence)
+                    )
+                        .prop_map(|(pattern, guard, body)| Arm {
+                            pattern,
+                            guard,
+                            body
+                        }),
+                    1..4
+                )
+            )
+                .prop_map(|(expr, arms)| Expr::Match {
+                    expr: Box::new(expr),
+                    arms
+                }),
+            (
+                "[A-Z][a-zA-Z0-9]*",
+                inner.clone(),
+                prop::collection::vec(inner.clone(), 1..10).prop_map(Sequence),
+                arb_label()
+            )
+                .prop_map(|(var, iter, body, label)| {
+                    Expr::For {
+                        var,
+                        iter: Box::new(iter),
+                        body,
+                        label,
+                    }
+                }),
+            (
+                prop::collection::vec(("[A-Z][a-zA-Z0-9]*", arb_opt_type()), 0..4),
+                option::of(arb_type()),
+                prop::collection::vec(inner.clone(), 1..4).prop_map(Sequence),
+                any::<bool>()
+            )
+                .prop_map(|(params, ret, body, moves)| Expr::Closure {
+                    params,
+                    ret,
+                    body,
+                    moves,
+                    captures: vec![]
+                }),
+            inner.clone().prop_map(|value| Expr::Yield(Box::new(value))),
+            inner.prop_map(|value| Expr::Try(Box::new(value))),
         ]
         .prop_map(Expression::from)
     })
@@ -143,9 +378,10 @@ fn arb_seq() -> impl Strategy<Value = Sequence<()>> {
 }
 
 fn arb_func() -> impl Strategy<Value = Function<()>> {
-    // name, args, ret and body
+    // name, type parameters, args, ret and body
     (
         "[A-Z][a-zA-Z0-9]*",
+        prop::collection::vec(arb_type_param(), 0..3),
         (
             prop::collection::vec("[A-Z][a-zA-Z0-9]*", 1..10),
             arb_type(),
@@ -158,17 +394,137 @@ fn arb_func() -> impl Strategy<Value = Function<()>> {
             }),
         arb_type(),
         arb_seq(),
+        arb_kind(),
+        any::<bool>(),
     )
-        .prop_map(|(name, args, ret, body)| Function {
+        .prop_map(|(name, generics, args, ret, body, kind, public)| Function {
             name,
+            public,
+            generics,
             args,
             ret,
             body,
+            kind,
+        })
+}
+
+fn arb_type_param() -> impl Strategy<Value = TypeParam> {
+    ("[A-Z][a-zA-Z0-9]*", prop::collection::vec(arb_path(), 0..3))
+        .prop_map(|(name, bounds)| TypeParam { name, bounds })
+}
+
+fn arb_trait() -> impl Strategy<Value = Trait> {
+    let sig = ("[A-Z][a-zA-Z0-9]*", arb_params(), arb_type())
+        .prop_map(|(name, args, ret)| Signature { name, args, ret });
+    (
+        "[A-Z][a-zA-Z0-9]*",
+        any::<bool>(),
+        prop::collection::vec(sig, 0..4),
+    )
+        .prop_map(|(name, public, methods)| Trait {
+            name,
+            public,
+            methods,
         })
 }
 
+fn arb_impl() -> impl Strategy<Value = Impl<()>> {
+    (
+        option::of(arb_path()),
+        arb_type(),
+        prop::collection::vec(arb_func(), 0..3),
+    )
+        .prop_map(|(trait_, ty, methods)| Impl {
+            trait_,
+            ty,
+            methods,
+        })
+}
+
+fn arb_struct() -> impl Strategy<Value = Struct> {
+    // The fields of a tuple struct are named by their positions
+    let positional = prop::collection::vec(arb_type(), 1..4).prop_map(|tys| {
+        tys.into_iter()
+            .enumerate()
+            .map(|(i, ty)| (i.to_string(), ty))
+            .collect()
+    });
+    let fields = prop_oneof![arb_params(), positional];
+    ("[A-Z][a-zA-Z0-9]*", any::<bool>(), fields).prop_map(|(name, public, fields)| Struct {
+        name,
+        public,
+        fields,
+    })
+}
+
+fn arb_enum() -> impl Strategy<Value = Enum> {
+    let variant = ("[A-Z][a-zA-Z0-9]*", prop::collection::vec(arb_type(), 0..4));
+    (
+        "[A-Z][a-zA-Z0-9]*",
+        any::<bool>(),
+        prop::collection::vec(variant, 0..4),
+    )
+        .prop_map(|(name, public, variants)| Enum {
+            name,
+            public,
+            variants,
+        })
+}
+
+fn arb_alias() -> impl Strategy<Value = Alias> {
+    ("[A-Z][a-zA-Z0-9]*", any::<bool>(), arb_type()).prop_map(|(name, public, ty)| Alias {
+        name,
+        public,
+        ty,
+    })
+}
+
+fn arb_global() -> impl Strategy<Value = Global<()>> {
+    ("[A-Z][a-zA-Z0-9]*", any::<bool>(), arb_type(), arb_expr()).prop_map(
+        |(name, public, ty, value)| Global {
+            name,
+            public,
+            ty,
+            value,
+        },
+    )
+}
+
+fn arb_item() -> impl Strategy<Value = Item<()>> {
+    let leaf = prop_oneof![
+        arb_func().prop_map(Item::Func),
+        arb_struct().prop_map(Item::Struct),
+        arb_enum().prop_map(Item::Enum),
+        arb_alias().prop_map(Item::Alias),
+        arb_trait().prop_map(Item::Trait),
+        arb_impl().prop_map(Item::Impl),
+        arb_global().prop_map(Item::Const),
+        arb_global().prop_map(Item::Static),
+        arb_path().prop_map(Item::Use),
+    ];
+    leaf.prop_recursive(2, 8, 4, |inner| {
+        (
+            "[A-Z][a-zA-Z0-9]*",
+            any::<bool>(),
+            option::of(prop::collection::vec(inner, 0..4)),
+        )
+            .prop_map(|(name, public, items)| {
+                Item::Mod(Module {
+                    name,
+                    public,
+                    items,
+                })
+            })
+    })
+}
+
+// A name, or a path to it through modules and types, like `Geo::Point`
+fn arb_path() -> impl Strategy<Value = String> {
+    "[A-Z][a-zA-Z0-9]*(::[A-Z][a-zA-Z0-9]*){0,2}"
+}
+
 pub fn arb_prgm() -> impl Strategy<Value = Program<()>> {
-    prop::collection::vec(arb_func(), 1..10).prop_map(Program)
+    prop::collection::vec(arb_item(), 1..10).prop_map(Program)
 }
 
 #[cfg(test)]
@@ -1,17 +1,220 @@
 use crate::{
     parser::error::Error,
-    semantics::types::{Size, Type},
+    semantics::types::{Size, Type, TypeParam},
 };
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct Program<T: TypeBound>(pub Vec<Function<T>>);
+pub struct Program<T: TypeBound>(pub Vec<Item<T>>);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Item<T: TypeBound> {
+    Func(Function<T>),
+    Struct(Struct),
+    Enum(Enum),
+    Alias(Alias),
+    Trait(Trait),
+    Impl(Impl<T>),
+    // Evaluated before the program runs, and copied wherever it's used
+    Const(Global<T>),
+    // Evaluated before the program runs, and kept in the same place for all of it
+    Static(Global<T>),
+    Mod(Module<T>),
+    // A path to an item, which can then be used by its last name
+    Use(String),
+}
+
+// The items are only missing for a `mod name;` whose file hasn't been loaded yet
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module<T: TypeBound> {
+    pub name: String,
+    pub public: bool,
+    pub items: Option<Vec<Item<T>>>,
+}
+
+// The fields of a tuple struct, like `struct Meters(i32);`, are named by their positions
+#[derive(Debug, Clone, PartialEq)]
+pub struct Struct {
+    pub name: String,
+    pub public: bool,
+    pub fields: Vec<(String, Type)>,
+}
+
+// Whether the fields are the positional fields of a tuple struct
+pub fn is_tuple<X>(fields: &[(String, X)]) -> bool {
+    !fields.is_empty()
+        && fields
+            .iter()
+            .enumerate()
+            .all(|(i, (name, _))| *name == i.to_string())
+}
+
+// Each variant of an enum carries a (possibly empty) tuple of values
+#[derive(Debug, Clone, PartialEq)]
+pub struct Enum {
+    pub name: String,
+    pub public: bool,
+    pub variants: Vec<(String, Vec<Type>)>,
+}
+
+// Another name for a type, which can be used wherever the type can
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alias {
+    pub name: String,
+    pub public: bool,
+    pub ty: Type,
+}
+
+// A constant or static, whose value has to be known without running the program
+#[derive(Debug, Clone, PartialEq)]
+pub struct Global<T: TypeBound> {
+    pub name: String,
+    pub public: bool,
+    pub ty: Type,
+    pub value: Expression<T>,
+}
+
+// The methods a type needs to implement a trait, where `Self` stands for that type
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trait {
+    pub name: String,
+    pub public: bool,
+    pub methods: Vec<Signature>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    pub name: String,
+    pub args: Vec<(String, Type)>,
+    pub ret: Type,
+}
+
+// Functions that belong to a type, implementing a trait when one is given.
+// The ones whose first parameter is `self` can be called as methods.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Impl<T: TypeBound> {
+    pub trait_: Option<String>,
+    pub ty: Type,
+    pub methods: Vec<Function<T>>,
+}
+
+// The name a function in an impl is known by outside of it, like `Point::new`
+pub fn method_path(ty: &Type, method: &str) -> String {
+    format!("{}::{}", ty, method)
+}
+
+// The name of a function in an impl of a trait, like `<Point as Shape>::area`,
+// since a type can have methods with the same name from its other impls
+pub fn trait_method_path(ty: &Type, trait_: &str, method: &str) -> String {
+    format!("<{} as {}>::{}", ty, trait_, method)
+}
+
+impl<T: TypeBound> Impl<T> {
+    pub fn path(&self, method: &str) -> String {
+        match &self.trait_ {
+            Some(trait_) => trait_method_path(&self.ty, trait_, method),
+            None => method_path(&self.ty, method),
+        }
+    }
+}
+
+impl<T: TypeBound> Program<T> {
+    pub fn funcs(&self) -> impl Iterator<Item = &Function<T>> {
+        self.0.iter().filter_map(|item| match item {
+            Item::Func(func) => Some(func),
+            _ => None,
+        })
+    }
+
+    pub fn structs(&self) -> impl Iterator<Item = &Struct> {
+        self.0.iter().filter_map(|item| match item {
+            Item::Struct(def) => Some(def),
+            _ => None,
+        })
+    }
+
+    pub fn enums(&self) -> impl Iterator<Item = &Enum> {
+        self.0.iter().filter_map(|item| match item {
+            Item::Enum(def) => Some(def),
+            _ => None,
+        })
+    }
+
+    pub fn aliases(&self) -> impl Iterator<Item = &Alias> {
+        self.0.iter().filter_map(|item| match item {
+            Item::Alias(def) => Some(def),
+            _ => None,
+        })
+    }
+
+    pub fn traits(&self) -> impl Iterator<Item = &Trait> {
+        self.0.iter().filter_map(|item| match item {
+            Item::Trait(def) => Some(def),
+            _ => None,
+        })
+    }
+
+    pub fn impls(&self) -> impl Iterator<Item = &Impl<T>> {
+        self.0.iter().filter_map(|item| match item {
+            Item::Impl(def) => Some(def),
+            _ => None,
+        })
+    }
+
+    // The constants along with the statics
+    pub fn globals(&self) -> impl Iterator<Item = &Global<T>> {
+        self.0.iter().filter_map(|item| match item {
+            Item::Const(def) | Item::Static(def) => Some(def),
+            _ => None,
+        })
+    }
+
+    pub fn statics(&self) -> impl Iterator<Item = &Global<T>> {
+        self.0.iter().filter_map(|item| match item {
+            Item::Static(def) => Some(def),
+            _ => None,
+        })
+    }
+
+    // The functions in impls, named by their paths
+    pub fn methods(&self) -> impl Iterator<Item = Function<T>> + '_ {
+        self.impls().flat_map(|def| {
+            def.methods.iter().map(|method| Function {
+                name: def.path(&method.name),
+                ..method.clone()
+            })
+        })
+    }
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Function<T: TypeBound> {
     pub name: String,
+    // Whether it can be used from outside of the module it's declared in
+    pub public: bool,
+    // The type parameters, which make the function generic when there are any
+    pub generics: Vec<TypeParam>,
     pub args: Vec<(String, Type)>,
     pub ret: Type,
     pub body: Sequence<T>,
+    pub kind: FuncKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FuncKind {
+    Func,
+    // A function that yields into the frame of the coroutine it's called from
+    Yields(Type),
+    // A coroutine, along with the parameters it receives when first resumed
+    Coro(Vec<(String, Type)>),
+}
+
+// A variable that a closure uses from the scope it's created in
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capture {
+    pub name: String,
+    pub ty: Type,
+    // Whether the closure assigns to it or borrows it mutably
+    pub mutable: bool,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -23,6 +226,87 @@ impl From<Expr<()>> for Expression<()> {
     }
 }
 
+impl<T: TypeBound> Expr<T> {
+    // The expressions directly inside of this one, including the ones in its blocks
+    pub fn children_mut(&mut self) -> Vec<&mut Expression<T>> {
+        fn seq<T: TypeBound>(seq: &mut Sequence<T>) -> std::slice::IterMut<'_, Expression<T>> {
+            seq.0.iter_mut()
+        }
+        let once = std::iter::once;
+        match self {
+            Expr::Unary { rhs, .. } => vec![&mut **rhs],
+            Expr::Binary { lhs, rhs, .. } | Expr::CompoundAssign { lhs, rhs, .. } => {
+                vec![&mut **lhs, &mut **rhs]
+            }
+            Expr::Tuple(exprs) | Expr::Array(exprs) => exprs.iter_mut().collect(),
+            Expr::Struct { fields, .. } => fields.iter_mut().map(|(_, value)| value).collect(),
+            Expr::Field { expr, .. } | Expr::Yield(expr) | Expr::Try(expr) => vec![&mut **expr],
+            Expr::Method { receiver, args, .. } => once(&mut **receiver).chain(args).collect(),
+            Expr::Variant { args, .. } => args.iter_mut().flatten().collect(),
+            Expr::Let { value, .. } => value.iter_mut().map(|value| &mut **value).collect(),
+            Expr::If { cond, then, else_ } => once(&mut **cond)
+                .chain(seq(then))
+                .chain(else_.iter_mut().flat_map(seq))
+                .collect(),
+            Expr::Call { func, args } => once(&mut **func).chain(args).collect(),
+            Expr::While { cond, body, .. } => once(&mut **cond).chain(seq(body)).collect(),
+            Expr::For { iter, body, .. } => once(&mut **iter).chain(seq(body)).collect(),
+            Expr::Loop { body, .. } | Expr::Closure { body, .. } => seq(body).collect(),
+            Expr::Break { value, .. } | Expr::Return(value) => {
+                value.iter_mut().map(|value| &mut **value).collect()
+            }
+            Expr::Match { expr, arms } => once(&mut **expr)
+                .chain(
+                    arms.iter_mut()
+                        .flat_map(|arm| arm.guard.iter_mut().chain(arm.body.0.iter_mut())),
+                )
+                .collect(),
+            Expr::Value(_) | Expr::Reference(_) | Expr::Instance { .. } | Expr::Continue(_) => {
+                vec![]
+            }
+        }
+    }
+
+    pub fn children(&self) -> Vec<&Expression<T>> {
+        fn seq<T: TypeBound>(seq: &Sequence<T>) -> std::slice::Iter<'_, Expression<T>> {
+            seq.0.iter()
+        }
+        let once = std::iter::once;
+        match self {
+            Expr::Unary { rhs, .. } => vec![&**rhs],
+            Expr::Binary { lhs, rhs, .. } | Expr::CompoundAssign { lhs, rhs, .. } => {
+                vec![&**lhs, &**rhs]
+            }
+            Expr::Tuple(exprs) | Expr::Array(exprs) => exprs.iter().collect(),
+            Expr::Struct { fields, .. } => fields.iter().map(|(_, value)| value).collect(),
+            Expr::Field { expr, .. } | Expr::Yield(expr) | Expr::Try(expr) => vec![&**expr],
+            Expr::Method { receiver, args, .. } => once(&**receiver).chain(args).collect(),
+            Expr::Variant { args, .. } => args.iter().flatten().collect(),
+            Expr::Let { value, .. } => value.iter().map(|value| &**value).collect(),
+            Expr::If { cond, then, else_ } => once(&**cond)
+                .chain(seq(then))
+                .chain(else_.iter().flat_map(seq))
+                .collect(),
+            Expr::Call { func, args } => once(&**func).chain(args).collect(),
+            Expr::While { cond, body, .. } => once(&**cond).chain(seq(body)).collect(),
+            Expr::For { iter, body, .. } => once(&**iter).chain(seq(body)).collect(),
+            Expr::Loop { body, .. } | Expr::Closure { body, .. } => seq(body).collect(),
+            Expr::Break { value, .. } | Expr::Return(value) => {
+                value.iter().map(|value| &**value).collect()
+            }
+            Expr::Match { expr, arms } => once(&**expr)
+                .chain(
+                    arms.iter()
+                        .flat_map(|arm| arm.guard.iter().chain(arm.body.0.iter())),
+                )
+                .collect(),
+            Expr::Value(_) | Expr::Reference(_) | Expr::Instance { .. } | Expr::Continue(_) => {
+                vec![]
+            }
+        }
+    }
+}
+
 // TODO: It might be worth making this generic over the type of the expression,
 // so types can initially be optional, and then be inferred later.
 #[derive(Debug, Clone, PartialEq)]
@@ -42,13 +326,50 @@ pub enum Expr<T: TypeBound> {
         op: Operator,
         rhs: Box<Expression<T>>,
     },
+    // Applies the operator to a place and assigns the result back to it, like `x += 1`,
+    // which only evaluates the place once
+    CompoundAssign {
+        lhs: Box<Expression<T>>,
+        op: Operator,
+        rhs: Box<Expression<T>>,
+    },
     Value(Value),
     Tuple(Vec<Expression<T>>),
     Array(Vec<Expression<T>>),
+    // A struct literal, with its fields in the order they're written
+    Struct {
+        name: String,
+        fields: Vec<(String, Expression<T>)>,
+    },
+    Field {
+        expr: Box<Expression<T>>,
+        field: String,
+    },
+    // Calls a function from an impl of the receiver's type, which gets the receiver as `self`
+    Method {
+        receiver: Box<Expression<T>>,
+        method: String,
+        args: Vec<Expression<T>>,
+        // The trait the method comes from, found during type inference when it isn't from an inherent impl
+        trait_: Option<String>,
+    },
+    // Constructs a variant of an enum, written `Enum::Variant(args)`. The arguments are None
+    // without parentheses, which for a function from an impl or module is the function itself.
+    Variant {
+        enum_: String,
+        variant: String,
+        args: Option<Vec<Expression<T>>>,
+    },
     Reference(String),
+    // A generic function with its type parameters given explicitly, like `id::<i32>`
+    Instance {
+        func: String,
+        types: Vec<Type>,
+    },
+    // Without a value, the variable has to be assigned before it's used
     Let {
         name: String,
-        value: Box<Expression<T>>,
+        value: Option<Box<Expression<T>>>,
         ty: Option<Type>,
         mutable: bool,
     },
@@ -61,15 +382,77 @@ pub enum Expr<T: TypeBound> {
         func: Box<Expression<T>>,
         args: Vec<Expression<T>>,
     },
+    // Every loop can have a label, so that breaks and continues can refer to an outer loop
     While {
         cond: Box<Expression<T>>,
         body: Sequence<T>,
+        label: Option<String>,
+    },
+    // Loops over a range or the elements of an array, binding each one to the variable
+    For {
+        var: String,
+        iter: Box<Expression<T>>,
+        body: Sequence<T>,
+        label: Option<String>,
+    },
+    // Loops until it's broken out of, evaluating to the value it's broken with
+    Loop {
+        body: Sequence<T>,
+        label: Option<String>,
+    },
+    Break {
+        label: Option<String>,
+        value: Option<Box<Expression<T>>>,
+    },
+    Continue(Option<String>),
+    // Leaves the current function, with unit if there's no value
+    Return(Option<Box<Expression<T>>>),
+    Yield(Box<Expression<T>>),
+    // The `?` after an option or result, which unwraps it or returns its `None` or `Err`
+    Try(Box<Expression<T>>),
+    // An anonymous function, which captures by reference the variables it uses, or by value with `move`
+    Closure {
+        // The types of the parameters and the return type are filled in during type inference
+        // when they're left out
+        params: Vec<(String, Option<Type>)>,
+        ret: Option<Type>,
+        body: Sequence<T>,
+        moves: bool,
+        // Found during type inference, so they're empty before it
+        captures: Vec<Capture>,
+    },
+    Match {
+        expr: Box<Expression<T>>,
+        arms: Vec<Arm<T>>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arm<T: TypeBound> {
+    pub pattern: Pattern,
+    pub guard: Option<Expression<T>>,
+    pub body: Sequence<T>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    Wildcard,
+    Binding(String),
+    Value(Value),
+    Variant {
+        enum_: String,
+        variant: String,
+        args: Vec<Pattern>,
     },
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
-    Number(i64),
+    // An integer, along with the type it's suffixed with, like `5u8`. Type inference fills in the
+    // type of the ones without a suffix, from the context they're used in.
+    Number(i128, Option<Type>),
+    // A floating-point number, which is an f64 unless it's suffixed or used as an f32
+    Float(f64, Option<Type>),
     Bool(bool),
     Char(char),
     String(String),
@@ -81,6 +464,7 @@ pub enum Operator {
     Sub,
     Mul, // if it's unary, it's a deref
     Div,
+    Rem,
     Assign,
     Gt,
     Lt,
@@ -91,10 +475,19 @@ pub enum Operator {
     And,
     Or,
     Not,
+    // Bitwise operators on integers, where `&`, `|` and `^` also work on booleans
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
     // Fun pointer stuff
     TupleIndex(usize),
     ArrayIndex,
     Ref,
+    RefMut,
+    // A range of integers, from the start up to but excluding the end
+    Range,
 }
 
 impl Operator {
@@ -104,6 +497,7 @@ impl Operator {
             ['-'] => Operator::Sub,
             ['*'] => Operator::Mul,
             ['/'] => Operator::Div,
+            ['%'] => Operator::Rem,
             ['='] => Operator::Assign,
             ['>'] => Operator::Gt,
             ['<'] => Operator::Lt,
@@ -114,19 +508,46 @@ impl Operator {
             ['&', '&'] => Operator::And,
             ['|', '|'] => Operator::Or,
             ['!'] => Operator::Not,
-            ['&'] => Operator::Ref,
+            ['&'] => Operator::BitAnd,
+            ['|'] => Operator::BitOr,
+            ['^'] => Operator::BitXor,
+            ['<', '<'] => Operator::Shl,
+            ['>', '>'] => Operator::Shr,
+            ['.', '.'] => Operator::Range,
             _ => return Err(Error::InvalidOperator(op.iter().collect())),
         })
     }
 
+    // An operator before its operand, where an `&` takes a reference
+    pub fn from_unary(op: &[char]) -> Result<Self, Error> {
+        match Self::from(op)? {
+            Operator::BitAnd => Ok(Operator::Ref),
+            op => Ok(op),
+        }
+    }
+
+    // The operator of a compound assignment like `+=`, which is followed by an `=`
+    pub fn compound(op: &[char]) -> Option<Self> {
+        let [op @ .., '='] = op else {
+            return None;
+        };
+        match Self::from(op) {
+            Ok(op) if op.is_arith() || op.is_bitwise() => Some(op),
+            _ => None,
+        }
+    }
+
     fn is_binary(&self) -> bool {
-        !matches!(self, Self::Not | Self::TupleIndex(_) | Self::Ref)
+        !matches!(
+            self,
+            Self::Not | Self::TupleIndex(_) | Self::Ref | Self::RefMut
+        )
     }
 
     fn is_unary(&self) -> bool {
         matches!(
             self,
-            Self::Not | Self::Sub | Self::TupleIndex(_) | Self::Ref | Operator::Mul
+            Self::Not | Self::Sub | Self::TupleIndex(_) | Self::Ref | Self::RefMut | Operator::Mul
         )
     }
 
@@ -150,7 +571,7 @@ impl Operator {
         match self {
             Self::Not => 0,
             Self::Add | Self::Sub => 1,
-            Self::Mul | Self::Div => 2,
+            Self::Mul | Self::Div | Self::Rem => 2,
             x if x.is_comparison() => 3,
             x if x.is_logical() => 4,
             _ => 5,
@@ -162,12 +583,27 @@ impl Operator {
             x if x.is_arith() => 1,
             x if x.is_comparison() => 1,
             x if x.is_logical() => 1,
+            x if x.is_bitwise() => 1,
             _ => 0,
         }
     }
 
     pub fn is_arith(&self) -> bool {
-        matches!(self, Self::Add | Self::Sub | Self::Mul | Self::Div)
+        matches!(
+            self,
+            Self::Add | Self::Sub | Self::Mul | Self::Div | Self::Rem
+        )
+    }
+
+    pub fn is_bitwise(&self) -> bool {
+        matches!(
+            self,
+            Self::BitAnd | Self::BitOr | Self::BitXor | Self::Shl | Self::Shr
+        )
+    }
+
+    pub fn is_shift(&self) -> bool {
+        matches!(self, Self::Shl | Self::Shr)
     }
 
     pub fn is_comparison(&self) -> bool {
@@ -187,7 +623,10 @@ impl TryInto<Size> for u8 {
 
     fn try_into(self) -> Result<Size, Self::Error> {
         Ok(match self {
+            8 => Size::Eight,
+            16 => Size::Sixteen,
             32 => Size::ThirtyTwo,
+            64 => Size::SixtyFour,
             _ => return Err(Error::InvalidSize(self)),
         })
     }
@@ -195,7 +634,7 @@ impl TryInto<Size> for u8 {
 
 impl From<u64> for Value {
     fn from(val: u64) -> Self {
-        Value::Number(val as i64)
+        Value::Number(val.into(), None)
     }
 }
 
@@ -208,7 +647,7 @@ impl From<bool> for Value {
 // These are the types that programs can take up. They can either have no type assigned to them, or (after type inference) they need to have a type assigned to them.
 pub trait TypeBound
 where
-    Self: Sized + Clone + PartialEq + std::fmt::Debug,
+    Self: Sized + Clone + PartialEq + std::fmt::Debug + 'static,
 {
 }
 
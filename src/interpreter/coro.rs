@@ -0,0 +1,82 @@
+use std::{cell::RefCell, fmt::Debug, rc::Rc};
+
+use super::{env::Env, error::Error, run::run_func, value::Value};
+use crate::ast::tree::{Function, TypeBound};
+use crate::semantics::types::Type;
+
+use anyhow::Error as AnyError;
+use corosensei::{stack::DefaultStack, Coroutine, CoroutineResult};
+
+// Each coroutine runs on its own stack, so that functions it calls can yield as well
+const STACK_SIZE: usize = 8 << 20;
+
+// Coroutines are resumed with a list of values, and yield a single value back
+pub type Yielder<T> = corosensei::Yielder<Vec<Value<T>>, Value<T>>;
+type Stack<T> = Coroutine<Vec<Value<T>>, Value<T>, Result<Value<T>, AnyError>>;
+
+// A started coroutine, shared between every copy of the value
+#[derive(Clone)]
+pub struct Frame<T: TypeBound> {
+    stack: Rc<RefCell<Stack<T>>>,
+    ty: Type,
+}
+
+impl<T: TypeBound> Frame<T> {
+    // Creates a new stack for the coroutine, which waits to be resumed before running its body
+    pub fn start(
+        coro: &Function<T>,
+        resume: &[(String, Type)],
+        env: Env<'static, T>,
+    ) -> Result<Self, AnyError> {
+        let ty = Type::Frame {
+            resume: resume.iter().map(|(_, ty)| ty).cloned().collect(),
+            yields: Box::new(coro.ret.clone()),
+        };
+        let coro = coro.clone();
+        let resume = resume.to_vec();
+        let stack = DefaultStack::new(STACK_SIZE)?;
+        let stack = Coroutine::with_stack(stack, move |yielder: &Yielder<T>, input| {
+            let mut env = env.on_stack(yielder);
+            env.bind(&resume, input);
+            run_func(&coro, env)
+        });
+        Ok(Self {
+            stack: Rc::new(RefCell::new(stack)),
+            ty,
+        })
+    }
+
+    // Runs the coroutine until it yields its next value
+    pub fn resume(&self, args: Vec<Value<T>>) -> Result<Value<T>, AnyError> {
+        let mut stack = self
+            .stack
+            .try_borrow_mut()
+            .map_err(|_| Error::CoroutineRunning)?;
+        if stack.done() {
+            Err(Error::CoroutineFinished)?
+        }
+        match stack.resume(args) {
+            CoroutineResult::Yield(val) => Ok(val),
+            CoroutineResult::Return(res) => {
+                res?;
+                Err(Error::CoroutineFinished)?
+            }
+        }
+    }
+
+    pub fn type_of(&self) -> Type {
+        self.ty.clone()
+    }
+}
+
+impl<T: TypeBound> PartialEq for Frame<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.stack, &other.stack)
+    }
+}
+
+impl<T: TypeBound> Debug for Frame<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Frame({})", self.ty)
+    }
+}
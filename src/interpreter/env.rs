@@ -1,70 +1,281 @@
-use std::collections::HashMap;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-use super::{error::Error, value::Value};
+use super::{
+    coro::Yielder,
+    error::Error,
+    store::{Borrow, Loc, Place, Store},
+    value::Value,
+};
 use crate::ast::tree::*;
+use crate::semantics::{prelude::Intrinsic, types::Type};
 
 use anyhow::Error as AnyError;
 
-#[derive(Debug)]
-pub struct Env<T: TypeBound> {
-    vars: HashMap<String, (bool, Value<T>)>,
+// The bindings made within a single block, and every location they own
+#[derive(Default)]
+struct Scope {
+    vars: HashMap<String, Loc>,
+    // Includes the locations of shadowed bindings and temporaries, which live until the block ends
+    locs: Vec<Loc>,
+    // Borrows taken within the block, along with how each location was borrowed before them
+    borrows: Vec<(Loc, Option<Borrow>)>,
+}
+
+// How control leaves a loop early, carried up through every block in between
+pub enum Exit<T: TypeBound> {
+    Break(Option<String>, Value<T>),
+    Continue(Option<String>),
+    Return(Value<T>),
+}
+
+pub struct Env<'a, T: TypeBound> {
+    // Innermost scope last, the first scope holds the parameters
+    scopes: Vec<Scope>,
+    // Shared by every function call and coroutine, so references stay valid across them
+    store: Rc<RefCell<Store<T>>>,
     funcs: HashMap<String, Function<T>>,
+    // The values of constants, and the locations of statics, which no block owns
+    consts: HashMap<String, Value<T>>,
+    statics: HashMap<String, Loc>,
+    // Only present when running on a coroutine's stack
+    yielder: Option<&'a Yielder<T>>,
+    // Set while an early exit is unwinding towards its loop or function
+    pub unwinding: Option<Exit<T>>,
 }
 
-impl<T: TypeBound> Env<T> {
-    pub fn new() -> Self {
+impl<'a, T: TypeBound> Env<'a, T> {
+    pub fn new(check: bool) -> Self {
         Self {
-            vars: HashMap::new(),
+            scopes: vec![Scope::default()],
+            store: Rc::new(RefCell::new(Store::new(check))),
             funcs: HashMap::new(),
+            consts: HashMap::new(),
+            statics: HashMap::new(),
+            yielder: None,
+            unwinding: None,
         }
     }
 
-    pub fn from_funcs(funcs: Vec<Function<T>>) -> Self {
-        let mut env = Self::new();
+    // Functions go by their full paths, like `geo::area`, since type inference has already
+    // resolved every name to the item it refers to
+    pub fn from_funcs(funcs: Vec<Function<T>>, check: bool) -> Self {
+        let mut env = Self::new(check);
         for func in funcs {
             env.funcs.insert(func.name.clone(), func);
         }
         env
     }
 
+    // Each use of a constant gets its own copy of the value
+    pub fn define_const(&mut self, name: &str, value: Value<T>) {
+        self.consts.insert(name.to_owned(), value);
+    }
+
+    // A static stays in the same location until the program ends
+    pub fn define_static(&mut self, name: &str, value: Value<T>) {
+        let loc = self.store.borrow_mut().alloc(value, Some(name), false);
+        self.statics.insert(name.to_owned(), loc);
+    }
+
     // Creates a copy of the env for a function call
     pub fn call(&self) -> Self {
         Self {
-            vars: HashMap::new(),
+            scopes: vec![Scope::default()],
+            store: self.store.clone(),
             funcs: self.funcs.clone(),
+            consts: self.consts.clone(),
+            statics: self.statics.clone(),
+            yielder: self.yielder,
+            unwinding: None,
+        }
+    }
+
+    // Creates a copy of the env for a coroutine, which gets its own stack
+    pub fn spawn(&self) -> Env<'static, T> {
+        Env {
+            scopes: vec![Scope::default()],
+            store: self.store.clone(),
+            funcs: self.funcs.clone(),
+            consts: self.consts.clone(),
+            statics: self.statics.clone(),
+            yielder: None,
+            unwinding: None,
+        }
+    }
+
+    // Moves the env onto the stack of a coroutine, so it can yield
+    pub fn on_stack<'b>(self, yielder: &'b Yielder<T>) -> Env<'b, T>
+    where
+        'a: 'b,
+    {
+        Env {
+            yielder: Some(yielder),
+            ..self
         }
     }
 
-    pub fn update(&mut self, name: &String, value: Value<T>) -> Result<Value<T>, AnyError> {
-        if let Some((mutable, val)) = self.vars.get(name) {
-            if val.type_of() != value.type_of() {
-                Err(Error::UnexpectedType(val.type_of(), value.type_of()))?
-            } else if !mutable {
-                Err(Error::ImmutableVariable(name.clone()))?
-            } else {
-                let update = self
-                    .vars
-                    .get_mut(name)
-                    .ok_or(Error::UndefinedVariable(name.clone()))?;
-                *update = (true, value.clone());
-                Ok(value)
+    pub fn yielder(&self) -> Result<&'a Yielder<T>, Error> {
+        self.yielder.ok_or(Error::YieldOutsideCoroutine)
+    }
+
+    // Enters a new block
+    pub fn enter(&mut self) {
+        self.scopes.push(Scope::default());
+    }
+
+    // Leaves the innermost block, ending its borrows and freeing everything it owns
+    pub fn exit(&mut self) {
+        if let Some(scope) = self.scopes.pop() {
+            let mut store = self.store.borrow_mut();
+            for (loc, borrow) in scope.borrows.into_iter().rev() {
+                store.restore(loc, borrow);
+            }
+            for loc in scope.locs {
+                store.free(loc);
             }
-        } else {
-            Err(Error::UndefinedVariable(name.clone()))?
         }
     }
 
-    pub fn get(&self, name: &String) -> Result<Value<T>, AnyError> {
-        if let Some((_, val)) = self.vars.get(name) {
-            Ok(val.clone())
+    // Leaves a block around a call, ending the borrows taken for its arguments unless the
+    // result still uses them, in which case they're handed to the enclosing block
+    pub fn exit_call(&mut self, keep: bool) {
+        if !keep {
+            return self.exit();
+        }
+        if let Some(scope) = self.scopes.pop()
+            && let Some(outer) = self.scopes.last_mut()
+        {
+            outer.borrows.extend(scope.borrows);
+            outer.locs.extend(scope.locs);
+        }
+    }
+
+    // Finds the innermost binding of a variable
+    fn lookup(&self, name: &String) -> Option<Loc> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.vars.get(name))
+            .copied()
+    }
+
+    // Finds the location of a variable or static, or stores a function value or a constant so it
+    // can be pointed to
+    pub fn locate(&mut self, name: &String) -> Result<Place, AnyError> {
+        if let Some(loc) = self
+            .lookup(name)
+            .or_else(|| self.statics.get(name).copied())
+        {
+            Ok(Place::new(loc))
+        } else if let Some(value) = self.consts.get(name) {
+            Ok(self.alloc(value.clone()))
         } else if let Some(func) = self.funcs.get(name) {
-            Ok(Value::Function(func.clone()))
+            Ok(self.alloc(Value::Function(func.clone())))
+        } else if let Some(intrinsic) = Intrinsic::from_name(name) {
+            Ok(self.alloc(Value::Intrinsic(intrinsic)))
         } else {
             Err(Error::UndefinedVariable(name.clone()))?
         }
     }
 
+    // Finds the function from an impl that a method call refers to, looking through references.
+    // Type inference has already found which trait it's from, if it isn't from an inherent impl.
+    pub fn method(
+        &self,
+        mut ty: Type,
+        trait_: Option<&str>,
+        method: &str,
+    ) -> Result<Function<T>, Error> {
+        loop {
+            let path = match trait_ {
+                Some(trait_) => trait_method_path(&ty, trait_, method),
+                None => method_path(&ty, method),
+            };
+            if let Some(func) = self.funcs.get(&path) {
+                return Ok(func.clone());
+            }
+            match ty {
+                Type::Reference(inner, _) => ty = *inner,
+                ty => return Err(Error::NoSuchMethod(method.to_owned(), ty)),
+            }
+        }
+    }
+
+    // Stores a temporary value, so it can be pointed to
+    pub fn alloc(&mut self, value: Value<T>) -> Place {
+        Place::new(self.own(value, None, false))
+    }
+
+    // Stores a value in a new location, owned by the innermost block
+    fn own(&mut self, value: Value<T>, name: Option<&str>, mutable: bool) -> Loc {
+        let loc = self.store.borrow_mut().alloc(value, name, mutable);
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.locs.push(loc);
+        }
+        loc
+    }
+
+    // Reads the value at a place, without moving it out
+    pub fn load(&self, place: &Place) -> Result<Value<T>, AnyError> {
+        Ok(self.store.borrow_mut().load(place)?)
+    }
+
+    // Reads the value at a place, moving it out unless it can be copied
+    pub fn read(&mut self, place: &Place) -> Result<Value<T>, AnyError> {
+        let mut store = self.store.borrow_mut();
+        let val = store.load(place)?;
+        if !val.is_copy() {
+            store.move_out(place.loc)?;
+        }
+        Ok(val)
+    }
+
+    // Borrows a place until the end of the innermost block
+    pub fn borrow(&mut self, place: &Place, borrow: Borrow) -> Result<(), AnyError> {
+        let prev = self.store.borrow_mut().borrow(place.loc, borrow)?;
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.borrows.push((place.loc, prev));
+        }
+        Ok(())
+    }
+
+    pub fn type_of(&self, place: &Place) -> Result<Type, AnyError> {
+        Ok(self.store.borrow_mut().type_of(place)?)
+    }
+
+    // Assigns to a location, or to a field/element nested inside it
+    pub fn update(&mut self, place: &Place, value: Value<T>) -> Result<(), AnyError> {
+        self.store.borrow_mut().store(place, value)
+    }
+
+    // Binds each parameter to its argument
+    pub fn bind(&mut self, params: &[(String, Type)], args: Vec<Value<T>>) {
+        for ((name, _), val) in params.iter().zip(args) {
+            self.insert(name, val, false);
+        }
+    }
+
+    // Binds a name to a location that's owned elsewhere, such as a variable captured by a closure
+    pub fn alias(&mut self, name: &str, loc: Loc) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.vars.insert(name.to_owned(), loc);
+        }
+    }
+
+    // Binds a name in the innermost block, shadowing any earlier binding
     pub fn insert(&mut self, name: &str, value: Value<T>, mutable: bool) {
-        self.vars.insert(name.to_owned(), (mutable, value));
+        let loc = self.own(value, Some(name), mutable);
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.vars.insert(name.to_owned(), loc);
+        }
+    }
+
+    // Binds a name without a value, which it only gets once it's assigned
+    pub fn declare(&mut self, name: &str, mutable: bool) {
+        let loc = self.store.borrow_mut().alloc_uninit(name, mutable);
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.locs.push(loc);
+            scope.vars.insert(name.to_owned(), loc);
+        }
     }
 }
@@ -19,6 +19,50 @@ pub enum Error {
     UndefinedVariable(String),
     #[error("Immutable variable: '{0}'")]
     ImmutableVariable(String),
+    #[error("Immutable location: {0}")]
+    ImmutableLocation(usize),
+    #[error("Dangling reference to location {0}")]
+    DanglingReference(usize),
+    #[error("Use after move: {0} has been moved out of")]
+    UseAfterMove(String),
+    #[error("Use of dead location: {0} is no longer in scope")]
+    DeadLocation(String),
+    #[error("Use of uninitialized variable: {0} hasn't been assigned a value")]
+    Uninitialized(String),
+    #[error("Already borrowed: {0} can't be borrowed again")]
+    AlreadyBorrowed(String),
     #[error("Invalid Assignment: '{0}' is not assignable")]
     InvalidAssignment(String),
+    #[error("No field '{0}' on type '{1}'")]
+    NoSuchField(String, Type),
+    #[error("No method '{0}' on type '{1}'")]
+    NoSuchMethod(String, Type),
+    #[error("No match arm covers a value of type '{0}'")]
+    NoMatch(Type),
+    #[error("Can't iterate over a value of type '{0}'")]
+    InvalidIteration(Type),
+    #[error("Leaving a loop or function early")]
+    Unwinding,
+    #[error("Overflow: the result of '{0}' doesn't fit in '{1}'")]
+    Overflow(Operator, Type),
+    #[error("Literal '{0}' doesn't fit in '{1}'")]
+    OutOfRange(i128, Type),
+    #[error("Division by zero")]
+    DivisionByZero,
+    #[error("Index out of bounds: the index is {0} but the length is {1}")]
+    IndexOutOfBounds(i64, usize),
+    #[error("Invalid slice: {0}..{1} of a string of length {2}")]
+    InvalidSlice(i128, i128, usize),
+    #[error("Assertion failed")]
+    AssertionFailed,
+    #[error("Assertion failed: '{0}' is not equal to '{1}'")]
+    AssertionNotEqual(String, String),
+    #[error("Exited with code {0}")]
+    Exit(i64),
+    #[error("Yield used outside of a coroutine")]
+    YieldOutsideCoroutine,
+    #[error("Coroutine has already finished")]
+    CoroutineFinished,
+    #[error("Coroutine is already running")]
+    CoroutineRunning,
 }
@@ -0,0 +1,146 @@
+use std::io::{self, Write};
+
+use super::{env::Env, error::Error, value::Value};
+use crate::ast::tree::{is_tuple, TypeBound};
+use crate::semantics::{
+    prelude::Intrinsic,
+    types::{builtin_enum, Size, Type},
+};
+use anyhow::Error as AnyError;
+
+// Runs a function from the prelude, which reads and writes through the host
+pub fn run_intrinsic<T: TypeBound>(
+    intrinsic: Intrinsic,
+    args: Vec<Value<T>>,
+    env: &mut Env<T>,
+) -> Result<Value<T>, AnyError> {
+    match (intrinsic, &args[..]) {
+        (Intrinsic::Print, [val]) => {
+            print!("{}", show(val, env)?);
+            io::stdout().flush()?;
+        }
+        (Intrinsic::Println, [val]) => println!("{}", show(val, env)?),
+        (Intrinsic::ReadLine, []) => {
+            let mut line = String::new();
+            let val = if io::stdin().read_line(&mut line)? == 0 {
+                Value::Enum("Option".into(), "None".into(), vec![])
+            } else {
+                let line = line.trim_end_matches(['\n', '\r']).to_owned();
+                Value::Enum("Option".into(), "Some".into(), vec![Value::String(line)])
+            };
+            return Ok(val);
+        }
+        (Intrinsic::Assert, [Value::Bool(cond)]) => {
+            if !cond {
+                Err(Error::AssertionFailed)?
+            }
+        }
+        // References are compared by what they point to
+        (Intrinsic::AssertEq, [lhs, rhs]) => {
+            if deref(lhs, env)? != deref(rhs, env)? {
+                Err(Error::AssertionNotEqual(show(lhs, env)?, show(rhs, env)?))?
+            }
+        }
+        (Intrinsic::Exit, [Value::Signed(code, _)]) => Err(Error::Exit(*code))?,
+        _ => Err(Error::UnexpectedType(
+            intrinsic.get_type(),
+            Type::Function {
+                args: args.iter().map(|arg| arg.type_of()).collect(),
+                ret: Box::new(Type::unit()),
+            },
+        ))?,
+    }
+    Ok(Value::Tuple(vec![]))
+}
+
+fn deref<T: TypeBound>(val: &Value<T>, env: &Env<T>) -> Result<Value<T>, AnyError> {
+    match val {
+        Value::Reference(place, ..) => deref(&env.load(place)?, env),
+        val => Ok(val.clone()),
+    }
+}
+
+// How a value is printed, which looks like the literal it could be written as
+fn show<T: TypeBound>(val: &Value<T>, env: &Env<T>) -> Result<String, AnyError> {
+    let list = |vals: &[Value<T>]| -> Result<String, AnyError> {
+        let vals = vals
+            .iter()
+            .map(|val| show(val, env))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(vals.join(", "))
+    };
+    Ok(match val {
+        Value::Signed(x, _) => x.to_string(),
+        Value::Unsigned(x, _) => x.to_string(),
+        Value::Float(x, Size::ThirtyTwo) => (*x as f32).to_string(),
+        Value::Float(x, _) => x.to_string(),
+        Value::Bool(x) => x.to_string(),
+        Value::Char(x) => x.to_string(),
+        Value::String(x) => x.clone(),
+        Value::Tuple(vals) => format!("({})", list(vals)?),
+        Value::Array(vals) => format!("[{}]", list(vals)?),
+        Value::Struct(name, fields) if is_tuple(fields) => {
+            let vals = fields
+                .iter()
+                .map(|(_, val)| val.clone())
+                .collect::<Vec<_>>();
+            format!("{}({})", name, list(&vals)?)
+        }
+        Value::Struct(name, fields) => {
+            let fields = fields
+                .iter()
+                .map(|(field, val)| Ok(format!("{}: {}", field, show(val, env)?)))
+                .collect::<Result<Vec<_>, AnyError>>()?;
+            format!("{} {{ {} }}", name, fields.join(", "))
+        }
+        Value::Enum(name, variant, vals) => {
+            // The variants of the builtin enums are written without their enum
+            let path = if builtin_enum(variant) == Some(name) {
+                variant.clone()
+            } else {
+                format!("{}::{}", name, variant)
+            };
+            if vals.is_empty() {
+                path
+            } else {
+                format!("{}({})", path, list(vals)?)
+            }
+        }
+        Value::Range(start, end, _) => format!("{}..{}", start, end),
+        Value::Reference(..) => show(&deref(val, env)?, env)?,
+        // Only reached through a struct or enum, since the type checker keeps them out otherwise
+        Value::Function(func) => format!("fn {}", func.name),
+        Value::Intrinsic(intrinsic) => format!("fn {}", intrinsic.name()),
+        Value::Closure(_) => "closure".to_owned(),
+        Value::Frame(_) => "frame".to_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_show() -> Result<(), AnyError> {
+        let env = Env::<Type>::new(false);
+        let point = Value::Struct(
+            "Point".into(),
+            vec![("x".into(), Value::Signed(1, Size::ThirtyTwo))],
+        );
+        let val = Value::Tuple(vec![
+            Value::Enum("Option".into(), "Some".into(), vec![point]),
+            Value::Enum("Shape".into(), "Empty".into(), vec![]),
+            Value::Struct(
+                "Meters".into(),
+                vec![("0".into(), Value::Signed(3, Size::ThirtyTwo))],
+            ),
+            Value::Float(0.1, Size::ThirtyTwo),
+            Value::String("hi".into()),
+        ]);
+        assert_eq!(
+            show(&val, &env)?,
+            "(Some(Point { x: 1 }), Shape::Empty, Meters(3), 0.1, hi)"
+        );
+        Ok(())
+    }
+}
@@ -1,4 +1,7 @@
+mod coro;
 mod env;
 pub mod error;
+mod intrinsics;
 pub mod run;
+mod store;
 mod value;
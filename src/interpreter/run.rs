@@ -1,5 +1,11 @@
-use super::env::Env;
-use super::{error::Error, value::Value};
+use super::coro::Frame;
+use super::env::{Env, Exit};
+use super::intrinsics::run_intrinsic;
+use super::{
+    error::Error,
+    store::{Borrow, Place},
+    value::{Captured, Closure, Step, Value},
+};
 use crate::ast::tree;
 use crate::ast::tree::*;
 use crate::semantics::types::{Size, Type};
@@ -8,16 +14,46 @@ use anyhow::Error as AnyError;
 
 // Currently, the interpreter completely ignores the type information (Type Erasure),
 // but we can use the type information to check the types of the values at runtime.
-pub fn run_program<T: TypeBound>(prgm: Program<T>) -> Result<(), AnyError> {
-    let funcs = prgm.0;
+// When checking ownership, each location also tracks whether it's alive and how it's borrowed.
+pub fn run_program<T: TypeBound>(prgm: Program<T>, check_ownership: bool) -> Result<(), AnyError> {
+    let funcs = prgm
+        .funcs()
+        .cloned()
+        .chain(prgm.methods())
+        .collect::<Vec<_>>();
     let main: Function<T> = funcs
         .iter()
         .find(|func| func.name == "main")
         .cloned()
         .context("No main() function found")?;
 
-    let value = run_func(&main, Env::from_funcs(funcs))?;
-    if let Value::Signed(val) = value {
+    let mut env = Env::from_funcs(funcs, check_ownership);
+    // Type inference has put each global after the ones its value uses, and worked out the
+    // value of each constant, so only the statics are left to evaluate
+    for item in &prgm.0 {
+        match item {
+            Item::Const(def) => {
+                let value = run_expr(&def.value, &mut env)
+                    .context(format!("In constant '{}'", def.name))?;
+                env.define_const(&def.name, value);
+            }
+            Item::Static(def) => {
+                let value =
+                    run_expr(&def.value, &mut env).context(format!("In static '{}'", def.name))?;
+                env.define_static(&def.name, value);
+            }
+            _ => (),
+        }
+    }
+    let value = match run_func(&main, env) {
+        Ok(value) => value,
+        // Exiting ends the program from anywhere, as if main had returned the code
+        Err(err) => match err.downcast_ref::<Error>() {
+            Some(Error::Exit(code)) => Value::Signed(*code, Size::ThirtyTwo),
+            _ => return Err(err),
+        },
+    };
+    if let Value::Signed(val, _) = value {
         if val == 0 {
             Ok(())
         } else {
@@ -31,23 +67,158 @@ pub fn run_program<T: TypeBound>(prgm: Program<T>) -> Result<(), AnyError> {
     }
 }
 
-fn run_func<T: TypeBound>(curr: &Function<T>, mut env: Env<T>) -> Result<Value<T>, AnyError> {
-    run_exprs(&curr.body.0, &mut env)
+pub fn run_func<T: TypeBound>(curr: &Function<T>, env: Env<T>) -> Result<Value<T>, AnyError> {
+    run_body(&curr.body, env)
 }
 
-fn run_exprs<T: TypeBound>(
-    exprs: &Vec<Expression<T>>,
+fn call_func<T: TypeBound>(
+    func: &Function<T>,
+    args: Vec<Value<T>>,
     env: &mut Env<T>,
 ) -> Result<Value<T>, AnyError> {
-    let mut val = Value::Signed(0);
-    for expr in exprs {
-        val = run_expr(expr, env).context(format!("On expression: {}", expr))?;
+    if let FuncKind::Coro(resume) = &func.kind {
+        // Starting a coroutine runs it on a separate stack, with its own environment
+        let mut env = env.spawn();
+        env.bind(&func.args, args);
+        Ok(Value::Frame(Frame::start(func, resume, env)?))
+    } else {
+        // Create a new environment for the function, and insert the arguments into it
+        let mut env = env.call();
+        env.bind(&func.args, args);
+        run_func(func, env).context(format!("On call to function '{}'", func.name))
     }
-    Ok(val)
+}
+
+fn run_call<T: TypeBound>(
+    func: &Expression<T>,
+    args: &[Expression<T>],
+    env: &mut Env<T>,
+) -> Result<Value<T>, AnyError> {
+    // Calling a value doesn't move it, so a frame can be resumed again
+    let place = run_place(func, env)?;
+    let func = env.load(&place)?;
+    let args = run_values(args, env)?;
+    if let Value::Function(func) = &func && func.args.len() == args.len(){
+        call_func(func, args, env)
+    } else if let Value::Closure(closure) = &func && closure.params.len() == args.len() {
+        let mut env = env.call();
+        for (name, captured) in &closure.captured {
+            match captured {
+                Captured::Ref(loc) => env.alias(name, *loc),
+                Captured::Value(val) => env.insert(name, (**val).clone(), false),
+            }
+        }
+        env.bind(&closure.params, args);
+        run_body(&closure.body, env).context("On call to closure")
+    } else if let Value::Intrinsic(intrinsic) = &func {
+        run_intrinsic(*intrinsic, args, env).context(format!("On call to '{}'", intrinsic.name()))
+    } else if let Value::Frame(frame) = &func {
+        frame.resume(args).context("On resuming coroutine")
+    } else {
+        let arg_ty = args.iter().map(|arg| arg.type_of()).collect::<Vec<_>>();
+        Err(Error::UnexpectedType(
+            Type::Function {
+                args: arg_ty,
+                ret: Box::new(Type::unit()),
+            },
+            func.type_of(),
+        ))?
+    }
+}
+
+// Methods are found by the type the receiver has at runtime, since generic functions are erased
+fn run_method<T: TypeBound>(
+    receiver: &Expression<T>,
+    trait_: Option<&str>,
+    method: &str,
+    args: &[Expression<T>],
+    env: &mut Env<T>,
+) -> Result<Value<T>, AnyError> {
+    let receiver = run_expr(receiver, env)?;
+    let func = match env.method(receiver.type_of(), trait_, method) {
+        Ok(func) => func,
+        Err(err) => return receiver.builtin_method(method).ok_or(err.into()),
+    };
+    let mut vals = vec![receiver];
+    vals.extend(run_values(args, env)?);
+    call_func(&func, vals, env)
+}
+
+// Runs the body of a function or closure, which a return can leave early
+fn run_body<T: TypeBound>(body: &Sequence<T>, mut env: Env<T>) -> Result<Value<T>, AnyError> {
+    let val = match run_exprs(&body.0, &mut env) {
+        Err(err) => match env.unwinding.take() {
+            Some(Exit::Return(val)) => Ok(val),
+            _ => Err(err),
+        },
+        val => val,
+    };
+    // Free the parameters
+    env.exit();
+    val
+}
+
+// Runs a block in its own scope, which is left even if an expression fails
+fn run_exprs<T: TypeBound>(
+    exprs: &[Expression<T>],
+    env: &mut Env<T>,
+) -> Result<Value<T>, AnyError> {
+    env.enter();
+    let val = exprs
+        .iter()
+        .try_fold(Value::Signed(0, Size::ThirtyTwo), |_, expr| {
+            run_expr(expr, env).context(format!("On expression: {}", expr))
+        });
+    env.exit();
+    val
+}
+
+// Numbers that haven't been through type inference are i32s
+fn run_number<T: TypeBound>(x: i128, ty: &Option<Type>) -> Result<Value<T>, Error> {
+    let ty = ty.clone().unwrap_or(Type::Signed(Size::ThirtyTwo));
+    Value::from_int(x, &ty).ok_or(Error::OutOfRange(x, ty))
 }
 
 fn run_expr<T: TypeBound>(expr: &Expression<T>, env: &mut Env<T>) -> Result<Value<T>, AnyError> {
     let val = match &expr.expr {
+        // Variables, along with their fields and elements, are read from their place in the store
+        Expr::Reference(_)
+        | Expr::Instance { .. }
+        | Expr::Field { .. }
+        | Expr::Unary {
+            op: Operator::TupleIndex(_) | Operator::Mul,
+            ..
+        }
+        | Expr::Binary {
+            op: Operator::ArrayIndex,
+            ..
+        } => {
+            let place = run_place(expr, env)?;
+            env.read(&place)?
+        }
+        Expr::Unary {
+            op: op @ (Operator::Ref | Operator::RefMut),
+            rhs,
+        } => {
+            let mutable = *op == Operator::RefMut;
+            let place = run_place(rhs, env)?;
+            let borrow = if mutable { Borrow::Mut } else { Borrow::Shared };
+            // Borrowing through a reference reuses the borrow it already holds
+            if !behind_ref(rhs) {
+                env.borrow(&place, borrow)?;
+            }
+            let ty = env.type_of(&place)?;
+            Value::Reference(place, ty, mutable)
+        }
+        // A negated literal only has to fit in its type once it's negative, like `-128i8`
+        Expr::Unary {
+            op: Operator::Sub,
+            rhs:
+                box Expression {
+                    expr: Expr::Value(tree::Value::Number(x, ty)),
+                    ..
+                },
+        } => run_number(-x, ty)?,
         Expr::Unary { op, rhs } => {
             let val = run_expr(rhs, env)?;
             val.unary(op)?
@@ -58,36 +229,73 @@ fn run_expr<T: TypeBound>(expr: &Expression<T>, env: &mut Env<T>) -> Result<Valu
             op: Operator::Assign,
             rhs,
         } => {
-            if let Expr::Reference(name) = &lhs.as_ref().expr {
-                let val = run_expr(rhs, env)?;
-                env.update(name, val)?;
-                Value::Tuple(vec![])
-            } else {
-                Err(Error::InvalidAssignment(lhs.to_string()))?
-            }
+            let val = run_expr(rhs, env)?;
+            let place = run_place(lhs, env)?;
+            env.update(&place, val)?;
+            Value::Tuple(vec![])
         }
         Expr::Binary { lhs, op, rhs } => {
             let lhs = run_expr(lhs, env)?;
             let rhs = run_expr(rhs, env)?;
             lhs.binary(op, &rhs)?
         }
+        // The place is only found once, and then both read and assigned
+        Expr::CompoundAssign { lhs, op, rhs } => {
+            let rhs = run_expr(rhs, env)?;
+            let place = run_place(lhs, env)?;
+            let val = env.load(&place)?.binary(op, &rhs)?;
+            env.update(&place, val)?;
+            Value::Tuple(vec![])
+        }
         Expr::Value(val) => match val {
-            tree::Value::Number(x) => Value::Signed(*x),
+            tree::Value::Number(x, ty) => run_number(*x, ty)?,
+            tree::Value::Float(x, ty) => match ty {
+                Some(Type::Float(size)) => Value::float(*x, *size),
+                _ => Value::Float(*x, Size::SixtyFour),
+            },
             tree::Value::Bool(x) => Value::Bool(*x),
             tree::Value::Char(x) => Value::Char(*x),
-            tree::Value::String(x) => Value::Array(x.chars().map(Value::Char).collect()),
+            tree::Value::String(x) => Value::String(x.clone()),
         },
-        Expr::Tuple(_) => todo!(),
-        Expr::Array(_) => todo!(),
-        Expr::Reference(x) => env.get(x)?,
+        Expr::Tuple(exprs) => Value::Tuple(run_values(exprs, env)?),
+        Expr::Array(exprs) => Value::Array(run_values(exprs, env)?),
+        Expr::Variant {
+            enum_,
+            variant,
+            args,
+        } => {
+            let args = args.as_deref().unwrap_or_default();
+            Value::Enum(enum_.clone(), variant.clone(), run_values(args, env)?)
+        }
+        Expr::Match { expr, arms } => {
+            let val = run_expr(expr, env)?;
+            run_match(&val, arms, env)?
+        }
+        Expr::Struct { name, fields } => {
+            let fields = fields
+                .iter()
+                .map(|(field, value)| Ok((field.clone(), run_expr(value, env)?)))
+                .collect::<Result<Vec<_>, AnyError>>()?;
+            Value::Struct(name.clone(), fields)
+        }
+        Expr::Let {
+            name,
+            value: None,
+            mutable,
+            ..
+        } => {
+            env.declare(name, *mutable);
+            Value::Tuple(vec![])
+        }
         Expr::Let {
             name,
-            value,
+            value: Some(value),
             ty,
             mutable,
         } => {
             let val = run_expr(value, env)?;
-            if let Some(ty) = ty {
+            // Type parameters are only known during type inference
+            if let Some(ty) = ty && !ty.is_generic() {
                 ty.context(val.type_of())?;
             }
             env.insert(name, val, *mutable);
@@ -108,36 +316,27 @@ fn run_expr<T: TypeBound>(expr: &Expression<T>, env: &mut Env<T>) -> Result<Valu
             }
         }
         Expr::Call { func, args } => {
-            let func = run_expr(func, env)?;
-            let args = args
-                .iter()
-                .map(|arg| run_expr(arg, env))
-                .collect::<Result<Vec<_>, _>>()?;
-            if let Value::Function(func) = &func && func.args.len() == args.len(){
-                let mut env = env.call();
-                // Create a new environment for the function, and insert the arguments into it
-                for ((name, _), val) in func.args.iter().zip(args) {
-                    env.insert(name, val, false);
-                }
-                run_func(func, env).context(format!("On call to function '{}'", func.name))?
-            } else {
-                let arg_ty = args.iter().map(|arg| arg.type_of()).collect::<Vec<_>>();
-                Err(Error::UnexpectedType(
-                    Type::Function {
-                        args: arg_ty,
-                        ret: Box::new(Type::unit()),
-                    },
-                    func.type_of(),
-                ))?
-            }
+            env.enter();
+            let val = run_call(func, args, env);
+            env.exit_call(val.as_ref().is_ok_and(|val| val.holds_refs()));
+            val?
+        }
+        Expr::Method {
+            receiver,
+            method,
+            args,
+            trait_,
+        } => {
+            env.enter();
+            let val = run_method(receiver, trait_.as_deref(), method, args, env);
+            env.exit_call(val.as_ref().is_ok_and(|val| val.holds_refs()));
+            val?
         }
-        Expr::While { cond, body } => {
+        Expr::While { cond, body, label } => {
             loop {
                 let cond = run_expr(cond, env)?;
                 if let Value::Bool(cond) = cond {
-                    if cond {
-                        run_exprs(&body.0, env)?;
-                    } else {
+                    if !cond || run_iteration(&body.0, label, env)?.is_some() {
                         break;
                     }
                 } else {
@@ -146,10 +345,247 @@ fn run_expr<T: TypeBound>(expr: &Expression<T>, env: &mut Env<T>) -> Result<Valu
             }
             Value::Tuple(vec![])
         }
+        Expr::For {
+            var,
+            iter,
+            body,
+            label,
+        } => {
+            let vals: Box<dyn Iterator<Item = Value<T>>> = match run_expr(iter, env)? {
+                Value::Range(start, end, ty) => {
+                    Box::new((start..end).filter_map(move |x| Value::from_int(x, &ty)))
+                }
+                Value::Array(vals) => Box::new(vals.into_iter()),
+                val => Err(Error::InvalidIteration(val.type_of()))?,
+            };
+            for val in vals {
+                // Each iteration binds the variable in a fresh scope
+                env.enter();
+                env.insert(var, val, false);
+                let res = run_iteration(&body.0, label, env);
+                env.exit();
+                if res?.is_some() {
+                    break;
+                }
+            }
+            Value::Tuple(vec![])
+        }
+        Expr::Loop { body, label } => loop {
+            if let Some(val) = run_iteration(&body.0, label, env)? {
+                break val;
+            }
+        },
+        Expr::Break { label, value } => {
+            let val = match value {
+                Some(value) => run_expr(value, env)?,
+                None => Value::Tuple(vec![]),
+            };
+            env.unwinding = Some(Exit::Break(label.clone(), val));
+            Err(Error::Unwinding)?
+        }
+        Expr::Continue(label) => {
+            env.unwinding = Some(Exit::Continue(label.clone()));
+            Err(Error::Unwinding)?
+        }
+        Expr::Return(value) => {
+            let val = match value {
+                Some(value) => run_expr(value, env)?,
+                None => Value::Tuple(vec![]),
+            };
+            env.unwinding = Some(Exit::Return(val));
+            Err(Error::Unwinding)?
+        }
+        Expr::Closure {
+            params,
+            ret,
+            body,
+            moves,
+            captures,
+        } => {
+            let mut captured = vec![];
+            for capture in captures {
+                let place = env.locate(&capture.name)?;
+                let val = if *moves {
+                    Captured::Value(Box::new(env.read(&place)?))
+                } else {
+                    let borrow = if capture.mutable {
+                        Borrow::Mut
+                    } else {
+                        Borrow::Shared
+                    };
+                    env.borrow(&place, borrow)?;
+                    Captured::Ref(place.loc)
+                };
+                captured.push((capture.name.clone(), val));
+            }
+            Value::Closure(Closure {
+                params: params
+                    .iter()
+                    .map(|(name, ty)| (name.clone(), ty.clone().unwrap_or_else(Type::unit)))
+                    .collect(),
+                // Type inference fills in the return types that were left out
+                ret: ret.clone().unwrap_or_else(Type::unit),
+                body: body.clone(),
+                captured,
+            })
+        }
+        Expr::Yield(value) => {
+            let val = run_expr(value, env)?;
+            Value::resumed(env.yielder()?.suspend(val))
+        }
+        Expr::Try(value) => match run_expr(value, env)? {
+            Value::Enum(_, variant, mut vals) if variant == "Some" || variant == "Ok" => {
+                vals.remove(0)
+            }
+            // A `None` or `Err` is returned from the function as it is
+            val => {
+                env.unwinding = Some(Exit::Return(val));
+                Err(Error::Unwinding)?
+            }
+        },
     };
     Ok(val)
 }
 
+// Runs one iteration of a loop, giving back the value it was broken out of with.
+// Exits aimed at an outer loop are left in place, and keep unwinding.
+fn run_iteration<T: TypeBound>(
+    body: &[Expression<T>],
+    label: &Option<String>,
+    env: &mut Env<T>,
+) -> Result<Option<Value<T>>, AnyError> {
+    let err = match run_exprs(body, env) {
+        Ok(_) => return Ok(None),
+        Err(err) => err,
+    };
+    let ours = |target: &Option<String>| target.is_none() || target == label;
+    match env.unwinding.take() {
+        Some(Exit::Break(target, val)) if ours(&target) => Ok(Some(val)),
+        Some(Exit::Continue(target)) if ours(&target) => Ok(None),
+        exit => {
+            env.unwinding = exit;
+            Err(err)
+        }
+    }
+}
+
+// Runs the first arm whose pattern matches the value, and whose guard holds
+fn run_match<T: TypeBound>(
+    val: &Value<T>,
+    arms: &[Arm<T>],
+    env: &mut Env<T>,
+) -> Result<Value<T>, AnyError> {
+    for arm in arms {
+        let mut bindings = vec![];
+        if !val.matches(&arm.pattern, &mut bindings) {
+            continue;
+        }
+        // The bindings are only in scope for the arm
+        env.enter();
+        let res = run_arm(arm, bindings, env);
+        env.exit();
+        if let Some(val) = res? {
+            return Ok(val);
+        }
+    }
+    Err(Error::NoMatch(val.type_of()))?
+}
+
+// Gives back nothing if the arm's guard doesn't hold
+fn run_arm<T: TypeBound>(
+    arm: &Arm<T>,
+    bindings: Vec<(String, Value<T>)>,
+    env: &mut Env<T>,
+) -> Result<Option<Value<T>>, AnyError> {
+    for (name, val) in bindings {
+        env.insert(&name, val, false);
+    }
+    if let Some(guard) = &arm.guard {
+        match run_expr(guard, env)? {
+            Value::Bool(true) => (),
+            Value::Bool(false) => return Ok(None),
+            val => Err(Error::UnexpectedType(Type::Bool, val.type_of()))?,
+        }
+    }
+    Ok(Some(run_exprs(&arm.body.0, env)?))
+}
+
+// Evaluates each expression in order, keeping every value
+fn run_values<T: TypeBound>(
+    exprs: &[Expression<T>],
+    env: &mut Env<T>,
+) -> Result<Vec<Value<T>>, AnyError> {
+    exprs.iter().map(|expr| run_expr(expr, env)).collect()
+}
+
+// Evaluates an expression into the place it refers to, such as the left hand side of an assignment.
+// Expressions that aren't places are stored as temporaries, so they can still be referenced.
+fn run_place<T: TypeBound>(expr: &Expression<T>, env: &mut Env<T>) -> Result<Place, AnyError> {
+    let place = match &expr.expr {
+        // Type parameters are erased, so a generic function runs the same for every instance
+        Expr::Reference(name) | Expr::Instance { func: name, .. } => env.locate(name)?,
+        Expr::Unary {
+            op: Operator::Mul,
+            rhs,
+        } => run_deref(rhs, env)?,
+        Expr::Unary {
+            op: Operator::TupleIndex(i),
+            rhs,
+        } => run_place(rhs, env)?.project(Step::Field(*i)),
+        Expr::Field { expr, field } => run_place(expr, env)?.project(Step::Member(field.clone())),
+        Expr::Binary {
+            lhs,
+            op: Operator::ArrayIndex,
+            rhs,
+        } => {
+            let place = run_place(lhs, env)?;
+            let index = run_expr(rhs, env)?;
+            // The characters of a string aren't stored on their own, so they're temporaries
+            if env.type_of(&place)? == Type::String {
+                let val = env.load(&place)?.index_string(&index)?;
+                env.alloc(val)
+            } else {
+                place.project(Step::Element(index.as_index()?))
+            }
+        }
+        _ => {
+            let val = run_expr(expr, env)?;
+            env.alloc(val)
+        }
+    };
+    Ok(place)
+}
+
+// Evaluates a reference, into the place it points to
+// Going through a reference only uses it, so a mutable reference isn't moved
+// Whether a place is reached by dereferencing a reference
+fn behind_ref<T: TypeBound>(expr: &Expression<T>) -> bool {
+    match &expr.expr {
+        Expr::Unary {
+            op: Operator::Mul, ..
+        } => true,
+        Expr::Unary {
+            op: Operator::TupleIndex(_),
+            rhs: inner,
+        }
+        | Expr::Field { expr: inner, .. }
+        | Expr::Binary {
+            lhs: inner,
+            op: Operator::ArrayIndex,
+            ..
+        } => behind_ref(inner),
+        _ => false,
+    }
+}
+
+fn run_deref<T: TypeBound>(expr: &Expression<T>, env: &mut Env<T>) -> Result<Place, AnyError> {
+    let place = run_place(expr, env)?;
+    match env.load(&place)? {
+        Value::Reference(place, ..) => Ok(place),
+        val => Err(Error::InvalidUnary(Operator::Mul, val.type_of()))?,
+    }
+}
+
 impl<T: TypeBound> Value<T> {
     fn unary(&self, op: &Operator) -> Result<Value<T>, AnyError> {
         match op {
@@ -160,10 +596,14 @@ impl<T: TypeBound> Value<T> {
                 Ok(Value::Bool(!x))
             }
             Operator::Sub => {
-                let Value::Signed(x) = self else {
+                if let Value::Float(x, size) = self {
+                    return Ok(Value::Float(-x, *size));
+                }
+                let Value::Signed(x, _) = self else {
                     Err(Error::InvalidUnary(*op, self.type_of()))?
                 };
-                Ok(Value::Signed(-x))
+                let ty = self.type_of();
+                Ok(Value::from_int(-i128::from(*x), &ty).ok_or(Error::Overflow(*op, ty))?)
             }
             _ => Err(Error::InvalidUnary(*op, self.type_of()))?,
         }
@@ -171,11 +611,23 @@ impl<T: TypeBound> Value<T> {
 
     fn binary(&self, op: &Operator, rhs: &Value<T>) -> Result<Value<T>, AnyError> {
         let value = match (self, rhs) {
-            (Value::Signed(x), Value::Signed(y)) => match op {
-                Operator::Add => Value::Signed(x + y),
-                Operator::Sub => Value::Signed(x - y),
-                Operator::Mul => Value::Signed(x * y),
-                Operator::Div => Value::Signed(x / y),
+            (Value::Signed(..) | Value::Unsigned(..), Value::Signed(..) | Value::Unsigned(..))
+                if op.is_shift() =>
+            {
+                self.shift(op, rhs)?
+            }
+            (Value::Signed(..) | Value::Unsigned(..), Value::Signed(..) | Value::Unsigned(..))
+                if self.type_of() == rhs.type_of() =>
+            {
+                self.integer(op, rhs)?
+            }
+            (Value::Float(x, size), Value::Float(y, rhs_size)) if size == rhs_size => match op {
+                // Rounding the f64 result gives the same result as doing it in an f32
+                Operator::Add => Value::float(x + y, *size),
+                Operator::Sub => Value::float(x - y, *size),
+                Operator::Mul => Value::float(x * y, *size),
+                Operator::Div => Value::float(x / y, *size),
+                Operator::Rem => Value::float(x % y, *size),
                 Operator::Gt => Value::Bool(x > y),
                 Operator::Lt => Value::Bool(x < y),
                 Operator::Gte => Value::Bool(x >= y),
@@ -187,11 +639,28 @@ impl<T: TypeBound> Value<T> {
             (Value::Bool(x), Value::Bool(y)) => match op {
                 Operator::And => Value::Bool(*x && *y),
                 Operator::Or => Value::Bool(*x || *y),
+                Operator::BitAnd => Value::Bool(x & y),
+                Operator::BitOr => Value::Bool(x | y),
+                Operator::BitXor => Value::Bool(x ^ y),
+                Operator::Eq => Value::Bool(x == y),
+                Operator::Neq => Value::Bool(x != y),
+                _ => Err(Error::InvalidBinary(*op, self.type_of(), rhs.type_of()))?,
+            },
+            (Value::String(x), Value::String(y)) => match op {
+                Operator::Add => Value::String(format!("{}{}", x, y)),
+                Operator::Gt => Value::Bool(x > y),
+                Operator::Lt => Value::Bool(x < y),
+                Operator::Gte => Value::Bool(x >= y),
+                Operator::Lte => Value::Bool(x <= y),
                 Operator::Eq => Value::Bool(x == y),
                 Operator::Neq => Value::Bool(x != y),
                 _ => Err(Error::InvalidBinary(*op, self.type_of(), rhs.type_of()))?,
             },
             (Value::Char(x), Value::Char(y)) => match op {
+                Operator::Gt => Value::Bool(x > y),
+                Operator::Lt => Value::Bool(x < y),
+                Operator::Gte => Value::Bool(x >= y),
+                Operator::Lte => Value::Bool(x <= y),
                 Operator::Eq => Value::Bool(x == y),
                 Operator::Neq => Value::Bool(x != y),
                 _ => Err(Error::InvalidBinary(*op, self.type_of(), rhs.type_of()))?,
@@ -200,6 +669,60 @@ impl<T: TypeBound> Value<T> {
         };
         Ok(value)
     }
+
+    // Integers of the same type, where arithmetic fails instead of going past what the type holds
+    fn integer(&self, op: &Operator, rhs: &Value<T>) -> Result<Value<T>, Error> {
+        let (Some(x), Some(y)) = (self.as_int(), rhs.as_int()) else {
+            return Err(Error::InvalidBinary(*op, self.type_of(), rhs.type_of()));
+        };
+        let ty = self.type_of();
+        let result = match op {
+            Operator::Add => x.checked_add(y),
+            Operator::Sub => x.checked_sub(y),
+            Operator::Mul => x.checked_mul(y),
+            Operator::Div if y == 0 => return Err(Error::DivisionByZero),
+            Operator::Div => x.checked_div(y),
+            Operator::Rem if y == 0 => return Err(Error::DivisionByZero),
+            Operator::Rem => x.checked_rem(y),
+            // Both sides fit in the type, and so does any of their bits
+            Operator::BitAnd => Some(x & y),
+            Operator::BitOr => Some(x | y),
+            Operator::BitXor => Some(x ^ y),
+            Operator::Gt => return Ok(Value::Bool(x > y)),
+            Operator::Lt => return Ok(Value::Bool(x < y)),
+            Operator::Gte => return Ok(Value::Bool(x >= y)),
+            Operator::Lte => return Ok(Value::Bool(x <= y)),
+            Operator::Eq => return Ok(Value::Bool(x == y)),
+            Operator::Neq => return Ok(Value::Bool(x != y)),
+            Operator::Range => return Ok(Value::Range(x, y, ty)),
+            _ => return Err(Error::InvalidBinary(*op, ty, rhs.type_of())),
+        };
+        result
+            .and_then(|result| Value::from_int(result, &ty))
+            .ok_or(Error::Overflow(*op, ty))
+    }
+
+    // Shifts by at least the number of bits in the type fail, while bits shifted past the top
+    // are dropped, so the result is sign extended from the type's width
+    fn shift(&self, op: &Operator, rhs: &Value<T>) -> Result<Value<T>, Error> {
+        let ty = self.type_of();
+        let (Some(x), Some(y), Type::Signed(size) | Type::Unsigned(size)) =
+            (self.as_int(), rhs.as_int(), &ty)
+        else {
+            return Err(Error::InvalidBinary(*op, ty, rhs.type_of()));
+        };
+        let bits = size.bits();
+        let Some(y) = u32::try_from(y).ok().filter(|&y| y < bits) else {
+            return Err(Error::Overflow(*op, ty));
+        };
+        let unused = 128 - bits;
+        let result = match (op, &ty) {
+            (Operator::Shl, Type::Signed(_)) => (x << y) << unused >> unused,
+            (Operator::Shl, _) => (((x << y) as u128) << unused >> unused) as i128,
+            _ => x >> y,
+        };
+        Value::from_int(result, &ty).ok_or(Error::Overflow(*op, ty))
+    }
 }
 
 impl Type {
@@ -212,10 +735,716 @@ impl Type {
     }
 
     fn context(&self, actual: Type) -> Result<(), Error> {
-        if self == &actual {
+        if actual.coerces_to(self) {
             Ok(())
         } else {
             Err(Error::UnexpectedType(self.clone(), actual))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{interpreter::run::run_program, parse, semantics::typeinfer::infer};
+    use anyhow::Error as AnyError;
+
+    fn run(src: &str) -> Result<(), AnyError> {
+        run_program(infer(parse(src)?)?, false)
+    }
+
+    fn run_checked(src: &str) -> Result<(), AnyError> {
+        run_program(infer(parse(src)?)?, true)
+    }
+
+    #[test]
+    fn test_coroutines() -> Result<(), AnyError> {
+        // Each resume hands the coroutine a value, and gets back the next one it yields
+        run("coro sum(start: i32)(x: i32) -> i32 {
+            var total = start + x;
+            while true { total = total + (yield total) }
+        }
+        fn main() -> i32 {
+            let s = sum(10);
+            let a = s(1);
+            let b = s(5);
+            (a - 11) + (b - 16)
+        }")?;
+        // A function called from a coroutine can yield through it
+        run("fn emit(x: i32) -> i32 yield frame(i32) -> i32 {
+            yield x
+        }
+        coro doubles()(x: i32) -> i32 {
+            var y = x;
+            while true { y = emit(y * 2) }
+        }
+        fn main() -> i32 {
+            let d = doubles();
+            let a = d(3);
+            let b = d(a);
+            (a - 6) + (b - 12)
+        }")
+    }
+
+    #[test]
+    fn test_coroutine_finished() {
+        let src = "coro once()() -> i32 { yield 1 }
+        fn main() -> i32 {
+            let f = once();
+            let a = f();
+            f();
+            a - 1
+        }";
+        assert_fails(run(src), "Coroutine has already finished");
+    }
+
+    #[test]
+    fn test_nested_places() -> Result<(), AnyError> {
+        run("fn main() -> i32 {
+            var tuple = (1, [1, 2, 3], 'a');
+            tuple.1[2] = 5;
+            tuple.0 = tuple.1[2];
+            tuple.0 - 5
+        }")
+    }
+
+    #[test]
+    fn test_index_out_of_bounds() {
+        let err = run("fn main() -> i32 {
+            let arr = [1, 2, 3];
+            arr[3]
+        }");
+        assert!(format!("{:?}", err.unwrap_err()).contains("Index out of bounds"));
+    }
+
+    #[test]
+    fn test_references() -> Result<(), AnyError> {
+        run("fn main() -> i32 {
+            var arr = [1, 2, 3];
+            let r = &mut arr[1];
+            *r = 5;
+            let t = (&arr, 4);
+            (*t.0)[1] - 5
+        }")
+    }
+
+    #[test]
+    fn test_references_across_calls() -> Result<(), AnyError> {
+        run("fn main() -> i32 {
+            var x = 1;
+            set(&mut x);
+            x - 7
+        }
+
+        fn set(r: &mut i32) -> () {
+            *r = 7
+        }")
+    }
+
+    #[test]
+    fn test_block_scopes() -> Result<(), AnyError> {
+        run("fn main() -> i32 {
+            var i = 0;
+            var sum = 0;
+            while i < 3 {
+                let x = i * 2;
+                let x = x + 1;
+                sum = sum + x;
+                i = i + 1
+            };
+            let sum = sum - 9;
+            sum
+        }")
+    }
+
+    #[test]
+    fn test_structs() -> Result<(), AnyError> {
+        run("struct Pair { left: i32, right: (i32, bool) }
+
+        fn main() -> i32 {
+            var p = Pair { right: (2, true), left: 1 };
+            p.right.0 = 5;
+            let r = &mut p.left;
+            *r = 3;
+            p.right.0 - (p.left + 2)
+        }")
+    }
+
+    #[test]
+    fn test_match() -> Result<(), AnyError> {
+        run("enum Token { Num(i32), Neg(i32), End }
+
+        fn value(token: Token) -> i32 {
+            match token {
+                Token::Num(0) => 100,
+                Token::Num(x) if x > 10 => 10,
+                Token::Num(x) => x,
+                Token::Neg(x) => 0 - x,
+                Token::End => 0,
+            }
+        }
+
+        fn main() -> i32 {
+            let sum = value(Token::Num(0)) + value(Token::Num(20)) + value(Token::Neg(3));
+            sum - (value(Token::Num(7)) + 100)
+        }")
+    }
+
+    #[test]
+    fn test_for_loops() -> Result<(), AnyError> {
+        run("fn main() -> i32 {
+            var sum = 0;
+            for i in 0..4 {
+                sum = sum + i
+            };
+            let i = 10;
+            for x in [i, 20, 30] {
+                let i = x / 10;
+                sum = sum + i
+            };
+            for i in 5..2 {
+                sum = 100
+            };
+            sum - (i + 2)
+        }")
+    }
+
+    #[test]
+    fn test_loops() -> Result<(), AnyError> {
+        let src = "fn main() -> i32 {
+            var n = 0;
+            let found = 'outer: loop {
+                'rows: for i in 1..10 {
+                    if i == 3 { continue 'rows };
+                    var j = 0;
+                    while j < i {
+                        j = j + 1;
+                        if j == 2 { continue };
+                        n = n + 1;
+                        if n == 6 { break 'outer (i * 10) + j }
+                    }
+                }
+            };
+            # Borrows taken inside the loop end when it's broken out of
+            var x = 0;
+            loop { let r = &mut x; *r = found; break };
+            x - 51
+        }";
+        run_checked(src)
+    }
+
+    #[test]
+    fn test_early_return() -> Result<(), AnyError> {
+        let src = "fn find(xs: [i32; 4], x: i32) -> i32 {
+            var i = 0;
+            for y in xs {
+                if y == x { return i };
+                i = i + 1
+            };
+            -1
+        }
+
+        fn main() -> i32 {
+            let xs = [4, 8, 15, 16];
+            if find(xs, 3) != -1 { return 1 };
+            find(xs, 15) - 2
+        }";
+        run_checked(src)
+    }
+
+    #[test]
+    fn test_closures() -> Result<(), AnyError> {
+        let src = "fn apply(f: (i32) -> i32, x: i32) -> i32 {
+            f(x)
+        }
+
+        fn adder(n: i32) -> (i32) -> i32 {
+            move |x: i32| x + n
+        }
+
+        fn main() -> i32 {
+            let y = 10;
+            var count = 0;
+            if true {
+                let inc = || count = count + 1;
+                inc();
+                inc()
+            };
+            let sign = |x: i32| { if x < 0 { return -1 }; 1 };
+            apply(|x: i32| x + y, count) + (adder(5)(sign(-3)) - 16)
+        }";
+        run(src)?;
+        run_checked(src)
+    }
+
+    #[test]
+    fn test_generics() -> Result<(), AnyError> {
+        let src = "struct Pair {
+            a: i32,
+            b: i32,
+        }
+
+        fn id<T>(x: T) -> T {
+            let y: T = x;
+            y
+        }
+
+        fn apply<T, U>(f: (T) -> U, x: T) -> U {
+            f(x)
+        }
+
+        fn main() -> i32 {
+            let p = id(Pair { a: 1, b: 2 });
+            let neg = apply(|x: bool| !x, id::<bool>(false));
+            if neg {
+                apply(id::<i32>, p.b) - (p.a + 1)
+            } else {
+                1
+            }
+        }";
+        run(src)?;
+        run_checked(src)
+    }
+
+    #[test]
+    fn test_traits() -> Result<(), AnyError> {
+        let src = "struct Counter {
+            n: i32,
+        }
+
+        trait Step {
+            fn step(&mut self) -> i32;
+        }
+
+        impl Counter {
+            fn new() -> Self {
+                Counter { n: 0 }
+            }
+        }
+
+        impl Step for Counter {
+            fn step(&mut self) -> i32 {
+                (*self).n = (*self).n + 1;
+                (*self).n
+            }
+        }
+
+        impl Step for i32 {
+            fn step(&mut self) -> i32 {
+                *self = *self * 2;
+                *self
+            }
+        }
+
+        fn twice<T: Step>(t: &mut T) -> i32 {
+            t.step();
+            t.step()
+        }
+
+        fn main() -> i32 {
+            var c = Counter::new();
+            var x = 3;
+            c.step();
+            twice(&mut c) - (twice(&mut x) - 9)
+        }";
+        run(src)?;
+        run_checked(src)
+    }
+
+    #[test]
+    fn test_function_paths() -> Result<(), AnyError> {
+        // A function from an impl or module can be used as a value, and called through it later
+        let src = "struct P {
+            x: i32,
+        }
+
+        impl P {
+            fn zero() -> i32 {
+                0
+            }
+
+            fn one(x: i32) -> i32 {
+                x - 1
+            }
+        }
+
+        mod m {
+            pub fn two() -> i32 {
+                super::P::one(3)
+            }
+        }
+
+        fn main() -> i32 {
+            let f = P::zero;
+            let g = P::one;
+            let h = m::two;
+            f() + g(1) + (h() - 2)
+        }";
+        run(src)?;
+        run_checked(src)
+    }
+
+    #[test]
+    fn test_method_names() -> Result<(), AnyError> {
+        let src = "struct P {
+            x: i32,
+        }
+
+        trait Get {
+            fn get(&self) -> i32;
+        }
+
+        trait Half {
+            fn get(&self) -> i32;
+        }
+
+        impl P {
+            fn get(&self) -> i32 {
+                (*self).x
+            }
+        }
+
+        impl Get for P {
+            fn get(&self) -> i32 {
+                (*self).x * 2
+            }
+        }
+
+        impl Half for P {
+            fn get(&self) -> i32 {
+                (*self).x / 2
+            }
+        }
+
+        fn get<T: Get>(t: &T) -> i32 {
+            t.get()
+        }
+
+        fn half<T: Half>(t: &T) -> i32 {
+            t.get()
+        }
+
+        fn main() -> i32 {
+            let p = P { x: 4 };
+            # The inherent method comes first, while a bound picks out the method from its trait
+            (p.get() + P::get(&p)) - (get(&p) + (half(&p) - 2))
+        }";
+        run(src)
+    }
+
+    fn assert_fails(res: Result<(), AnyError>, msg: &str) {
+        let err = format!("{:?}", res.unwrap_err());
+        assert!(err.contains(msg), "{}", err);
+    }
+
+    #[test]
+    fn test_use_after_move() {
+        let src = "coro count()() -> i32 {
+            var i = 0;
+            while true { yield i; i = i + 1 }
+        }
+
+        fn main() -> i32 {
+            let a = count();
+            let b = a;
+            a() + b() - 1
+        }";
+        assert!(run(src).is_ok());
+        assert_fails(run_checked(src), "Use after move: 'a'");
+    }
+
+    #[test]
+    fn test_dead_location() {
+        let src = "fn main() -> i32 {
+            let x = 0;
+            var r = &x;
+            if true { let y = 1; r = &y };
+            *r - 1
+        }";
+        assert_fails(run(src), "Dangling reference");
+        assert_fails(run_checked(src), "Use of dead location: 'y'");
+    }
+
+    #[test]
+    fn test_borrows_end_with_scope() -> Result<(), AnyError> {
+        let src = "fn main() -> i32 {
+            var x = 1;
+            if true { let r = &mut x; *r = 2 };
+            let q = &mut x;
+            *q = 3;
+            let s = &mut x;
+            x - 3
+        }";
+        assert_fails(run_checked(src), "Already borrowed: 'x'");
+        run_checked(&src.replace("let s = &mut x;", ""))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_accepted_by_borrowck() -> Result<(), AnyError> {
+        // The programs the static borrow checker accepts run with ownership checked too
+        let point = "struct P { x: i32 }
+        impl P {
+            fn get(&self) -> &i32 { &(*self).x }
+            fn take(self) -> i32 { self.x }
+        }
+        static S: P = P { x: 1 };
+        const C: P = P { x: 2 };
+        fn get() -> &P { &S }
+        fn first<T>(xs: &[T; 2]) -> &T { &(*xs)[0] }
+        coro count()() -> i32 {
+            var i = 0;
+            while true { yield i; i = i + 1 }
+        }";
+        let bodies = [
+            "let a = count(); let b = a; b()",
+            "var x = 1; let r = &x; let y = *r; x = 2; x - 2",
+            "var i = 0; var s = 0; while i < 3 { let r = &i; s = *r; i = i + 1 }; s - 2",
+            "var t = (1, 2); let r = &t.0; let z = *r; t.1 = z; t.1 - 1",
+            "var x = 1; let r = &mut x; let r = 2; x = r; x - 2",
+            "var x = 1; let r = &mut x; *r = 2; let q = &(*r); *q - 2",
+            "var n = 0; let inc = || n = n + 1; inc(); inc(); n - 2",
+            "let xs = [1, 2]; *first(&xs) - 1",
+            "let p = P { x: 1 }; let x = *p.get(); p.take() - x",
+            "let x; if true { x = 1 } else { x = 2 }; x - 1",
+            "var x; x = 1; x = 2; x - 2",
+            "let c = C; let d = C; S.x - (*get()).x",
+        ];
+        for body in bodies {
+            run_checked(&format!("{point} fn main() -> i32 {{ {body} }}"))?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_second_mutable_borrow() {
+        let src = "fn main() -> i32 {
+            var x = 1;
+            let r = &mut x;
+            let q = &mut x;
+            *q = 2;
+            *q = 3;
+            *r - 3
+        }";
+        assert!(run(src).is_ok());
+        assert_fails(run_checked(src), "Already borrowed: 'x'");
+    }
+
+    #[test]
+    fn test_struct_moves() {
+        let src = "struct Point { x: i32, y: i32 }
+
+        fn main() -> i32 {
+            let p = Point { x: 1, y: 2 };
+            let q = p;
+            p.x + q.y - 3
+        }";
+        assert!(run(src).is_ok());
+        assert_fails(run_checked(src), "Use after move: 'p'");
+    }
+
+    #[test]
+    fn test_integer_widths() -> Result<(), AnyError> {
+        run("fn main() -> i32 {
+            let a: u8 = 200;
+            let b: i64 = 5000000000;
+            let c = 18446744073709551615u64;
+            let d: i8 = -128;
+            let sums = ((a + 55) == 255) && ((b * 2) == 10000000000);
+            if sums && ((c / 5) == 3689348814741910323) && ((d / 2) == -64) {
+                0
+            } else {
+                1
+            }
+        }")?;
+        assert_fails(
+            run("fn main() -> i32 { let a: u8 = 200; let b = a + 56; 0 }"),
+            "Overflow: the result of '+' doesn't fit in 'u8'",
+        );
+        assert_fails(
+            run("fn main() -> i32 { let a = 0u32; let b = a - 1; 0 }"),
+            "Overflow: the result of '-' doesn't fit in 'u32'",
+        );
+        assert_fails(
+            run("fn main() -> i32 { let a: i8 = -128; let b = -a; 0 }"),
+            "Overflow: the result of '-' doesn't fit in 'i8'",
+        );
+        assert_fails(
+            run("fn main() -> i32 { let a = 0i16; 1i16 / a; 0 }"),
+            "Division by zero",
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_floats() -> Result<(), AnyError> {
+        run("fn main() -> i32 {
+            let third: f32 = 1.0 / 3.0;
+            let precise = 1.0 / 3.0;
+            let rounded = (third == 0.33333334) && (precise != 0.33333334);
+            let sums = ((0.1f32 + 0.2) == 0.3) && ((0.1 + 0.2) != 0.3);
+            let inf = 1.0 / 0.0;
+            let nan = 0.0 / 0.0;
+            if rounded && sums && (inf > 1e308) && (nan != nan) && (-2.5 < -2e0) {
+                0
+            } else {
+                1
+            }
+        }")
+    }
+
+    #[test]
+    fn test_strings() -> Result<(), AnyError> {
+        run_checked(
+            "fn main() -> i32 {
+            let s = \"héllo\" + \" \" + \"wörld\";
+            let sorted = (\"apple\" < \"banana\") && (\"b\" > \"abc\") && (s != \"héllo\");
+            if sorted && (s.len() == 11) && (s[1] == 'é') && (s[6..11] == \"wörld\") {
+                0
+            } else {
+                1
+            }
+        }",
+        )?;
+        assert_fails(
+            run("fn main() -> i32 { let s = \"héllo\"; let c = s[5]; 0 }"),
+            "Index out of bounds: the index is 5 but the length is 5",
+        );
+        assert_fails(
+            run("fn main() -> i32 { let s = \"abc\"; let t = s[2..4]; 0 }"),
+            "Invalid slice: 2..4 of a string of length 3",
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_intrinsics() -> Result<(), AnyError> {
+        // Exiting with zero ends the program successfully, even from deep inside it
+        run(
+            "fn stop(x: i32) -> i32 { if x == 0 { exit(0) } else { stop(x - 1) } }
+            fn main() -> i32 { assert(true); assert_eq((1, 'a'), (1, 'a')); stop(3); 1 }",
+        )?;
+        assert_fails(
+            run("fn main() -> i32 { exit(2); 0 }"),
+            "Non-zero exit code: 2",
+        );
+        assert_fails(
+            run("fn main() -> i32 { let x = 1; assert_eq(&x, &2); 0 }"),
+            "Assertion failed: '1' is not equal to '2'",
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_options() -> Result<(), AnyError> {
+        run_checked(
+            "fn half(x: i32) -> Result<i32, String> {
+                if (x % 2) == 0 { Ok(x / 2) } else { Err(\"odd\") }
+            }
+            fn quarter(x: i32) -> Result<i32, String> {
+                let y = half(x)?;
+                half(y)
+            }
+            fn main() -> i32 {
+                let a = match quarter(12) { Ok(n) => n, Err(_) => 0 };
+                let b = match quarter(6) { Ok(_) => \"\", Err(e) => e };
+                let c: Option<i32> = None;
+                let d = match c { Some(n) => n, None => 7 };
+                if (a == 3) && (b == \"odd\") && (d == 7) { 0 } else { 1 }
+            }",
+        )
+    }
+
+    #[test]
+    fn test_bitwise_operators() -> Result<(), AnyError> {
+        run("fn main() -> i32 {
+            var x = 17;
+            x %= 5;
+            x <<= 3;
+            var b: u8 = 200;
+            b <<= 1;
+            let masks = ((12 & 10) == 8) && ((12 | 10) == 14) && ((12 ^ 10) == 6);
+            let shifts = ((-8 >> 1) == -4) && ((1u64 << 40) == 1099511627776) && (b == 144);
+            let bools = (true ^ true) | (false & true);
+            if masks && shifts && (x == 16) && ((-7 % 2) == -1) && !bools {
+                0
+            } else {
+                1
+            }
+        }")?;
+        assert_fails(
+            run("fn main() -> i32 { let n = 32; 1 << n }"),
+            "Overflow: the result of '<<' doesn't fit in 'i32'",
+        );
+        assert_fails(
+            run("fn main() -> i32 { let a = 0; 5 % a }"),
+            "Division by zero",
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_compound_assignment() -> Result<(), AnyError> {
+        // The place is only evaluated once, so the index is only found once
+        let src = "fn next(n: &mut i32) -> i32 {
+            *n += 1;
+            *n
+        }
+
+        fn main() -> i32 {
+            var calls = 0;
+            var xs = [1, 2, 3];
+            xs[next(&mut calls)] += 10;
+            xs[next(&mut calls) - 1] *= 3;
+            var s = \"a\";
+            s += \"b\";
+            if (calls == 2) && (xs[1] == 36) && (s == \"ab\") { 0 } else { 1 }
+        }";
+        run(src)?;
+        run_checked(src)
+    }
+
+    #[test]
+    fn test_inference() -> Result<(), AnyError> {
+        let src = "fn main() -> i32 {
+            var xs = [];
+            xs = [1, 2, 3];
+            let n;
+            n = xs[2];
+            let add = |a, b| a + b;
+            let twice = |f: (i64) -> i64, x| f(f(x));
+            if twice(|x| x * 2, 5) == 20 { add(n, 1) - 4 } else { 1 }
+        }";
+        run(src)?;
+        run_checked(src)
+    }
+
+    #[test]
+    fn test_globals() -> Result<(), AnyError> {
+        let src = "const SIZES: [i64; 2] = [PAGE, PAGE * 2];
+        const PAGE: i64 = 1 << 12;
+        static ORIGIN: (i32, i32) = (0, -1);
+        fn origin() -> &(i32, i32) { &ORIGIN }
+        fn main() -> i32 {
+            let a = origin();
+            let b = origin();
+            if SIZES[1] == 8192 { (*a).1 - (*b).1 } else { 1 }
+        }";
+        run(src)?;
+        run_checked(src)
+    }
+
+    #[test]
+    fn test_tuple_structs() -> Result<(), AnyError> {
+        let src = "type Pair = (i32, i32);
+        struct Meters(i32);
+        struct Span(Meters, Pair);
+        fn main() -> i32 {
+            var span = Span(Meters(3), (1, 2));
+            span.0 = Meters((span.0).0 + 1);
+            let pair: Pair = span.1;
+            (span.0).0 - (pair.0 + pair.1 + 1)
+        }";
+        run(src)?;
+        run_checked(src)
+    }
+}
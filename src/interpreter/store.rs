@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+
+use super::{
+    error::Error,
+    value::{Step, Value},
+};
+use crate::ast::tree::TypeBound;
+use crate::semantics::types::Type;
+
+use anyhow::Error as AnyError;
+
+pub type Loc = usize;
+
+// A location in the store, along with the path to a field or element nested inside it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Place {
+    pub loc: Loc,
+    pub path: Vec<Step>,
+}
+
+impl Place {
+    pub fn new(loc: Loc) -> Self {
+        Self { loc, path: vec![] }
+    }
+
+    pub fn project(mut self, step: Step) -> Self {
+        self.path.push(step);
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Borrow {
+    Shared,
+    Mut,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Life {
+    Alive,
+    Moved,
+    // The scope that created the location has ended
+    Dropped,
+    // Declared without a value, which the first assignment gives it
+    Uninit,
+}
+
+#[derive(Debug)]
+struct Cell<T: TypeBound> {
+    value: Value<T>,
+    // The variable bound to the location, used in error messages
+    name: Option<String>,
+    mutable: bool,
+    life: Life,
+    borrow: Option<Borrow>,
+}
+
+// Every value that lives in a variable, or is pointed to by a reference, has a location here
+#[derive(Debug)]
+pub struct Store<T: TypeBound> {
+    cells: HashMap<Loc, Cell<T>>,
+    next: Loc,
+    // Whether to track and enforce the ownership of each location
+    check: bool,
+}
+
+impl<T: TypeBound> Store<T> {
+    pub fn new(check: bool) -> Self {
+        Self {
+            cells: HashMap::new(),
+            next: 0,
+            check,
+        }
+    }
+
+    pub fn alloc(&mut self, value: Value<T>, name: Option<&str>, mutable: bool) -> Loc {
+        let loc = self.next;
+        self.next += 1;
+        let cell = Cell {
+            value,
+            name: name.map(str::to_owned),
+            mutable,
+            life: Life::Alive,
+            borrow: None,
+        };
+        self.cells.insert(loc, cell);
+        loc
+    }
+
+    // The location of a variable that's declared without a value, until it's assigned one
+    pub fn alloc_uninit(&mut self, name: &str, mutable: bool) -> Loc {
+        let loc = self.alloc(Value::Tuple(vec![]), Some(name), mutable);
+        if let Some(cell) = self.cells.get_mut(&loc) {
+            cell.life = Life::Uninit;
+        }
+        loc
+    }
+
+    // Dead locations are kept around when checking ownership, to report any later use of them
+    pub fn free(&mut self, loc: Loc) {
+        if self.check {
+            if let Some(cell) = self.cells.get_mut(&loc) {
+                cell.life = Life::Dropped;
+            }
+        } else {
+            self.cells.remove(&loc);
+        }
+    }
+
+    pub fn load(&mut self, place: &Place) -> Result<Value<T>, Error> {
+        self.alive(place.loc)?;
+        Ok(self.place_mut(place)?.clone())
+    }
+
+    pub fn type_of(&mut self, place: &Place) -> Result<Type, Error> {
+        Ok(self.place_mut(place)?.type_of())
+    }
+
+    // Overwrites the value at the place, which must keep the same type
+    pub fn store(&mut self, place: &Place, value: Value<T>) -> Result<(), AnyError> {
+        let cell = self.cell(place.loc)?;
+        // The first value of a variable can be assigned even when it's immutable
+        if cell.life == Life::Uninit && place.path.is_empty() {
+            let cell = self.cell_mut(place.loc)?;
+            cell.value = value;
+            cell.life = Life::Alive;
+            return Ok(());
+        }
+        if !cell.mutable {
+            match &cell.name {
+                Some(name) => Err(Error::ImmutableVariable(name.clone()))?,
+                None => Err(Error::ImmutableLocation(place.loc))?,
+            }
+        }
+        // Assigning to a whole location gives it a value again, after it's been moved out of
+        if self.check && cell.life == Life::Moved && place.path.is_empty() {
+            self.cell_mut(place.loc)?.life = Life::Alive;
+        }
+        // Even without checking ownership, a variable has to be given a value before it's changed
+        self.alive(place.loc)?;
+        let old = self.place_mut(place)?;
+        let (old_ty, ty) = (old.type_of(), value.type_of());
+        // An empty array gets its type from how it's used, so any array can replace it
+        let empty = old_ty == Type::Array(Box::new(Type::Never), 0);
+        if !ty.coerces_to(&old_ty) && !(empty && old_ty.coerces_to(&ty)) {
+            Err(Error::UnexpectedType(old_ty, ty))?
+        }
+        *old = value;
+        Ok(())
+    }
+
+    // Moving out of a field or element moves out of the whole location
+    pub fn move_out(&mut self, loc: Loc) -> Result<(), Error> {
+        if self.check {
+            self.cell_mut(loc)?.life = Life::Moved;
+        }
+        Ok(())
+    }
+
+    // Borrows a location, giving back how it was borrowed before
+    pub fn borrow(&mut self, loc: Loc, borrow: Borrow) -> Result<Option<Borrow>, Error> {
+        let cell = self.cell(loc)?;
+        if borrow == Borrow::Mut && !cell.mutable {
+            match &cell.name {
+                Some(name) => Err(Error::ImmutableVariable(name.clone()))?,
+                None => Err(Error::ImmutableLocation(loc))?,
+            }
+        }
+        if !self.check {
+            return Ok(None);
+        }
+        self.alive(loc)?;
+        let prev = self.cell(loc)?.borrow;
+        match (prev, borrow) {
+            (None, _) | (Some(Borrow::Shared), Borrow::Shared) => {
+                self.cell_mut(loc)?.borrow = Some(borrow);
+                Ok(prev)
+            }
+            _ => Err(Error::AlreadyBorrowed(self.describe(loc))),
+        }
+    }
+
+    pub fn restore(&mut self, loc: Loc, borrow: Option<Borrow>) {
+        if let Some(cell) = self.cells.get_mut(&loc) {
+            cell.borrow = borrow;
+        }
+    }
+
+    fn alive(&self, loc: Loc) -> Result<(), Error> {
+        match self.cell(loc)?.life {
+            Life::Alive => Ok(()),
+            Life::Moved => Err(Error::UseAfterMove(self.describe(loc))),
+            Life::Dropped => Err(Error::DeadLocation(self.describe(loc))),
+            Life::Uninit => Err(Error::Uninitialized(self.describe(loc))),
+        }
+    }
+
+    fn describe(&self, loc: Loc) -> String {
+        match self.cells.get(&loc).and_then(|cell| cell.name.as_ref()) {
+            Some(name) => format!("'{}'", name),
+            None => format!("location {}", loc),
+        }
+    }
+
+    fn cell(&self, loc: Loc) -> Result<&Cell<T>, Error> {
+        self.cells.get(&loc).ok_or(Error::DanglingReference(loc))
+    }
+
+    fn cell_mut(&mut self, loc: Loc) -> Result<&mut Cell<T>, Error> {
+        self.cells
+            .get_mut(&loc)
+            .ok_or(Error::DanglingReference(loc))
+    }
+
+    fn place_mut(&mut self, place: &Place) -> Result<&mut Value<T>, Error> {
+        self.cell_mut(place.loc)?.value.place_mut(&place.path)
+    }
+}
@@ -1,28 +1,297 @@
-use crate::ast::tree::{Function, TypeBound};
-use crate::semantics::types::{Size, Type};
+use super::{
+    coro::Frame,
+    error::Error,
+    store::{Loc, Place},
+};
+use crate::ast::tree::{self, Function, Operator, Pattern, Sequence, TypeBound};
+use crate::semantics::{
+    prelude::Intrinsic,
+    types::{Size, Type},
+};
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Value<T: TypeBound> {
-    Signed(i64),
+    Signed(i64, Size),
+    Unsigned(u64, Size),
+    // An f32 is kept as an f64, rounded to the precision of an f32 after every operation
+    Float(f64, Size),
     Bool(bool),
     Char(char),
+    String(String),
     Tuple(Vec<Value<T>>),
     Array(Vec<Value<T>>),
+    // A struct's name, along with its fields in the order they were written
+    Struct(String, Vec<(String, Value<T>)>),
+    // The enum's name, the variant and the values it carries
+    Enum(String, String, Vec<Value<T>>),
+    // The start and the end of a range, which doesn't include the end, along with their integer type
+    Range(i128, i128, Type),
     Function(Function<T>),
+    // A function from the prelude, which the interpreter runs itself
+    Intrinsic(Intrinsic),
+    Closure(Closure<T>),
+    Frame(Frame<T>),
+    // Points to a place in the store, along with the type of the value there, and whether it's mutable
+    Reference(Place, Type, bool),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Closure<T: TypeBound> {
+    pub params: Vec<(String, Type)>,
+    pub ret: Type,
+    pub body: Sequence<T>,
+    pub captured: Vec<(String, Captured<T>)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Captured<T: TypeBound> {
+    // Captured by reference, so the closure shares the variable's location
+    Ref(Loc),
+    Value(Box<Value<T>>),
 }
 
 impl<T: TypeBound> Value<T> {
     pub fn type_of(&self) -> Type {
         match &self {
-            Value::Signed(_) => Type::Signed(Size::ThirtyTwo),
+            Value::Signed(_, size) => Type::Signed(*size),
+            Value::Unsigned(_, size) => Type::Unsigned(*size),
+            Value::Float(_, size) => Type::Float(*size),
             Value::Bool(_) => Type::Bool,
             Value::Char(_) => Type::Char,
+            Value::String(_) => Type::String,
             Value::Tuple(x) => Type::Tuple(x.iter().map(|x| x.type_of()).collect()),
-            Value::Array(x) => Type::Array(Box::new(Type::Char), x.len()),
-            Value::Function(x) => Type::Function {
-                args: x.args.iter().map(|x| x.1.clone()).collect(),
+            // The first element gives the type, and an empty array has no elements of any type
+            Value::Array(x) => Type::Array(
+                Box::new(x.first().map_or(Type::Never, |x| x.type_of())),
+                x.len(),
+            ),
+            // The builtin enums are typed by what they hold
+            Value::Enum(name, variant, vals) => {
+                let tys = vals.iter().map(|val| val.type_of()).collect::<Vec<_>>();
+                Type::of_variant(name, variant, &tys).unwrap_or(Type::Named(name.clone()))
+            }
+            Value::Struct(name, _) => Type::Named(name.clone()),
+            Value::Range(_, _, ty) => Type::Range(Box::new(ty.clone())),
+            Value::Function(x) => x.get_type(),
+            Value::Intrinsic(x) => x.get_type(),
+            Value::Closure(x) => Type::Function {
+                args: x.params.iter().map(|(_, ty)| ty.clone()).collect(),
                 ret: Box::new(x.ret.clone()),
             },
+            Value::Frame(x) => x.type_of(),
+            Value::Reference(_, ty, mutable) => Type::Reference(Box::new(ty.clone()), *mutable),
+        }
+    }
+
+    // Whether reading the value copies it, instead of moving it out of its location
+    pub fn is_copy(&self) -> bool {
+        match self {
+            // The builtin enums are copied when what they hold is, like their types
+            Value::Enum(name, _, vals) if name == "Option" || name == "Result" => {
+                vals.iter().all(|val| val.is_copy())
+            }
+            Value::Frame(_)
+            | Value::Reference(_, _, true)
+            | Value::Struct(..)
+            | Value::Enum(..) => false,
+            Value::Tuple(vals) | Value::Array(vals) => vals.iter().all(|val| val.is_copy()),
+            // Closures have function types, so they're copied like functions
+            _ => true,
+        }
+    }
+
+    // Whether the value still points into the store, so the borrows behind it have to last
+    pub fn holds_refs(&self) -> bool {
+        match self {
+            Value::Reference(..) | Value::Frame(_) => true,
+            Value::Closure(closure) => closure
+                .captured
+                .iter()
+                .any(|(_, captured)| matches!(captured, Captured::Ref(_))),
+            Value::Tuple(vals) | Value::Array(vals) | Value::Enum(_, _, vals) => {
+                vals.iter().any(|val| val.holds_refs())
+            }
+            Value::Struct(_, fields) => fields.iter().any(|(_, val)| val.holds_refs()),
+            _ => false,
+        }
+    }
+
+    // Whether the value matches the pattern, collecting the values bound by it
+    pub fn matches(&self, pattern: &Pattern, bindings: &mut Vec<(String, Value<T>)>) -> bool {
+        match (pattern, self) {
+            (Pattern::Wildcard, _) => true,
+            (Pattern::Binding(name), val) => {
+                bindings.push((name.clone(), val.clone()));
+                true
+            }
+            (Pattern::Value(tree::Value::Number(x, _)), val) => val.as_int() == Some(*x),
+            (Pattern::Value(tree::Value::Float(x, _)), Value::Float(y, _)) => x == y,
+            (Pattern::Value(tree::Value::Bool(x)), Value::Bool(y)) => x == y,
+            (Pattern::Value(tree::Value::Char(x)), Value::Char(y)) => x == y,
+            (Pattern::Value(tree::Value::String(x)), Value::String(y)) => x == y,
+            (Pattern::Variant { variant, args, .. }, Value::Enum(_, name, vals)) => {
+                variant == name
+                    && args.len() == vals.len()
+                    && args
+                        .iter()
+                        .zip(vals)
+                        .all(|(arg, val)| val.matches(arg, bindings))
+            }
+            _ => false,
+        }
+    }
+
+    // The value a yield evaluates to, once its coroutine is resumed with these values
+    pub fn resumed(mut vals: Vec<Value<T>>) -> Self {
+        if vals.len() == 1 {
+            vals.remove(0)
+        } else {
+            Value::Tuple(vals)
+        }
+    }
+}
+
+// A single step into a value, used to reach a tuple field, struct field or array element
+#[derive(Clone, Debug, PartialEq)]
+pub enum Step {
+    Field(usize),
+    Member(String),
+    Element(i64),
+}
+
+impl<T: TypeBound> Value<T> {
+    // Takes out the field or element that the step points to
+    pub fn project(mut self, step: &Step) -> Result<Value<T>, Error> {
+        Ok(self.project_mut(step)?.clone())
+    }
+
+    pub fn project_mut(&mut self, step: &Step) -> Result<&mut Value<T>, Error> {
+        match step {
+            Step::Field(i) => match self {
+                Value::Tuple(vals) => {
+                    if *i >= vals.len() {
+                        let ty = Type::Tuple(vals.iter().map(|x| x.type_of()).collect());
+                        Err(Error::InvalidUnary(Operator::TupleIndex(*i), ty))?
+                    }
+                    Ok(&mut vals[*i])
+                }
+                val => Err(Error::InvalidUnary(Operator::TupleIndex(*i), val.type_of())),
+            },
+            Step::Member(field) => match self {
+                Value::Struct(name, fields) => {
+                    let ty = Type::Named(name.clone());
+                    fields
+                        .iter_mut()
+                        .find(|(name, _)| name == field)
+                        .map(|(_, val)| val)
+                        .ok_or(Error::NoSuchField(field.clone(), ty))
+                }
+                val => Err(Error::NoSuchField(field.clone(), val.type_of())),
+            },
+            Step::Element(i) => match self {
+                Value::Array(vals) => {
+                    let len = vals.len();
+                    usize::try_from(*i)
+                        .ok()
+                        .and_then(|i| vals.get_mut(i))
+                        .ok_or(Error::IndexOutOfBounds(*i, len))
+                }
+                val => Err(Error::InvalidBinary(
+                    Operator::ArrayIndex,
+                    val.type_of(),
+                    Type::Signed(Size::ThirtyTwo),
+                )),
+            },
+        }
+    }
+
+    // Follows a path of steps, to the value that would be assigned to
+    pub fn place_mut(&mut self, path: &[Step]) -> Result<&mut Value<T>, Error> {
+        path.iter()
+            .try_fold(self, |val, step| val.project_mut(step))
+    }
+
+    // An index that's too large for an i64 is out of bounds of any array anyway
+    pub fn as_index(&self) -> Result<i64, Error> {
+        match self.as_int() {
+            Some(i) => Ok(i64::try_from(i).unwrap_or(i64::MAX)),
+            None => Err(Error::UnexpectedType(
+                Type::Signed(Size::ThirtyTwo),
+                self.type_of(),
+            )),
+        }
+    }
+
+    // The character at an index of a string, or the substring for a range of indices
+    pub fn index_string(&self, index: &Value<T>) -> Result<Value<T>, Error> {
+        let Value::String(s) = self else {
+            return Err(Error::InvalidBinary(
+                Operator::ArrayIndex,
+                self.type_of(),
+                index.type_of(),
+            ));
+        };
+        let len = s.chars().count();
+        match index {
+            Value::Range(start, end, _) => {
+                let (Ok(from), Ok(to)) = (usize::try_from(*start), usize::try_from(*end)) else {
+                    return Err(Error::InvalidSlice(*start, *end, len));
+                };
+                if from > to || to > len {
+                    return Err(Error::InvalidSlice(*start, *end, len));
+                }
+                Ok(Value::String(
+                    s.chars().skip(from).take(to - from).collect(),
+                ))
+            }
+            index => {
+                let i = index.as_index()?;
+                usize::try_from(i)
+                    .ok()
+                    .and_then(|i| s.chars().nth(i))
+                    .map(Value::Char)
+                    .ok_or(Error::IndexOutOfBounds(i, len))
+            }
+        }
+    }
+
+    // Calls a method that comes with a builtin type, if there is one by that name
+    pub fn builtin_method(&self, name: &str) -> Option<Value<T>> {
+        match (self, name) {
+            (Value::String(s), "len") => {
+                Some(Value::Unsigned(s.chars().count() as u64, Size::SixtyFour))
+            }
+            _ => None,
+        }
+    }
+
+    // The value of an integer of any type, which is wide enough to hold all of them
+    pub fn as_int(&self) -> Option<i128> {
+        match self {
+            Value::Signed(x, _) => Some((*x).into()),
+            Value::Unsigned(x, _) => Some((*x).into()),
+            _ => None,
+        }
+    }
+
+    // A float of the given size, rounded to fit in it
+    pub fn float(x: f64, size: Size) -> Value<T> {
+        match size {
+            Size::ThirtyTwo => Value::Float(x as f32 as f64, size),
+            _ => Value::Float(x, size),
+        }
+    }
+
+    // An integer of the given type, unless it doesn't fit in it
+    pub fn from_int(x: i128, ty: &Type) -> Option<Value<T>> {
+        let (min, max) = ty.bounds()?;
+        if x < min || x > max {
+            return None;
+        }
+        match ty {
+            Type::Signed(size) => Some(Value::Signed(x as i64, *size)),
+            Type::Unsigned(size) => Some(Value::Unsigned(x as u64, *size)),
+            _ => None,
         }
     }
 }
@@ -5,7 +5,7 @@
 #![feature(trivial_bounds)]
 #![feature(box_patterns)]
 // Cargo Clippy settings
-#![allow(dead_code, unused_variables)]
+#![allow(dead_code, unused_variables, clippy::result_large_err)]
 
 // Crates
 use crate::error::Error;
@@ -13,14 +13,17 @@ use ::log::debug;
 use ::log::info;
 use anyhow::Context;
 use anyhow::Error as AnyError;
+use ast::tree::Program;
 use clap::Parser;
 use colored::Colorize;
 use interpreter::run::run_program;
+use parser::load::load;
 use parser::parse::parse;
 use proptest::test_runner::Reason;
 use proptest::test_runner::{Config, TestCaseError, TestRunner};
+use semantics::borrowck::check;
 use semantics::typeinfer::infer;
-use std::fs;
+use std::path::Path;
 
 // Modules
 mod ast;
@@ -39,14 +42,18 @@ struct Args {
     /// Logging messages
     #[clap(short, long, default_value = "false")]
     verbose: bool,
+    /// Track ownership and borrows at runtime, halting on any violation
+    #[clap(long, default_value = "false")]
+    check_ownership: bool,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
     log::init(args.verbose).map_err(|_| Error::Log)?;
     if let Some(src) = args.file {
-        let source = fs::read_to_string(src).context("Failed to read file")?;
-        run_str(&source).context("Failed to run program".bright_red())?;
+        load(Path::new(&src))
+            .and_then(|ast| run_ast(ast, args.check_ownership))
+            .context("Failed to run program".bright_red())?;
     } else {
         info!("Running a random program");
         let mut runner = TestRunner::new(Config {
@@ -55,15 +62,20 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         });
         let ast_strat = ast::proptest::arb_prgm();
         runner.run(&ast_strat, |ast| {
-            run_str(format!("{}", ast).as_str()).map_err(|err| TestCaseError::Fail(into(err)))
+            run_str(format!("{}", ast).as_str(), args.check_ownership)
+                .map_err(|err| TestCaseError::Fail(into(err)))
         })?;
     };
     Ok(())
 }
 
 // Run program from string
-fn run_str(source: &str) -> Result<(), AnyError> {
+fn run_str(source: &str, check_ownership: bool) -> Result<(), AnyError> {
     let ast = parse(source).context("Error while parsing")?;
+    run_ast(ast, check_ownership)
+}
+
+fn run_ast(ast: Program<()>, check_ownership: bool) -> Result<(), AnyError> {
     debug!(
         "{}\n{}",
         "Untyped AST:".bright_yellow(),
@@ -75,7 +87,8 @@ fn run_str(source: &str) -> Result<(), AnyError> {
         "Typed AST:".bright_yellow(),
         format!("{}", ast).bright_cyan()
     );
-    run_program(ast).context("Error while running program")?;
+    check(&ast).context("Error while checking borrows")?;
+    run_program(ast, check_ownership).context("Error while running program")?;
     Ok(())
 }
 
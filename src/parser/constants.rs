@@ -1,7 +1,11 @@
 /// Constants used by the parser
-const DELIMS: [char; 10] = [';', ':', ',', '(', ')', '{', '}', '[', ']', '.'];
-const KEYWORDS: [&str; 8] = ["let", "var", "if", "else", "true", "false", "fn", "while"];
-const OPERATORS: [char; 10] = ['+', '-', '*', '/', '=', '>', '<', '&', '!', '|'];
+const DELIMS: [char; 11] = [';', ':', ',', '(', ')', '{', '}', '[', ']', '.', '?'];
+const KEYWORDS: [&str; 29] = [
+    "let", "var", "if", "else", "true", "false", "fn", "while", "coro", "yield", "mut", "struct",
+    "enum", "match", "for", "in", "loop", "break", "continue", "return", "move", "trait", "impl",
+    "pub", "mod", "use", "const", "static", "type",
+];
+const OPERATORS: [char; 12] = ['+', '-', '*', '/', '%', '=', '>', '<', '&', '!', '|', '^'];
 const COMMENT: char = '#';
 
 pub fn is_comment(c: char) -> bool {
@@ -6,6 +6,8 @@ use super::tokens::Token;
 pub enum Error {
     #[error("Error while parsing number: '{0}'")]
     ParseInt(#[from] std::num::ParseIntError),
+    #[error("Error while parsing float: '{0}'")]
+    ParseFloat(#[from] std::num::ParseFloatError),
     #[error("Unrecognized token: '{0}'")]
     UnrecognizedToken(char, usize),
     #[error("Expected {0}, Received: '{1}'")]
@@ -14,12 +16,16 @@ pub enum Error {
     InvalidOperator(String),
     #[error("Unknown size: '{0}'")]
     InvalidSize(u8),
+    #[error("Unknown suffix on number: '{0}'")]
+    InvalidSuffix(String),
     #[error("Unknown Type: '{0}'")]
     InvalidType(Token),
     #[error("Unterminated char '{0}'")]
     UnterminatedChar(usize),
     #[error("Invalid char '\\{0}'")]
     InvalidEscape(char),
+    #[error("Module file '{0}' is loaded from within itself")]
+    RecursiveModule(String),
 }
 
 pub type Parse<T> = Result<T, Error>;
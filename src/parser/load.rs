@@ -0,0 +1,90 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Error as AnyError};
+
+use super::{error::Error, parse::parse};
+use crate::ast::tree::{Item, Module, Program};
+
+// Reads a program from its file, along with the files of the modules it declares with `mod name;`,
+// which are found next to the file that declares them
+pub fn load(path: &Path) -> Result<Program<()>, AnyError> {
+    load_file(path, &mut vec![])
+}
+
+// The files being loaded are kept, so a module can't end up loading itself forever
+fn load_file(path: &Path, loading: &mut Vec<PathBuf>) -> Result<Program<()>, AnyError> {
+    if loading.iter().any(|file| file == path) {
+        Err(Error::RecursiveModule(path.display().to_string()))?
+    }
+    let source =
+        fs::read_to_string(path).context(format!("Failed to read file '{}'", path.display()))?;
+    let prgm = parse(&source).context(format!("Error while parsing '{}'", path.display()))?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    loading.push(path.to_owned());
+    let items = load_items(prgm.0, dir, loading);
+    loading.pop();
+    Ok(Program(items?))
+}
+
+fn load_items(
+    items: Vec<Item<()>>,
+    dir: &Path,
+    loading: &mut Vec<PathBuf>,
+) -> Result<Vec<Item<()>>, AnyError> {
+    items
+        .into_iter()
+        .map(|item| match item {
+            Item::Mod(Module {
+                name,
+                public,
+                items,
+            }) => {
+                let items = match items {
+                    Some(items) => load_items(items, dir, loading)?,
+                    None => {
+                        let path = dir.join(format!("{}.yk", name));
+                        load_file(&path, loading)
+                            .context(format!("In module '{}'", name))?
+                            .0
+                    }
+                };
+                Ok(Item::Mod(Module {
+                    name,
+                    public,
+                    items: Some(items),
+                }))
+            }
+            item => Ok(item),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::load;
+    use crate::ast::tree::Item;
+
+    #[test]
+    fn test_load() {
+        let prgm = load(Path::new("examples/modules/main.yk")).unwrap();
+        let modules = prgm
+            .0
+            .iter()
+            .filter_map(|item| match item {
+                Item::Mod(def) => Some((def.name.as_str(), def.items.is_some())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(modules, [("geo", true), ("shapes", true)]);
+        let err = format!(
+            "{:?}",
+            load(Path::new("examples/modules/none.yk")).unwrap_err()
+        );
+        assert!(err.contains("Failed to read file"), "{}", err);
+    }
+}
@@ -1,5 +1,6 @@
 mod constants;
 pub mod error;
+pub mod load;
 pub mod parse;
 mod scanner;
 mod tokens;
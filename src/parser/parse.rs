@@ -1,12 +1,19 @@
 use crate::{
-    ast::tree::{Expr, Expression, Function, Operator, Program, Sequence as Seq},
+    ast::tree::{
+        Alias, Arm, Enum as EnumDef, Expr, Expression, FuncKind, Function, Global, Impl, Item,
+        Module, Operator, Pattern, Program, Sequence as Seq, Signature, Struct as StructDef,
+        Trait as TraitDef, Value,
+    },
     parser::error::Error,
     parser::tokens::Keyword::*,
     parser::tokens::TokenType::*,
 };
 
-use super::scanner::Scanner;
-use crate::semantics::types::Type;
+use super::{
+    scanner::Scanner,
+    tokens::{Token, TokenType},
+};
+use crate::semantics::types::{builtin_enum, Type, TypeParam};
 
 // Peek at the next token, and return an error if it doesn't match the pattern
 macro_rules! expect {
@@ -35,54 +42,123 @@ fn parse_opt_type(scan: &mut Scanner) -> Result<Option<Type>, Error> {
     }
 }
 
+// A number type like `i32`, `u8` or `f64`, which starts with its kind and ends with its size
+fn parse_num_type(name: &str) -> Result<Option<Type>, Error> {
+    let Some(bits) = name.get(1..).and_then(|bits| bits.parse::<u8>().ok()) else {
+        return Ok(None);
+    };
+    Ok(match &name[..1] {
+        "i" => Some(Type::Signed(bits.try_into()?)),
+        "u" => Some(Type::Unsigned(bits.try_into()?)),
+        "f" if matches!(bits, 32 | 64) => Some(Type::Float(bits.try_into()?)),
+        "f" => Err(Error::InvalidSize(bits))?,
+        _ => None,
+    })
+}
+
+// The type a number literal is suffixed with, if it has one
+fn parse_suffix(suffix: Option<String>) -> Result<Option<Type>, Error> {
+    match suffix {
+        Some(suffix) => Ok(Some(
+            parse_num_type(&suffix)?.ok_or(Error::InvalidSuffix(suffix))?,
+        )),
+        None => Ok(None),
+    }
+}
+
+// A number literal, which is typed by its suffix if it has one, and is a float if that's a float type
+fn parse_number(x: u64, suffix: Option<String>, negative: bool) -> Result<Value, Error> {
+    let ty = parse_suffix(suffix)?;
+    let x = i128::from(x);
+    let x = if negative { -x } else { x };
+    match ty {
+        Some(Type::Float(_)) => Ok(Value::Float(x as f64, ty)),
+        _ => Ok(Value::Number(x, ty)),
+    }
+}
+
+// A float literal, which can only be suffixed with a float type
+fn parse_float(x: f64, suffix: Option<String>) -> Result<Value, Error> {
+    match parse_suffix(suffix)? {
+        Some(ty) if !ty.is_float() => Err(Error::InvalidSuffix(ty.to_string())),
+        ty => Ok(Value::Float(x, ty)),
+    }
+}
+
 fn parse_type(scan: &mut Scanner) -> Result<Type, Error> {
     let tok = scan.next()?;
     let ty = match tok.token {
-        Name(ref x) => {
-            // First check if the beginning is i, u, f
-            let ty = match &x[..1] {
-                "i" => {
-                    let size = x[1..].parse::<u8>()?.try_into()?;
-                    Type::Signed(size)
-                }
-                _ => match x.as_str() {
-                    "bool" => Type::Bool,
-                    "char" => Type::Char,
+        Name(ref x) => match x.as_str() {
+            _ if let Some(ty) = parse_num_type(x)? => ty,
+            "bool" => Type::Bool,
+            "char" => Type::Char,
+            "String" => Type::String,
+            // The builtin enums, unless there's no list of types for them
+            "Option" | "Result" if scan.peek()?.token == Op(vec!['<']) => {
+                let mut tys = parse_angled(scan, parse_type)?.into_iter().map(Box::new);
+                match (x.as_str(), tys.next(), tys.next(), tys.next()) {
+                    ("Option", Some(ty), None, None) => Type::Option(ty),
+                    ("Result", Some(ty), Some(err), None) => Type::Result(ty, err),
                     _ => return Err(Error::InvalidType(tok)),
-                },
-            };
-            ty
-        }
-        Delim('(') => {
-            let mut types = vec![];
-            loop {
-                if let Delim(')') = scan.peek()?.token {
-                    expect!(scan, Delim(')'))?;
-                    break;
-                }
-                types.push(parse_type(scan)?);
-                if let Delim(',') = scan.peek()?.token {
-                    scan.next()?;
                 }
             }
-            Type::Tuple(types)
+            "range" => {
+                expect!(scan, Delim('('))?;
+                let ty = parse_type(scan)?;
+                expect!(scan, Delim(')'))?;
+                Type::Range(Box::new(ty))
+            }
+            "frame" => {
+                expect!(scan, Delim('('))?;
+                let resume = parse_tuple_type(scan)?;
+                expect!(scan, Op(x) if let ['-', '>'] == x[..])?;
+                let yields = Box::new(parse_type(scan)?);
+                Type::Frame { resume, yields }
+            }
+            // Any other name refers to a struct or enum, which is resolved during type inference
+            _ => Type::Named(parse_path(scan, x.clone())?),
+        },
+        Delim('(') => Type::Tuple(parse_tuple_type(scan)?),
+        Keyword(Coro) => {
+            expect!(scan, Delim('('))?;
+            let args = parse_tuple_type(scan)?;
+            expect!(scan, Delim('('))?;
+            let resume = parse_tuple_type(scan)?;
+            expect!(scan, Op(x) if let ['-', '>'] == x[..])?;
+            let yields = Box::new(parse_type(scan)?);
+            Type::Coro {
+                args,
+                resume,
+                yields,
+            }
         }
         Delim('[') => {
             let ty = parse_type(scan)?;
             expect!(scan, Delim(';'))?;
-            let size = scan.next()?.number()?;
+            let tok = scan.next()?;
+            // The length can be a constant, which type inference puts the value of in
+            let ty = match tok.token {
+                Name(len) => Type::ArrayLen(Box::new(ty), parse_path(scan, len)?),
+                _ => Type::Array(Box::new(ty), tok.number()? as usize),
+            };
             expect!(scan, Delim(']'))?;
-            Type::Array(Box::new(ty), size as usize)
+            ty
         }
+        Op(ref x) if x[..] == ['!'] => Type::Never,
         Op(ref x) => {
             // Ensure all x's are &
             if x.iter().any(|x| *x != '&') {
                 return Err(Error::InvalidType(tok));
             }
+            // A mut only applies to the innermost reference
+            let mutable = scan.peek()?.token == Keyword(Mut);
+            if mutable {
+                scan.next()?; // mut
+            }
             // Box the type as many times as there are &'s
-            let mut ty = parse_type(scan)?;
-            for _ in 0..x.len() {
-                ty = Type::Reference(Box::new(ty));
+            let mut ty = Type::Reference(Box::new(parse_type(scan)?), mutable);
+            for _ in 1..x.len() {
+                ty = Type::Reference(Box::new(ty), false);
             }
             ty
         }
@@ -104,20 +180,73 @@ fn parse_type(scan: &mut Scanner) -> Result<Type, Error> {
     }
 }
 
+// Parses the types of a tuple, after the opening '('
+fn parse_tuple_type(scan: &mut Scanner) -> Result<Vec<Type>, Error> {
+    let mut types = vec![];
+    loop {
+        if let Delim(')') = scan.peek()?.token {
+            expect!(scan, Delim(')'))?;
+            break;
+        }
+        types.push(parse_type(scan)?);
+        if let Delim(',') = scan.peek()?.token {
+            scan.next()?;
+        }
+    }
+    Ok(types)
+}
+
 // Minimum precedence of the next operator
 fn parse_expr(scan: &mut Scanner, _min: u8) -> Result<Expression<()>, Error> {
+    let mut expr = parse_operand(scan)?.expr;
+
+    // Operator Parsing (with Precedence Climbing), which stops at the '=>' of a match arm
+    while let Op(x) = scan.peek()?.token && x[..] != ['=', '>'] {
+        // A compound assignment like `x += 1` binds as loosely as `=`
+        if let Some(op) = Operator::compound(&x) {
+            scan.next()?;
+            let rhs = parse_expr(scan, Operator::Assign.prec())?;
+            expr = Expr::CompoundAssign {
+                lhs: Box::new(expr.into()),
+                op,
+                rhs: Box::new(rhs),
+            };
+            continue;
+        }
+        let op = Operator::from(&x)?;
+        scan.next()?;
+        op.expect_binary()?;
+        let rhs = parse_expr(scan, op.prec() + op.assoc())?;
+        expr = Expr::Binary {
+            lhs: Box::new(expr.into()),
+            op,
+            rhs: Box::new(rhs),
+        };
+    }
+    Ok(expr.into())
+}
+
+// Parses a single operand of a binary operator, so unary operators bind tighter than binary ones
+fn parse_operand(scan: &mut Scanner) -> Result<Expression<()>, Error> {
     let tok = scan.next()?;
 
     let mut expr: Expr<()> = match tok.token {
+        Op(ref x) if x[0] == '|' => parse_closure(tok, false, scan)?,
+        Keyword(Move) => parse_closure(scan.next()?, true, scan)?,
         Op(x) => {
-            let op = Operator::from(&x)?;
+            let mut op = Operator::from_unary(&x)?;
+            if op == Operator::Ref && scan.peek()?.token == Keyword(Mut) {
+                scan.next()?; // mut
+                op = Operator::RefMut;
+            }
             op.expect_unary()?;
-            let rhs = Box::new(parse_expr(scan, _min)?);
+            let rhs = Box::new(parse_operand(scan)?);
             Expr::Unary { op, rhs }
         }
-        Number(x) => Expr::Value(x.into()),
+        Number(x, suffix) => Expr::Value(parse_number(x, suffix, false)?),
+        Float(x, suffix) => Expr::Value(parse_float(x, suffix)?),
         Literal(x) => Expr::Value(x.into()),
-        Name(x) => Expr::Reference(x),
+        Name(x) => parse_named(parse_path(scan, x)?, scan)?,
         Keyword(True) => Expr::Value(true.into()),
         Keyword(False) => Expr::Value(false.into()),
         Keyword(If) => {
@@ -137,18 +266,43 @@ fn parse_expr(scan: &mut Scanner, _min: u8) -> Result<Expression<()>, Error> {
             Expr::If { cond, then, else_ }
         }
         Keyword(Else) => Err(Error::UnexpectedToken("".into(), tok))?,
-        Keyword(While) => {
-            let cond = Box::new(parse_expr(scan, 0)?);
+        Keyword(While | For | Loop) => parse_loop(tok, None, scan)?,
+        Label(label) => {
+            expect!(scan, Delim(':'))?;
+            let tok = scan.next()?;
+            parse_loop(tok, Some(label), scan)?
+        }
+        Keyword(Break) => {
+            let label = parse_label(scan)?;
+            let value = parse_opt_value(scan)?;
+            Expr::Break { label, value }
+        }
+        Keyword(Continue) => Expr::Continue(parse_label(scan)?),
+        Keyword(Return) => Expr::Return(parse_opt_value(scan)?),
+        Keyword(Yield) => Expr::Yield(Box::new(parse_expr(scan, 0)?)),
+        Keyword(Match) => {
+            let expr = Box::new(parse_expr(scan, 0)?);
             expect!(scan, Delim('{'))?; // {
-            let body = parse_seq(scan)?;
+            let mut arms = vec![];
+            while scan.peek()?.token != Delim('}') {
+                arms.push(parse_arm(scan)?);
+                if let Delim(',') = scan.peek()?.token {
+                    scan.next()?; // ,
+                }
+            }
             expect!(scan, Delim('}'))?; // }
-            Expr::While { cond, body }
+            Expr::Match { expr, arms }
         }
         Keyword(ref key) => {
             let name = scan.next()?.name()?;
             let ty = parse_opt_type(scan)?;
-            expect!(scan, Op(x) if let ['='] == x[..])?;
-            let value = Box::new(parse_expr(scan, 0)?);
+            // The value can be left for a later assignment
+            let value = if matches!(scan.peek()?.token, Op(ref x) if x[..] == ['=']) {
+                scan.next()?; // =
+                Some(Box::new(parse_expr(scan, 0)?))
+            } else {
+                None
+            };
             let mutable = match key {
                 Let => false,
                 Var => true,
@@ -243,32 +397,365 @@ fn parse_expr(scan: &mut Scanner, _min: u8) -> Result<Expression<()>, Error> {
                     rhs: Box::new(index),
                 };
             }
-            // Tuple Access
+            // Propagating a `None` or `Err`
+            Delim('?') => {
+                scan.next()?; // ?
+                expr = Expr::Try(Box::new(expr.into()));
+            }
+            // Tuple and Field Access
             Delim('.') => {
                 scan.next()?; // .
-                let index = scan.next()?.number()?;
-                expr = Expr::Unary {
-                    rhs: Box::new(expr.into()),
-                    op: Operator::TupleIndex(index as usize),
+                let tok = scan.next()?;
+                expr = match tok.token {
+                    Name(method) if scan.peek()?.token == Delim('(') => Expr::Method {
+                        receiver: Box::new(expr.into()),
+                        method,
+                        args: parse_args(scan)?.unwrap_or_default(),
+                        trait_: None,
+                    },
+                    Name(field) => Expr::Field {
+                        expr: Box::new(expr.into()),
+                        field,
+                    },
+                    _ => Expr::Unary {
+                        rhs: Box::new(expr.into()),
+                        op: Operator::TupleIndex(tok.number()? as usize),
+                    },
                 };
             }
             _ => break,
         }
     }
+    Ok(expr.into())
+}
 
-    // Operator Parsing (with Precedence Climbing)
-    while let Op(x) = scan.peek()?.token {
-        let op = Operator::from(&x)?;
-        scan.next()?;
-        op.expect_binary()?;
-        let rhs = parse_expr(scan, op.prec() + op.assoc())?;
-        expr = Expr::Binary {
-            lhs: Box::new(expr.into()),
-            op,
-            rhs: Box::new(rhs),
-        };
+// A name followed by '{' starts a struct literal only if the braces are empty or open with
+// 'field:', so that the body of an `if x { ... }` isn't mistaken for one
+fn is_struct_literal(scan: &mut Scanner) -> Result<bool, Error> {
+    if scan.peek()?.token != Delim('{') {
+        return Ok(false);
+    }
+    let ahead = &mut scan.clone();
+    ahead.next()?; // {
+    Ok(match ahead.next()?.token {
+        Delim('}') => true,
+        // A path like `Enum::Variant` isn't a field
+        Name(_) => ahead.next()?.token == Delim(':') && ahead.next()?.token != Delim(':'),
+        _ => false,
+    })
+}
+
+fn parse_loop(tok: Token, label: Option<String>, scan: &mut Scanner) -> Result<Expr<()>, Error> {
+    let expr = match tok.token {
+        Keyword(While) => {
+            let cond = Box::new(parse_expr(scan, 0)?);
+            let body = parse_block(scan)?;
+            Expr::While { cond, body, label }
+        }
+        Keyword(For) => {
+            let var = scan.next()?.name()?;
+            expect!(scan, Keyword(In))?;
+            let iter = Box::new(parse_expr(scan, 0)?);
+            let body = parse_block(scan)?;
+            Expr::For {
+                var,
+                iter,
+                body,
+                label,
+            }
+        }
+        Keyword(Loop) => Expr::Loop {
+            body: parse_block(scan)?,
+            label,
+        },
+        _ => Err(Error::UnexpectedToken("Loop".into(), tok))?,
+    };
+    Ok(expr)
+}
+
+fn parse_block(scan: &mut Scanner) -> Result<Seq<()>, Error> {
+    expect!(scan, Delim('{'))?; // {
+    let body = parse_seq(scan)?;
+    expect!(scan, Delim('}'))?; // }
+    Ok(body)
+}
+
+// The label a break or continue refers to, unless it starts a labelled loop instead
+fn parse_label(scan: &mut Scanner) -> Result<Option<String>, Error> {
+    let Label(label) = scan.peek()?.token else {
+        return Ok(None);
+    };
+    let ahead = &mut scan.clone();
+    ahead.next()?; // 'label
+    if ahead.next()?.token == Delim(':') {
+        return Ok(None);
+    }
+    scan.next()?;
+    Ok(Some(label))
+}
+
+// The value of a break or return, if the next token can start one
+fn parse_opt_value(scan: &mut Scanner) -> Result<Option<Box<Expression<()>>>, Error> {
+    if starts_expr(&scan.peek()?.token) {
+        Ok(Some(Box::new(parse_expr(scan, 0)?)))
+    } else {
+        Ok(None)
+    }
+}
+
+// Whether the token can start an expression, so a break or return is followed by its value
+fn starts_expr(tok: &TokenType) -> bool {
+    match tok {
+        Number(..) | Float(..) | Literal(_) | Name(_) | Label(_) | Delim('(' | '[') => true,
+        Keyword(Let | Var | If | True | False | While | For | Loop | Match | Yield) => true,
+        Keyword(Break | Continue | Return | Move) => true,
+        // Closures start with a pipe, which `|=` isn't
+        Op(x) if x[0] == '|' => Operator::compound(x).is_none(),
+        Op(x) => Operator::from_unary(x).is_ok_and(|op| op.expect_unary().is_ok()),
+        _ => false,
+    }
+}
+
+// Whether the name is followed by `::<`, which gives a generic function its type parameters
+fn is_instance(scan: &mut Scanner) -> Result<bool, Error> {
+    let ahead = &mut scan.clone();
+    for _ in 0..2 {
+        if ahead.next()?.token != Delim(':') {
+            return Ok(false);
+        }
+    }
+    Ok(matches!(ahead.next()?.token, Op(x) if x[..] == ['<']))
+}
+
+// Parses a list between angle brackets, like the type parameters of a function
+fn parse_angled<X>(
+    scan: &mut Scanner,
+    item: fn(&mut Scanner) -> Result<X, Error>,
+) -> Result<Vec<X>, Error> {
+    expect!(scan, Op(x) if let ['<'] == x[..])?;
+    let mut items = vec![];
+    loop {
+        if scan.next_angle()? {
+            break;
+        }
+        items.push(item(scan)?);
+        if let Delim(',') = scan.peek()?.token {
+            scan.next()?; // ,
+        } else if scan.next_angle()? {
+            break;
+        } else {
+            Err(Error::UnexpectedToken("'>'".into(), scan.next()?))?
+        }
+    }
+    Ok(items)
+}
+
+// Parses the rest of a path like `geo::Point`, after its first name. A `::` that isn't followed
+// by a name, like the one before the types given to a generic function, is left alone.
+fn parse_path(scan: &mut Scanner, first: String) -> Result<String, Error> {
+    let mut path = first;
+    loop {
+        let ahead = &mut scan.clone();
+        if ahead.next()?.token != Delim(':')
+            || ahead.next()?.token != Delim(':')
+            || !matches!(ahead.next()?.token, Name(_))
+        {
+            return Ok(path);
+        }
+        scan.next()?; // :
+        scan.next()?; // :
+        path = format!("{}::{}", path, scan.next()?.name()?);
+    }
+}
+
+// Parses what a path starts: a struct literal, a generic function given its types, a variant or a
+// function from an impl or module, or just a variable
+fn parse_named(path: String, scan: &mut Scanner) -> Result<Expr<()>, Error> {
+    if is_struct_literal(scan)? {
+        expect!(scan, Delim('{'))?;
+        let mut fields = vec![];
+        loop {
+            if let Delim('}') = scan.peek()?.token {
+                scan.next()?; // }
+                break;
+            }
+            let field = scan.next()?.name()?;
+            expect!(scan, Delim(':'))?;
+            fields.push((field, parse_expr(scan, 0)?));
+            if let Delim(',') = scan.peek()?.token {
+                scan.next()?; // ,
+            } else {
+                expect!(scan, Delim('}'))?;
+                break;
+            }
+        }
+        Ok(Expr::Struct { name: path, fields })
+    } else if is_instance(scan)? {
+        expect!(scan, Delim(':'))?;
+        expect!(scan, Delim(':'))?;
+        let types = parse_angled(scan, parse_type)?;
+        Ok(Expr::Instance { func: path, types })
+    } else if let Some((enum_, variant)) = path.rsplit_once("::") {
+        Ok(Expr::Variant {
+            enum_: enum_.into(),
+            variant: variant.into(),
+            args: parse_args(scan)?,
+        })
+    } else if let Some(enum_) = builtin_enum(&path) {
+        Ok(Expr::Variant {
+            enum_: enum_.into(),
+            variant: path,
+            args: parse_args(scan)?,
+        })
+    } else {
+        Ok(Expr::Reference(path))
     }
-    Ok(expr.into())
+}
+
+// Parses the parenthesized arguments of a variant or method, if it has parentheses
+fn parse_args(scan: &mut Scanner) -> Result<Option<Vec<Expression<()>>>, Error> {
+    let mut args = vec![];
+    if scan.peek()?.token != Delim('(') {
+        return Ok(None);
+    }
+    scan.next()?; // (
+    loop {
+        if let Delim(')') = scan.peek()?.token {
+            scan.next()?; // )
+            break;
+        }
+        args.push(parse_expr(scan, 0)?);
+        if let Delim(',') = scan.peek()?.token {
+            scan.next()?; // ,
+        } else {
+            expect!(scan, Delim(')'))?;
+            break;
+        }
+    }
+    Ok(Some(args))
+}
+
+fn parse_pattern(scan: &mut Scanner) -> Result<Pattern, Error> {
+    let tok = scan.next()?;
+    let pattern = match tok.token {
+        Name(x) if x == "_" => Pattern::Wildcard,
+        Name(x) if scan.peek()?.token == Delim(':') => {
+            let path = parse_path(scan, x)?;
+            let Some((enum_, variant)) = path.rsplit_once("::") else {
+                Err(Error::UnexpectedToken("Pattern".into(), scan.next()?))?
+            };
+            Pattern::Variant {
+                enum_: enum_.to_owned(),
+                variant: variant.to_owned(),
+                args: parse_pattern_args(scan)?,
+            }
+        }
+        Name(x) if let Some(enum_) = builtin_enum(&x) => Pattern::Variant {
+            enum_: enum_.to_owned(),
+            variant: x,
+            args: parse_pattern_args(scan)?,
+        },
+        Name(x) => Pattern::Binding(x),
+        Number(x, suffix) => Pattern::Value(parse_number(x, suffix, false)?),
+        // Negative numbers are only literals in patterns, since there's nothing to negate
+        Op(ref x) if x[..] == ['-'] => {
+            let tok = scan.next()?;
+            let Number(x, suffix) = tok.token else {
+                Err(Error::UnexpectedToken("Number(_)".into(), tok))?
+            };
+            Pattern::Value(parse_number(x, suffix, true)?)
+        }
+        Literal(x) => Pattern::Value(x.into()),
+        Keyword(True) => Pattern::Value(true.into()),
+        Keyword(False) => Pattern::Value(false.into()),
+        _ => Err(Error::UnexpectedToken("Pattern".into(), tok))?,
+    };
+    Ok(pattern)
+}
+
+// Parses the parenthesized patterns for the values a variant holds, if there are any
+fn parse_pattern_args(scan: &mut Scanner) -> Result<Vec<Pattern>, Error> {
+    let mut args = vec![];
+    if scan.peek()?.token != Delim('(') {
+        return Ok(args);
+    }
+    scan.next()?; // (
+    loop {
+        if let Delim(')') = scan.peek()?.token {
+            scan.next()?; // )
+            break;
+        }
+        args.push(parse_pattern(scan)?);
+        if let Delim(',') = scan.peek()?.token {
+            scan.next()?; // ,
+        } else {
+            expect!(scan, Delim(')'))?;
+            break;
+        }
+    }
+    Ok(args)
+}
+
+fn parse_arm(scan: &mut Scanner) -> Result<Arm<()>, Error> {
+    let pattern = parse_pattern(scan)?;
+    let guard = if scan.peek()?.token == Keyword(If) {
+        scan.next()?; // if
+        Some(parse_expr(scan, 0)?)
+    } else {
+        None
+    };
+    expect!(scan, Op(x) if let ['=', '>'] == x[..])?;
+    let body = parse_body(scan)?;
+    Ok(Arm {
+        pattern,
+        guard,
+        body,
+    })
+}
+
+// The body of an arm or closure is either a block, or a single expression
+fn parse_body(scan: &mut Scanner) -> Result<Seq<()>, Error> {
+    if let Delim('{') = scan.peek()?.token {
+        parse_block(scan)
+    } else {
+        Ok(Seq(vec![parse_expr(scan, 0)?]))
+    }
+}
+
+// Parses a closure from its opening pipe, which is a `||` when it has no parameters
+fn parse_closure(tok: Token, moves: bool, scan: &mut Scanner) -> Result<Expr<()>, Error> {
+    let mut params = vec![];
+    match tok.token {
+        Op(ref x) if x[..] == ['|', '|'] => (),
+        Op(ref x) if x[..] == ['|'] => loop {
+            if let Op(x) = scan.peek()?.token && x[..] == ['|'] {
+                scan.next()?; // |
+                break;
+            }
+            let name = scan.next()?.name()?;
+            params.push((name, parse_opt_type(scan)?));
+            if let Delim(',') = scan.peek()?.token {
+                scan.next()?; // ,
+            } else {
+                expect!(scan, Op(x) if let ['|'] == x[..])?;
+                break;
+            }
+        },
+        _ => Err(Error::UnexpectedToken("Op(['|'])".into(), tok))?,
+    }
+    let ret = if let Op(x) = scan.peek()?.token && let ['-', '>'] = x[..] {
+        scan.next()?;
+        Some(parse_type(scan)?)
+    } else {
+        None
+    };
+    Ok(Expr::Closure {
+        params,
+        ret,
+        body: parse_body(scan)?,
+        moves,
+        captures: vec![],
+    })
 }
 
 // As long as we see a semicolon, there's another expression
@@ -285,20 +772,16 @@ pub fn parse_seq(scan: &mut Scanner) -> Result<Seq<()>, Error> {
     Ok(Seq(exprs))
 }
 
-pub fn parse_func(scan: &mut Scanner) -> Result<Function<()>, Error> {
-    expect!(scan, Keyword(Func))?;
-    let name = scan.next()?.name()?;
+// Parses a parenthesized list of named parameters
+fn parse_params(scan: &mut Scanner) -> Result<Vec<(String, Type)>, Error> {
     expect!(scan, Delim('('))?;
-    let mut args = vec![];
+    let mut params = vec![];
     loop {
         if let Delim(')') = scan.peek()?.token {
             scan.next()?;
             break;
         }
-        let name = scan.next()?.name()?;
-        expect!(scan, Delim(':'))?;
-        let ty = parse_type(scan)?;
-        args.push((name, ty));
+        params.push(parse_param(scan)?);
         if let Delim(',') = scan.peek()?.token {
             scan.next()?;
         } else {
@@ -306,28 +789,356 @@ pub fn parse_func(scan: &mut Scanner) -> Result<Function<()>, Error> {
             break;
         }
     }
-    let ret = if let Op(x) = scan.peek()?.token && let ['-', '>'] = x[..] {
+    Ok(params)
+}
+
+// A method's receiver can be written as just `self`, `&self` or `&mut self`
+fn parse_param(scan: &mut Scanner) -> Result<(String, Type), Error> {
+    let this = || Type::Named("Self".into());
+    let tok = scan.next()?;
+    let param = match tok.token {
+        Op(ref x) if x[..] == ['&'] => {
+            let mutable = scan.peek()?.token == Keyword(Mut);
+            if mutable {
+                scan.next()?; // mut
+            }
+            expect!(scan, Name(x) if let x == "self")?;
+            ("self".into(), Type::Reference(Box::new(this()), mutable))
+        }
+        Name(x) if x == "self" && scan.peek()?.token != Delim(':') => (x, this()),
+        Name(x) => {
+            expect!(scan, Delim(':'))?;
+            (x, parse_type(scan)?)
+        }
+        _ => Err(Error::UnexpectedToken("Parameter".into(), tok))?,
+    };
+    Ok(param)
+}
+
+// A type parameter, along with the traits it's bound by, like `T: Show + Eq`
+fn parse_type_param(scan: &mut Scanner) -> Result<TypeParam, Error> {
+    let name = scan.next()?.name()?;
+    let mut bounds = vec![];
+    if let Delim(':') = scan.peek()?.token {
+        scan.next()?; // :
+        loop {
+            let bound = scan.next()?.name()?;
+            bounds.push(parse_path(scan, bound)?);
+            if let Op(x) = scan.peek()?.token && x[..] == ['+'] {
+                scan.next()?; // +
+            } else {
+                break;
+            }
+        }
+    }
+    Ok(TypeParam { name, bounds })
+}
+
+// The return type after the parameters, which is the unit type if it's left out
+fn parse_ret(scan: &mut Scanner) -> Result<Type, Error> {
+    if let Op(x) = scan.peek()?.token && let ['-', '>'] = x[..] {
         scan.next()?;
-        parse_type(scan)?
+        parse_type(scan)
     } else {
-        Type::unit()
+        Ok(Type::unit())
+    }
+}
+
+pub fn parse_func(scan: &mut Scanner) -> Result<Function<()>, Error> {
+    let tok = scan.next()?;
+    let coro = match tok.token {
+        Keyword(Func) => false,
+        Keyword(Coro) => true,
+        _ => Err(Error::UnexpectedToken(
+            "Keyword(Func) | Keyword(Coro)".into(),
+            tok,
+        ))?,
+    };
+    let name = scan.next()?.name()?;
+    let generics = match scan.peek()?.token {
+        Op(x) if x[..] == ['<'] => parse_angled(scan, parse_type_param)?,
+        _ => vec![],
+    };
+    let args = parse_params(scan)?;
+    // Coroutines take a second set of parameters, which they receive when first resumed
+    let resume = if coro {
+        Some(parse_params(scan)?)
+    } else {
+        None
+    };
+    let ret = parse_ret(scan)?;
+    let kind = if let Some(resume) = resume {
+        FuncKind::Coro(resume)
+    } else if let Keyword(Yield) = scan.peek()?.token {
+        scan.next()?; // yield
+        let tok = scan.peek()?;
+        match parse_type(scan)? {
+            frame @ Type::Frame { .. } => FuncKind::Yields(frame),
+            _ => Err(Error::InvalidType(tok))?,
+        }
+    } else {
+        FuncKind::Func
     };
     expect!(scan, Delim('{'))?;
     let body = parse_seq(scan)?;
     expect!(scan, Delim('}'))?;
     Ok(Function {
         name,
+        public: false,
+        generics,
         args,
         ret,
         body,
+        kind,
+    })
+}
+
+pub fn parse_struct(scan: &mut Scanner) -> Result<StructDef, Error> {
+    expect!(scan, Keyword(Struct))?;
+    let name = scan.next()?.name()?;
+    // A tuple struct, whose fields are named by their positions
+    if let Delim('(') = scan.peek()?.token {
+        scan.next()?; // (
+        let fields = parse_tuple_type(scan)?
+            .into_iter()
+            .enumerate()
+            .map(|(i, ty)| (i.to_string(), ty))
+            .collect();
+        expect!(scan, Delim(';'))?;
+        return Ok(StructDef {
+            name,
+            public: false,
+            fields,
+        });
+    }
+    expect!(scan, Delim('{'))?;
+    let mut fields = vec![];
+    loop {
+        if let Delim('}') = scan.peek()?.token {
+            scan.next()?;
+            break;
+        }
+        let field = scan.next()?.name()?;
+        expect!(scan, Delim(':'))?;
+        fields.push((field, parse_type(scan)?));
+        if let Delim(',') = scan.peek()?.token {
+            scan.next()?;
+        } else {
+            expect!(scan, Delim('}'))?;
+            break;
+        }
+    }
+    Ok(StructDef {
+        name,
+        public: false,
+        fields,
+    })
+}
+
+pub fn parse_enum(scan: &mut Scanner) -> Result<EnumDef, Error> {
+    expect!(scan, Keyword(Enum))?;
+    let name = scan.next()?.name()?;
+    expect!(scan, Delim('{'))?;
+    let mut variants = vec![];
+    loop {
+        if let Delim('}') = scan.peek()?.token {
+            scan.next()?;
+            break;
+        }
+        let variant = scan.next()?.name()?;
+        let payload = if let Delim('(') = scan.peek()?.token {
+            scan.next()?;
+            parse_tuple_type(scan)?
+        } else {
+            vec![]
+        };
+        variants.push((variant, payload));
+        if let Delim(',') = scan.peek()?.token {
+            scan.next()?;
+        } else {
+            expect!(scan, Delim('}'))?;
+            break;
+        }
+    }
+    Ok(EnumDef {
+        name,
+        public: false,
+        variants,
+    })
+}
+
+pub fn parse_trait(scan: &mut Scanner) -> Result<TraitDef, Error> {
+    expect!(scan, Keyword(Trait))?;
+    let name = scan.next()?.name()?;
+    expect!(scan, Delim('{'))?;
+    let mut methods = vec![];
+    // Each method is just a signature, ending with a semicolon
+    while scan.peek()?.token != Delim('}') {
+        expect!(scan, Keyword(Func))?;
+        let name = scan.next()?.name()?;
+        let args = parse_params(scan)?;
+        let ret = parse_ret(scan)?;
+        expect!(scan, Delim(';'))?;
+        methods.push(Signature { name, args, ret });
+    }
+    scan.next()?; // }
+    Ok(TraitDef {
+        name,
+        public: false,
+        methods,
+    })
+}
+
+pub fn parse_alias(scan: &mut Scanner) -> Result<Alias, Error> {
+    expect!(scan, Keyword(Type))?;
+    let name = scan.next()?.name()?;
+    expect!(scan, Op(x) if let ['='] == x[..])?;
+    let ty = parse_type(scan)?;
+    expect!(scan, Delim(';'))?;
+    Ok(Alias {
+        name,
+        public: false,
+        ty,
+    })
+}
+
+// A constant or static, which always has its type written out
+pub fn parse_global(scan: &mut Scanner) -> Result<Global<()>, Error> {
+    expect!(scan, Keyword(Const | Static))?;
+    let name = scan.next()?.name()?;
+    expect!(scan, Delim(':'))?;
+    let ty = parse_type(scan)?;
+    expect!(scan, Op(x) if let ['='] == x[..])?;
+    let value = parse_expr(scan, 0)?;
+    expect!(scan, Delim(';'))?;
+    Ok(Global {
+        name,
+        public: false,
+        ty,
+        value,
+    })
+}
+
+pub fn parse_impl(scan: &mut Scanner) -> Result<Impl<()>, Error> {
+    expect!(scan, Keyword(Impl))?;
+    let tok = scan.peek()?;
+    let mut ty = parse_type(scan)?;
+    // The type we've parsed is the trait, if it's followed by the type it's implemented for
+    let trait_ = if let Keyword(For) = scan.peek()?.token {
+        scan.next()?; // for
+        let Type::Named(trait_) = std::mem::replace(&mut ty, parse_type(scan)?) else {
+            Err(Error::InvalidType(tok))?
+        };
+        Some(trait_)
+    } else {
+        None
+    };
+    expect!(scan, Delim('{'))?;
+    let mut methods = vec![];
+    while scan.peek()?.token != Delim('}') {
+        let public = parse_pub(scan)?;
+        methods.push(Function {
+            public,
+            ..parse_func(scan)?
+        });
+    }
+    scan.next()?; // }
+    Ok(Impl {
+        trait_,
+        ty,
+        methods,
     })
 }
 
 pub fn parse(src: &str) -> Result<Program<()>, Error> {
     let scan = &mut Scanner::new(src);
-    let mut prgm = vec![];
-    while !matches!(scan.peek()?.token, Eof) {
-        prgm.push(parse_func(scan)?);
+    let items = parse_items(scan)?;
+    expect!(scan, Eof)?;
+    Ok(Program(items))
+}
+
+// Parses items up to the end of the file, or the '}' that closes an inline module
+fn parse_items(scan: &mut Scanner) -> Result<Vec<Item<()>>, Error> {
+    let mut items = vec![];
+    while !matches!(scan.peek()?.token, Eof | Delim('}')) {
+        let public = parse_pub(scan)?;
+        let item = match scan.peek()?.token {
+            Keyword(Struct) => Item::Struct(StructDef {
+                public,
+                ..parse_struct(scan)?
+            }),
+            Keyword(Enum) => Item::Enum(EnumDef {
+                public,
+                ..parse_enum(scan)?
+            }),
+            Keyword(Trait) => Item::Trait(TraitDef {
+                public,
+                ..parse_trait(scan)?
+            }),
+            Keyword(Type) => Item::Alias(Alias {
+                public,
+                ..parse_alias(scan)?
+            }),
+            Keyword(Mod) => Item::Mod(Module {
+                public,
+                ..parse_mod(scan)?
+            }),
+            Keyword(Const) => Item::Const(Global {
+                public,
+                ..parse_global(scan)?
+            }),
+            Keyword(Static) => Item::Static(Global {
+                public,
+                ..parse_global(scan)?
+            }),
+            // Impls and imports can't be made public
+            Keyword(Impl | Use) if public => {
+                Err(Error::UnexpectedToken("Item".into(), scan.next()?))?
+            }
+            Keyword(Impl) => Item::Impl(parse_impl(scan)?),
+            Keyword(Use) => Item::Use(parse_use(scan)?),
+            _ => Item::Func(Function {
+                public,
+                ..parse_func(scan)?
+            }),
+        };
+        items.push(item);
+    }
+    Ok(items)
+}
+
+fn parse_pub(scan: &mut Scanner) -> Result<bool, Error> {
+    let public = scan.peek()?.token == Keyword(Pub);
+    if public {
+        scan.next()?; // pub
     }
-    Ok(Program(prgm))
+    Ok(public)
+}
+
+// A module is either written out in braces, or left to be loaded from its own file
+pub fn parse_mod(scan: &mut Scanner) -> Result<Module<()>, Error> {
+    expect!(scan, Keyword(Mod))?;
+    let name = scan.next()?.name()?;
+    let items = if scan.peek()?.token == Delim(';') {
+        scan.next()?; // ;
+        None
+    } else {
+        expect!(scan, Delim('{'))?;
+        let items = parse_items(scan)?;
+        expect!(scan, Delim('}'))?;
+        Some(items)
+    };
+    Ok(Module {
+        name,
+        public: false,
+        items,
+    })
+}
+
+pub fn parse_use(scan: &mut Scanner) -> Result<String, Error> {
+    expect!(scan, Keyword(Use))?;
+    let first = scan.next()?.name()?;
+    let path = parse_path(scan, first)?;
+    expect!(scan, Delim(';'))?;
+    Ok(path)
 }
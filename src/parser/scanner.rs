@@ -1,9 +1,10 @@
 use super::{
     constants::{is_comment, is_delim, is_keyword, is_operator},
     error::{Error, Parse},
-    tokens::{Token, TokenType::*},
+    tokens::{Token, TokenType, TokenType::*},
 };
 
+#[derive(Clone)]
 pub struct Scanner<'a> {
     src: &'a str,
     pos: usize,
@@ -34,14 +35,32 @@ impl<'a> Scanner<'a> {
         }
     }
 
+    // Consumes the `>` that closes a list of types. It can be the start of an operator, like the
+    // `>>` closing two lists at once, and the rest of it is left for the next token.
+    pub fn next_angle(&mut self) -> Parse<bool> {
+        let tok = self.peek()?;
+        let Op(op) = tok.token else {
+            return Ok(false);
+        };
+        let Some((&'>', rest)) = op.split_first() else {
+            return Ok(false);
+        };
+        self.next = if rest.is_empty() {
+            None
+        } else {
+            Some(Op(rest.to_vec()).with_pos(tok.pos + 1))
+        };
+        Ok(true)
+    }
+
     fn next_tok(&mut self) -> Parse<Token> {
         // Consume whitespace
         self.consume_whitespace();
         // Save current position
         let pos = self.pos;
         match self.peek_char() {
-            Some(c) if c.is_numeric() => Ok(Number(self.next_num()?)),
-            Some(c) if c.is_alphabetic() => {
+            Some(c) if c.is_numeric() => self.next_number(),
+            Some(c) if c.is_alphabetic() || c == '_' => {
                 let word = self.next_word()?;
                 if is_keyword(&word) {
                     Ok(Keyword(word.into()))
@@ -50,7 +69,17 @@ impl<'a> Scanner<'a> {
                 }
             }
             Some(c) if is_operator(c) => Ok(Op(self.next_op()?)),
-            Some(c) if c == '\'' => {
+            // Two dots make a range, while a single one is a delimiter for field access
+            Some('.') if self.src[self.pos..].starts_with("..") => {
+                self.pos += 2;
+                Ok(Op(vec!['.', '.']))
+            }
+            // A quote followed by a name is a label, unless it's closed right after one character
+            Some('\'') if self.is_label() => {
+                self.next_char();
+                Ok(Label(self.next_word()?))
+            }
+            Some('\'') => {
                 self.next_char();
                 let mut c = self.next_char().ok_or(Error::UnterminatedChar(pos))?;
                 if c == '\\' {
@@ -62,7 +91,7 @@ impl<'a> Scanner<'a> {
                     .ok_or(Error::UnterminatedChar(pos))?;
                 Ok(Literal(c.into()))
             }
-            Some(c) if c == '"' => {
+            Some('"') => {
                 self.next_char();
                 let mut string = String::new();
                 while let Some(mut c) = self.peek_char() && c != '"' {
@@ -96,12 +125,28 @@ impl<'a> Scanner<'a> {
     fn next_op(&mut self) -> Parse<Vec<char>> {
         let mut op = vec![];
         while let Some(c) = self.peek_char() && is_operator(c) {
+            // Pipes only join each other and the `=` of `|=`, so a closure's parameters can be
+            // followed by an operator
+            if let Some(&last) = op.last() && (last == '|') != (c == '|') && c != '=' {
+                break;
+            }
+            // An angle bracket can open a list of types, like `::<&T>`, so it only joins the `=` of
+            // `<=` and the `<` of `<<`
+            if op.last() == Some(&'<') && c != '=' && c != '<' {
+                break;
+            }
             op.push(c);
             self.next_char();
         }
         Ok(op)
     }
 
+    fn is_label(&self) -> bool {
+        let mut chars = self.src[self.pos..].chars().skip(1);
+        matches!(chars.next(), Some(c) if c.is_alphabetic() || c == '_')
+            && chars.next() != Some('\'')
+    }
+
     fn next_word(&mut self) -> Parse<String> {
         let mut word = String::new();
         while let Some(c) = self.peek_char() && (c.is_alphanumeric() || c == '_') {
@@ -111,13 +156,42 @@ impl<'a> Scanner<'a> {
         Ok(word)
     }
 
-    fn next_num(&mut self) -> Result<u64, Error> {
-        let mut num = String::new();
-        while let Some(c) = self.peek_char() && c.is_numeric() {
-            num.push(c);
+    // A number is an integer unless it has a fraction or an exponent, and it can end with a
+    // suffix for its type. Right after a dot it's always an integer, so `t.0.1` indexes twice.
+    fn next_number(&mut self) -> Parse<TokenType> {
+        let start = self.pos;
+        let after_dot = self.src[..start].ends_with('.') && !self.src[..start].ends_with("..");
+        self.next_digits();
+        let mut float = false;
+        if !after_dot && self.peek_char() == Some('.') && self.peek_digit(1) {
+            self.next_char(); // .
+            self.next_digits();
+            float = true;
+        }
+        if matches!(self.peek_char(), Some('e' | 'E')) {
+            let sign = usize::from(matches!(self.peek_at(1), Some('+' | '-')));
+            if self.peek_digit(1 + sign) {
+                self.pos += 1 + sign;
+                self.next_digits();
+                float = true;
+            }
+        }
+        let num = self.src[start..self.pos].to_owned();
+        let suffix = match self.peek_char() {
+            Some(c) if c.is_alphabetic() => Some(self.next_word()?),
+            _ => None,
+        };
+        if float {
+            Ok(Float(num.parse()?, suffix))
+        } else {
+            Ok(Number(num.parse()?, suffix))
+        }
+    }
+
+    fn next_digits(&mut self) {
+        while self.peek_digit(0) {
             self.next_char();
         }
-        Ok(num.parse()?)
     }
 
     fn next_char(&mut self) -> Option<char> {
@@ -143,6 +217,15 @@ impl<'a> Scanner<'a> {
         })
     }
 
+    // The character n places after the next one
+    fn peek_at(&self, n: usize) -> Option<char> {
+        self.src[self.pos..].chars().nth(n)
+    }
+
+    fn peek_digit(&self, n: usize) -> bool {
+        self.peek_at(n).is_some_and(|c| c.is_ascii_digit())
+    }
+
     fn peek_char(&self) -> Option<char> {
         if self.pos >= self.src.len() {
             None
@@ -178,10 +261,52 @@ mod tests {
         assert_eq!(scan.next()?.token, Keyword(Let));
         assert_eq!(scan.next()?.token, Name("x".into()));
         assert_eq!(scan.next()?.token, Op(vec!['=']));
-        assert_eq!(scan.next()?.token, Number(5));
+        assert_eq!(scan.next()?.token, Number(5, None));
         assert_eq!(scan.next()?.token, Delim(';'));
         assert_eq!(scan.next()?.token, Eof);
         assert_eq!(scan.next()?.token, Eof);
+        let mut scan = Scanner::new("5u8..10");
+        assert_eq!(scan.next()?.token, Number(5, Some("u8".into())));
+        assert_eq!(scan.next()?.token, Op(vec!['.', '.']));
+        assert_eq!(scan.next()?.token, Number(10, None));
+        // A fraction or an exponent makes a float, but not when it would index a tuple
+        let mut scan = Scanner::new("1.5 2e10 3.0e-2f32 t.0.1 4.x");
+        assert_eq!(scan.next()?.token, Float(1.5, None));
+        assert_eq!(scan.next()?.token, Float(2e10, None));
+        assert_eq!(scan.next()?.token, Float(3.0e-2, Some("f32".into())));
+        assert_eq!(scan.next()?.token, Name("t".into()));
+        assert_eq!(scan.next()?.token, Delim('.'));
+        assert_eq!(scan.next()?.token, Number(0, None));
+        assert_eq!(scan.next()?.token, Delim('.'));
+        assert_eq!(scan.next()?.token, Number(1, None));
+        assert_eq!(scan.next()?.token, Number(4, None));
+        assert_eq!(scan.next()?.token, Delim('.'));
+        assert_eq!(scan.next()?.token, Name("x".into()));
+        // Shifts and compound assignments join, but a closure's pipe doesn't
+        let mut scan = Scanner::new("x <<= 1 >> 2; y |= |z| -z");
+        assert_eq!(scan.next()?.token, Name("x".into()));
+        assert_eq!(scan.next()?.token, Op(vec!['<', '<', '=']));
+        assert_eq!(scan.next()?.token, Number(1, None));
+        assert_eq!(scan.next()?.token, Op(vec!['>', '>']));
+        assert_eq!(scan.next()?.token, Number(2, None));
+        assert_eq!(scan.next()?.token, Delim(';'));
+        assert_eq!(scan.next()?.token, Name("y".into()));
+        assert_eq!(scan.next()?.token, Op(vec!['|', '=']));
+        assert_eq!(scan.next()?.token, Op(vec!['|']));
+        assert_eq!(scan.next()?.token, Name("z".into()));
+        assert_eq!(scan.next()?.token, Op(vec!['|']));
+        assert_eq!(scan.next()?.token, Op(vec!['-']));
+        // A `>>` closes two lists of types, one `>` at a time
+        let mut scan = Scanner::new("Option<Option<i32>> = x");
+        assert_eq!(scan.next()?.token, Name("Option".into()));
+        assert_eq!(scan.next()?.token, Op(vec!['<']));
+        assert_eq!(scan.next()?.token, Name("Option".into()));
+        assert_eq!(scan.next()?.token, Op(vec!['<']));
+        assert_eq!(scan.next()?.token, Name("i32".into()));
+        assert!(scan.next_angle()?);
+        assert!(scan.next_angle()?);
+        assert!(!scan.next_angle()?);
+        assert_eq!(scan.next()?.token, Op(vec!['=']));
         Ok(())
     }
 }
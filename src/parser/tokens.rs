@@ -11,12 +11,17 @@ pub struct Token {
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenType {
-    Number(u64),
+    // A number, along with the suffix right after it, like the `u8` of `5u8`
+    Number(u64, Option<String>),
+    // A number with a fraction or an exponent, like `1.5` or `2e10`
+    Float(f64, Option<String>),
     Op(Vec<char>),
     Name(String),
     Keyword(Keyword),
     Delim(char),
     Literal(Literal),
+    // A loop label, such as 'outer
+    Label(String),
     Eof,
 }
 
@@ -57,6 +62,27 @@ pub enum Keyword {
     False,
     Func,
     While,
+    Coro,
+    Yield,
+    Mut,
+    Struct,
+    Enum,
+    Match,
+    For,
+    In,
+    Loop,
+    Break,
+    Continue,
+    Return,
+    Move,
+    Trait,
+    Impl,
+    Pub,
+    Mod,
+    Use,
+    Const,
+    Static,
+    Type,
 }
 
 impl TokenType {
@@ -76,6 +102,27 @@ impl From<String> for Keyword {
             "false" => Keyword::False,
             "fn" => Keyword::Func,
             "while" => Keyword::While,
+            "coro" => Keyword::Coro,
+            "yield" => Keyword::Yield,
+            "mut" => Keyword::Mut,
+            "struct" => Keyword::Struct,
+            "enum" => Keyword::Enum,
+            "match" => Keyword::Match,
+            "for" => Keyword::For,
+            "in" => Keyword::In,
+            "loop" => Keyword::Loop,
+            "break" => Keyword::Break,
+            "continue" => Keyword::Continue,
+            "return" => Keyword::Return,
+            "move" => Keyword::Move,
+            "trait" => Keyword::Trait,
+            "impl" => Keyword::Impl,
+            "pub" => Keyword::Pub,
+            "mod" => Keyword::Mod,
+            "use" => Keyword::Use,
+            "const" => Keyword::Const,
+            "static" => Keyword::Static,
+            "type" => Keyword::Type,
             _ => panic!("Invalid keyword"),
         }
     }
@@ -99,7 +146,7 @@ impl Token {
     // Number or throws an error:
     pub fn number(&self) -> Result<u64, Error> {
         match &self.token {
-            TokenType::Number(n) => Ok(*n),
+            TokenType::Number(n, _) => Ok(*n),
             _ => Err(Error::UnexpectedToken("Number(_)".into(), self.clone())),
         }
     }
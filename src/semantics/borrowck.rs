@@ -0,0 +1,1019 @@
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use crate::ast::tree::*;
+use anyhow::{Context, Error as AnyError};
+
+use super::{error::Error, types::Type};
+
+// Variables are numbered in the order they're declared, so leaving a scope drops the newest ones
+type VarId = usize;
+
+// The types each struct or enum holds
+type Named = HashMap<String, Vec<Type>>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Borrow {
+    Shared,
+    Mut,
+}
+
+// A borrow of a variable, held by the value that was created from it
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Loan {
+    var: VarId,
+    kind: Borrow,
+}
+
+#[derive(Clone, Debug)]
+struct Var {
+    name: String,
+    depth: usize,
+    moved: bool,
+    // Borrows held by the variable's value, which last until the variable is last used
+    loans: Vec<Loan>,
+    init: Init,
+    // Declared without a value and immutable, so it can only be assigned once
+    once: bool,
+}
+
+// Whether a variable has been given a value, which one declared without a value only has once
+// it's assigned on every path
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Init {
+    Yes,
+    No,
+    Maybe,
+}
+
+// Where a place expression leads to
+#[derive(Clone)]
+enum Root {
+    Var(VarId),
+    // Through a reference, which holds these loans
+    Deref(Vec<Loan>),
+    // A static, which lives for the whole program and is never moved out of
+    Static(String),
+    // A temporary value, which holds these loans
+    Temp(Vec<Loan>),
+}
+
+// A loop being checked, along with the loans held by the values it's broken out of with
+#[derive(Clone, Debug)]
+struct LoopLoans {
+    label: Option<String>,
+    // How many scopes were open when the loop started, since the values outlive the ones after
+    depth: usize,
+    loans: Vec<Loan>,
+}
+
+// The names used by an expression of a sequence and the ones after it, which refer to the
+// variables that were visible before it
+#[derive(Clone, Debug)]
+struct Later {
+    names: HashSet<String>,
+    visible: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+struct BorrowEnv {
+    vars: Vec<Var>,
+    // Where the variables of each scope start
+    scopes: Vec<VarId>,
+    named: Rc<Named>,
+    statics: Rc<HashSet<String>>,
+    // The loops around the current expression, innermost last
+    loops: Vec<LoopLoans>,
+    // How many scopes returned values have to outlive, or nothing if they're dropped.
+    // A coroutine's final value is dropped, so only functions and closures can return references.
+    returns: Option<usize>,
+    // What's used after the current expression, for each sequence around it
+    later: Vec<Later>,
+}
+
+// Checks ownership and borrowing, where the borrows a variable holds end once it's no longer used
+pub fn check(prgm: &Program<Type>) -> Result<(), AnyError> {
+    let fields = prgm.structs().map(|def| {
+        let tys = def.fields.iter().map(|(_, ty)| ty.clone()).collect();
+        (def.name.clone(), tys)
+    });
+    let variants = prgm.enums().map(|def| {
+        let tys = def
+            .variants
+            .iter()
+            .flat_map(|(_, tys)| tys.clone())
+            .collect();
+        (def.name.clone(), tys)
+    });
+    let named = Rc::new(fields.chain(variants).collect::<Named>());
+    let statics = Rc::new(prgm.statics().map(|def| def.name.clone()).collect());
+    for func in prgm.funcs().cloned().chain(prgm.methods()) {
+        check_function(&func, &named, &statics).context(format!("In function '{}'", func.name))?;
+    }
+    Ok(())
+}
+
+fn check_function(
+    func: &Function<Type>,
+    named: &Rc<Named>,
+    statics: &Rc<HashSet<String>>,
+) -> Result<(), AnyError> {
+    let mut env = BorrowEnv {
+        named: named.clone(),
+        statics: statics.clone(),
+        returns: (!matches!(func.kind, FuncKind::Coro(_))).then_some(0),
+        ..Default::default()
+    };
+    env.enter();
+    for (name, _) in &func.args {
+        env.declare(name, vec![]);
+    }
+    if let FuncKind::Coro(resume) = &func.kind {
+        for (name, _) in resume {
+            env.declare(name, vec![]);
+        }
+    }
+    let loans = check_seq(&func.body, &mut env)?;
+    if let Some(depth) = env.returns {
+        env.outlives(&loans, depth)?;
+    }
+    Ok(())
+}
+
+// Gives back the loans held by the value of the sequence
+fn check_seq(seq: &Sequence<Type>, env: &mut BorrowEnv) -> Result<Vec<Loan>, AnyError> {
+    env.enter();
+    let mut loans = vec![];
+    for (expr, names) in seq.0.iter().zip(used_later(seq)) {
+        let visible = env.vars.len();
+        env.later.push(Later { names, visible });
+        loans = check_expr(expr, env).context(format!("On expression: {}", expr))?;
+        env.later.pop();
+    }
+    // The value of the block can't borrow from the variables that are dropped with it
+    env.outlives(&loans, env.scopes.len() - 1)?;
+    env.exit();
+    Ok(loans)
+}
+
+// The names used by each expression of the sequence or any after it, leaving out the uses of a
+// variable declared later on, which can't refer to one that's visible yet
+fn used_later(seq: &Sequence<Type>) -> Vec<HashSet<String>> {
+    let mut after = HashSet::new();
+    let mut sets = seq
+        .0
+        .iter()
+        .rev()
+        .map(|expr| {
+            let used = expr.names();
+            let mut names = after.clone();
+            names.extend(used.iter().cloned());
+            if let Expr::Let { name, .. } = &expr.expr {
+                after.remove(name);
+            }
+            after.extend(used);
+            names
+        })
+        .collect::<Vec<_>>();
+    sets.reverse();
+    sets
+}
+
+// Gives back the loans held by the value of the expression
+fn check_expr(expr: &Expression<Type>, env: &mut BorrowEnv) -> Result<Vec<Loan>, AnyError> {
+    let loans = match &expr.expr {
+        Expr::Reference(_)
+        | Expr::Instance { .. }
+        | Expr::Field { .. }
+        | Expr::Unary {
+            op: Operator::TupleIndex(_) | Operator::Mul,
+            ..
+        }
+        | Expr::Binary {
+            op: Operator::ArrayIndex,
+            ..
+        } => check_read(expr, env)?,
+        Expr::Unary {
+            op: Operator::Ref,
+            rhs,
+        } => check_borrow(rhs, Borrow::Shared, env)?,
+        Expr::Unary {
+            op: Operator::RefMut,
+            rhs,
+        } => check_borrow(rhs, Borrow::Mut, env)?,
+        Expr::Unary { rhs, .. } => {
+            check_expr(rhs, env)?;
+            vec![]
+        }
+        Expr::Binary {
+            lhs,
+            op: Operator::Assign,
+            rhs,
+        } => {
+            let loans = check_expr(rhs, env)?;
+            check_assign(lhs, loans, env)?;
+            vec![]
+        }
+        // The place is only found once, and then both read and assigned
+        Expr::CompoundAssign { lhs, rhs, .. } => {
+            check_expr(rhs, env)?;
+            let root = check_place(lhs, env)?;
+            read_place(root.clone(), &lhs.ty, env)?;
+            assign_place(root, matches!(lhs.expr, Expr::Reference(_)), vec![], env)?;
+            vec![]
+        }
+        Expr::Binary { lhs, rhs, .. } => {
+            check_expr(lhs, env)?;
+            check_expr(rhs, env)?;
+            vec![]
+        }
+        Expr::Value(_) => vec![],
+        Expr::Tuple(exprs) | Expr::Array(exprs) => check_values(exprs, env)?,
+        Expr::Variant { args, .. } => check_values(args.as_deref().unwrap_or_default(), env)?,
+        Expr::Match { expr, arms } => {
+            let held = check_expr(expr, env)?;
+            let before = env.clone();
+            let mut loans = vec![];
+            // Matches are exhaustive, so the state after one is the state after any of its arms
+            let mut after: Option<BorrowEnv> = None;
+            for arm in arms {
+                let mut branch = before.clone();
+                branch.enter();
+                // Whatever the matched value borrows, the names bound from it might too
+                for name in arm.pattern.bindings() {
+                    branch.declare(name, held.clone());
+                }
+                if let Some(guard) = &arm.guard {
+                    check_expr(guard, &mut branch)?;
+                }
+                let arm_loans = check_seq(&arm.body, &mut branch)?;
+                branch.outlives(&arm_loans, branch.scopes.len() - 1)?;
+                branch.exit();
+                loans.extend(arm_loans);
+                match &mut after {
+                    Some(after) => after.merge(branch),
+                    None => after = Some(branch),
+                }
+            }
+            if let Some(after) = after {
+                *env = after;
+            }
+            loans
+        }
+        Expr::Struct { fields, .. } => {
+            let mut loans = vec![];
+            for (_, value) in fields {
+                loans.extend(check_expr(value, env)?);
+            }
+            loans
+        }
+        Expr::Let {
+            name,
+            value: Some(value),
+            ..
+        } => {
+            let loans = check_expr(value, env)?;
+            env.declare(name, loans);
+            vec![]
+        }
+        Expr::Let {
+            name,
+            value: None,
+            mutable,
+            ..
+        } => {
+            env.declare(name, vec![]);
+            if let Some(var) = env.vars.last_mut() {
+                var.init = Init::No;
+                var.once = !mutable;
+            }
+            vec![]
+        }
+        Expr::If { cond, then, else_ } => {
+            check_expr(cond, env)?;
+            let mut other = env.clone();
+            let mut loans = check_seq(then, env)?;
+            if let Some(else_) = else_ {
+                loans.extend(check_seq(else_, &mut other)?);
+            }
+            env.merge(other);
+            loans
+        }
+        Expr::Call { func, args } => {
+            // Calling a value doesn't move it, so a frame can be resumed again
+            let mut loans = match check_place(func, env)? {
+                Root::Var(var) => {
+                    env.alive(var)?;
+                    env.vars[var].loans.clone()
+                }
+                Root::Deref(loans) | Root::Temp(loans) => loans,
+                Root::Static(_) => vec![],
+            };
+            loans.extend(check_values(args, env)?);
+            // The result can only borrow from what was passed in, including the arguments a frame holds on to
+            if expr.ty.holds_loans(&env.named) {
+                loans
+            } else {
+                vec![]
+            }
+        }
+        Expr::Method { receiver, args, .. } => {
+            // The receiver has already been borrowed, if that's how the method takes it
+            let mut loans = check_expr(receiver, env)?;
+            loans.extend(check_values(args, env)?);
+            if expr.ty.holds_loans(&env.named) {
+                loans
+            } else {
+                vec![]
+            }
+        }
+        Expr::While { cond, body, label } => {
+            // Check the loop twice, to catch anything that conflicts with the previous iteration
+            let before = env.clone();
+            env.enter_loop(label);
+            for _ in 0..2 {
+                check_expr(cond, env)?;
+                check_seq(body, env)?;
+            }
+            check_expr(cond, env)?;
+            env.loops.pop();
+            env.merge(before);
+            vec![]
+        }
+        Expr::For {
+            var,
+            iter,
+            body,
+            label,
+        } => {
+            let loans = check_expr(iter, env)?;
+            let before = env.clone();
+            env.enter_loop(label);
+            for _ in 0..2 {
+                env.enter();
+                env.declare(var, loans.clone());
+                check_seq(body, env)?;
+                env.exit();
+            }
+            env.loops.pop();
+            env.merge(before);
+            vec![]
+        }
+        Expr::Loop { body, label } => {
+            let before = env.clone();
+            env.enter_loop(label);
+            for _ in 0..2 {
+                check_seq(body, env)?;
+            }
+            let loans = env.loops.pop().map_or(vec![], |scope| scope.loans);
+            env.merge(before);
+            loans
+        }
+        Expr::Break { label, value } => {
+            let loans = match value {
+                Some(value) => check_expr(value, env)?,
+                None => vec![],
+            };
+            // The value can't borrow from the variables that are dropped when leaving the loop
+            let target = env.target(label);
+            env.outlives(&loans, env.loops[target].depth)?;
+            env.loops[target].loans.extend(loans);
+            vec![]
+        }
+        Expr::Continue(_) => vec![],
+        Expr::Return(value) => {
+            if let Some(value) = value {
+                let loans = check_expr(value, env)?;
+                if let Some(depth) = env.returns {
+                    env.outlives(&loans, depth)?;
+                }
+            }
+            vec![]
+        }
+        Expr::Closure {
+            params,
+            body,
+            moves,
+            captures,
+            ..
+        } => {
+            check_closure(params, body, *moves, env)?;
+            // The closure holds on to what it captured
+            let mut loans = vec![];
+            for capture in captures {
+                let var = Expression {
+                    expr: Expr::Reference(capture.name.clone()),
+                    ty: capture.ty.clone(),
+                };
+                loans.extend(match (moves, capture.mutable) {
+                    (true, _) => check_read(&var, env)?,
+                    (false, false) => check_borrow(&var, Borrow::Shared, env)?,
+                    (false, true) => check_borrow(&var, Borrow::Mut, env)?,
+                });
+            }
+            loans
+        }
+        Expr::Yield(value) => {
+            check_expr(value, env)?;
+            vec![]
+        }
+        // The unwrapped value keeps the loans, and so does what's returned early
+        Expr::Try(value) => {
+            let loans = check_expr(value, env)?;
+            if let Some(depth) = env.returns {
+                env.outlives(&loans, depth)?;
+            }
+            loans
+        }
+    };
+    Ok(loans)
+}
+
+// The body runs every time the closure is called, so it's checked twice where it's created,
+// as if it used the captured variables directly
+fn check_closure(
+    params: &[(String, Option<Type>)],
+    body: &Sequence<Type>,
+    moves: bool,
+    env: &mut BorrowEnv,
+) -> Result<(), AnyError> {
+    let depth = env.scopes.len();
+    let moved = env.vars.iter().map(|var| var.moved).collect::<Vec<_>>();
+    // A closure can't give a value to what it captures, since it might be called any number of times
+    let init = env.vars.iter().map(|var| var.init).collect::<Vec<_>>();
+    let returns = env.returns.replace(depth);
+    let loops = std::mem::take(&mut env.loops);
+    for _ in 0..2 {
+        env.enter();
+        for (name, _) in params {
+            env.declare(name, vec![]);
+        }
+        let loans = check_seq(body, env)?;
+        env.outlives(&loans, depth)?;
+        env.exit();
+        // Each call of a move closure gets its own copy of what it captured
+        if moves {
+            for (var, moved) in env.vars.iter_mut().zip(&moved) {
+                var.moved = *moved;
+            }
+        }
+    }
+    for (var, init) in env.vars.iter_mut().zip(init) {
+        var.init = init;
+    }
+    env.returns = returns;
+    env.loops = loops;
+    Ok(())
+}
+
+fn check_values(exprs: &[Expression<Type>], env: &mut BorrowEnv) -> Result<Vec<Loan>, AnyError> {
+    let mut loans = vec![];
+    for expr in exprs {
+        loans.extend(check_expr(expr, env)?);
+    }
+    Ok(loans)
+}
+
+// Finds the root of a place expression, checking any indices along the way
+fn check_place(expr: &Expression<Type>, env: &mut BorrowEnv) -> Result<Root, AnyError> {
+    let root = match &expr.expr {
+        // Functions aren't variables, so they can't be moved or borrowed
+        Expr::Reference(name) | Expr::Instance { func: name, .. } => match env.lookup(name) {
+            Some(var) => Root::Var(var),
+            None if env.statics.contains(name) => Root::Static(name.clone()),
+            None => Root::Temp(vec![]),
+        },
+        Expr::Unary {
+            op: Operator::TupleIndex(_),
+            rhs,
+        } => check_place(rhs, env)?,
+        Expr::Field { expr, .. } => check_place(expr, env)?,
+        Expr::Binary {
+            lhs,
+            op: Operator::ArrayIndex,
+            rhs,
+        } => {
+            let root = check_place(lhs, env)?;
+            check_expr(rhs, env)?;
+            root
+        }
+        // Going through a reference only uses it, so a mutable reference isn't moved
+        Expr::Unary {
+            op: Operator::Mul,
+            rhs,
+        } => match check_place(rhs, env)? {
+            Root::Var(var) => {
+                env.alive(var)?;
+                Root::Deref(env.vars[var].loans.clone())
+            }
+            Root::Deref(loans) | Root::Temp(loans) => Root::Deref(loans),
+            Root::Static(_) => Root::Deref(vec![]),
+        },
+        _ => Root::Temp(check_expr(expr, env)?),
+    };
+    Ok(root)
+}
+
+// Reading a place moves out of it, unless its type is copied
+fn check_read(expr: &Expression<Type>, env: &mut BorrowEnv) -> Result<Vec<Loan>, AnyError> {
+    let root = check_place(expr, env)?;
+    read_place(root, &expr.ty, env)
+}
+
+fn read_place(root: Root, ty: &Type, env: &mut BorrowEnv) -> Result<Vec<Loan>, AnyError> {
+    let loans = match root {
+        Root::Var(var) => {
+            env.alive(var)?;
+            if env.borrows(var).any(|kind| kind == Borrow::Mut) {
+                Err(Error::ConflictingBorrow(env.vars[var].name.clone()))?
+            }
+            if !ty.is_copy() {
+                if env.borrows(var).next().is_some() {
+                    Err(Error::MoveOutOfBorrowed(env.vars[var].name.clone()))?
+                }
+                // Moving out of a field or element moves out of the whole variable
+                env.vars[var].moved = true;
+            }
+            env.vars[var].loans.clone()
+        }
+        Root::Deref(loans) | Root::Temp(loans) => loans,
+        Root::Static(name) if !ty.is_copy() => Err(Error::MoveOutOfStatic(name))?,
+        Root::Static(_) => vec![],
+    };
+    if ty.holds_loans(&env.named) {
+        Ok(loans)
+    } else {
+        Ok(vec![])
+    }
+}
+
+fn check_borrow(
+    expr: &Expression<Type>,
+    kind: Borrow,
+    env: &mut BorrowEnv,
+) -> Result<Vec<Loan>, AnyError> {
+    let loans = match check_place(expr, env)? {
+        Root::Var(var) => {
+            env.alive(var)?;
+            // A mutable borrow can't overlap with any other borrow
+            let conflict = match kind {
+                Borrow::Shared => env.borrows(var).any(|kind| kind == Borrow::Mut),
+                Borrow::Mut => env.borrows(var).next().is_some(),
+            };
+            if conflict {
+                Err(Error::ConflictingBorrow(env.vars[var].name.clone()))?
+            }
+            // The reference also holds on to whatever the variable borrows
+            let mut loans = env.vars[var].loans.clone();
+            loans.push(Loan { var, kind });
+            loans
+        }
+        // Reborrowing through a reference keeps the loans of the reference
+        Root::Deref(loans) | Root::Temp(loans) => loans,
+        Root::Static(_) => vec![],
+    };
+    Ok(loans)
+}
+
+fn check_assign(
+    lhs: &Expression<Type>,
+    loans: Vec<Loan>,
+    env: &mut BorrowEnv,
+) -> Result<(), AnyError> {
+    let whole = matches!(lhs.expr, Expr::Reference(_));
+    let root = check_place(lhs, env)?;
+    assign_place(root, whole, loans, env)
+}
+
+fn assign_place(
+    root: Root,
+    whole: bool,
+    loans: Vec<Loan>,
+    env: &mut BorrowEnv,
+) -> Result<(), AnyError> {
+    match root {
+        Root::Var(var) => {
+            if env.borrows(var).next().is_some() {
+                Err(Error::AssignToBorrowed(env.vars[var].name.clone()))?
+            }
+            env.outlives(&loans, env.vars[var].depth)?;
+            // Assigning to the whole variable replaces its value, and gives it one again if it was moved
+            let var = &mut env.vars[var];
+            if whole && var.once && var.init != Init::No {
+                Err(Error::AssignedTwice(var.name.clone()))?
+            } else if !whole && var.init != Init::Yes {
+                Err(Error::Uninitialized(var.name.clone()))?
+            }
+            if whole {
+                var.loans = loans;
+                var.moved = false;
+                var.init = Init::Yes;
+            } else {
+                var.loans.extend(loans);
+            }
+        }
+        // Assigning through a reference stores the loans in everything it points to
+        Root::Deref(targets) => {
+            for target in targets {
+                env.outlives(&loans, env.vars[target.var].depth)?;
+                env.vars[target.var].loans.extend(loans.iter().copied());
+            }
+        }
+        // Statics are immutable, which type inference already ensures
+        Root::Static(_) | Root::Temp(_) => (),
+    }
+    Ok(())
+}
+
+impl BorrowEnv {
+    fn enter(&mut self) {
+        self.scopes.push(self.vars.len());
+    }
+
+    fn exit(&mut self) {
+        if let Some(start) = self.scopes.pop() {
+            self.vars.truncate(start);
+        }
+    }
+
+    fn declare(&mut self, name: &str, loans: Vec<Loan>) {
+        self.vars.push(Var {
+            name: name.to_owned(),
+            depth: self.scopes.len(),
+            moved: false,
+            loans,
+            init: Init::Yes,
+            once: false,
+        });
+    }
+
+    fn lookup(&self, name: &str) -> Option<VarId> {
+        self.vars.iter().rposition(|var| var.name == name)
+    }
+
+    fn alive(&self, var: VarId) -> Result<(), Error> {
+        if self.vars[var].init != Init::Yes {
+            Err(Error::Uninitialized(self.vars[var].name.clone()))
+        } else if self.vars[var].moved {
+            Err(Error::UseOfMoved(self.vars[var].name.clone()))
+        } else {
+            Ok(())
+        }
+    }
+
+    // The borrows of a variable, held by variables that haven't been moved out of and are still used
+    fn borrows(&self, var: VarId) -> impl Iterator<Item = Borrow> + '_ {
+        let live = self.live();
+        self.vars
+            .iter()
+            .zip(live)
+            .filter(|(holder, live)| *live && !holder.moved)
+            .flat_map(|(holder, _)| holder.loans.iter())
+            .filter(move |loan| loan.var == var)
+            .map(|loan| loan.kind)
+    }
+
+    // The variables that are used later on, along with the ones they borrow,
+    // since those can still be reached through them
+    fn live(&self) -> Vec<bool> {
+        let mut live = vec![false; self.vars.len()];
+        let mut used = vec![];
+        for later in &self.later {
+            let visible = &self.vars[..later.visible];
+            for name in &later.names {
+                used.extend(visible.iter().rposition(|var| &var.name == name));
+            }
+        }
+        while let Some(var) = used.pop() {
+            if !live[var] {
+                live[var] = true;
+                used.extend(self.vars[var].loans.iter().map(|loan| loan.var));
+            }
+        }
+        live
+    }
+
+    // Ensures none of the loans borrow from variables declared deeper than the given depth
+    fn outlives(&self, loans: &[Loan], depth: usize) -> Result<(), Error> {
+        match loans.iter().find(|loan| self.vars[loan.var].depth > depth) {
+            Some(loan) => Err(Error::OutlivesReferent(self.vars[loan.var].name.clone())),
+            None => Ok(()),
+        }
+    }
+
+    fn enter_loop(&mut self, label: &Option<String>) {
+        self.loops.push(LoopLoans {
+            label: label.clone(),
+            depth: self.scopes.len(),
+            loans: vec![],
+        });
+    }
+
+    // The loop that a break refers to, which type inference has already found
+    fn target(&self, label: &Option<String>) -> usize {
+        let target = match label {
+            Some(label) => self
+                .loops
+                .iter()
+                .rposition(|scope| scope.label.as_ref() == Some(label)),
+            None => self.loops.len().checked_sub(1),
+        };
+        target.expect("break targets are checked during type inference")
+    }
+
+    // Joins the state after two different branches, keeping only the variables declared before them
+    fn merge(&mut self, other: BorrowEnv) {
+        for (var, other) in self.vars.iter_mut().zip(other.vars) {
+            var.moved |= other.moved;
+            if var.init != other.init {
+                var.init = Init::Maybe;
+            }
+            union(&mut var.loans, other.loans);
+        }
+        for (scope, other) in self.loops.iter_mut().zip(other.loops) {
+            union(&mut scope.loans, other.loans);
+        }
+    }
+}
+
+fn union(loans: &mut Vec<Loan>, other: Vec<Loan>) {
+    for loan in other {
+        if !loans.contains(&loan) {
+            loans.push(loan);
+        }
+    }
+}
+
+impl Expression<Type> {
+    // The names of the variables used anywhere in the expression, which is
+    // enough to know whether a variable might be used without following the scopes
+    fn names(&self) -> HashSet<String> {
+        let mut names = HashSet::new();
+        let mut exprs = vec![self];
+        while let Some(expr) = exprs.pop() {
+            if let Expr::Reference(name) | Expr::Instance { func: name, .. } = &expr.expr {
+                names.insert(name.clone());
+            }
+            exprs.extend(expr.expr.children());
+        }
+        names
+    }
+}
+
+impl Pattern {
+    fn bindings(&self) -> Vec<&str> {
+        match self {
+            Pattern::Binding(name) => vec![name],
+            Pattern::Variant { args, .. } => args.iter().flat_map(|arg| arg.bindings()).collect(),
+            Pattern::Wildcard | Pattern::Value(_) => vec![],
+        }
+    }
+}
+
+impl Type {
+    // Whether reading a value of this type copies it, instead of moving it
+    fn is_copy(&self) -> bool {
+        match self {
+            // Named and enums are moved, like in Rust without `#[derive(Copy)]`,
+            // and so are type parameters, since they could be filled in with either
+            Type::Frame { .. } | Type::Reference(_, true) | Type::Named(_) | Type::Param(_) => {
+                false
+            }
+            Type::Tuple(tys) => tys.iter().all(|ty| ty.is_copy()),
+            Type::Array(ty, _) | Type::Alias(_, ty) => ty.is_copy(),
+            Type::Option(_) | Type::Result(..) => self.children().iter().all(|ty| ty.is_copy()),
+            _ => true,
+        }
+    }
+
+    // Whether a value of this type can borrow from a variable
+    fn holds_loans(&self, named: &Named) -> bool {
+        match self {
+            // Closures hold on to what they capture
+            Type::Reference(..) | Type::Frame { .. } | Type::Function { .. } | Type::Param(_) => {
+                true
+            }
+            Type::Tuple(tys) => tys.iter().any(|ty| ty.holds_loans(named)),
+            Type::Array(ty, _) | Type::Alias(_, ty) => ty.holds_loans(named),
+            Type::Option(_) | Type::Result(..) => {
+                self.children().iter().any(|ty| ty.holds_loans(named))
+            }
+            Type::Named(name) => named[name].iter().any(|ty| ty.holds_loans(named)),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check;
+    use crate::{parse, semantics::typeinfer::infer};
+    use anyhow::Error as AnyError;
+
+    fn check_src(src: &str) -> Result<(), AnyError> {
+        check(&infer(parse(src)?)?)
+    }
+
+    fn assert_rejects(src: &str, msg: &str) {
+        let err = format!("{:?}", check_src(src).unwrap_err());
+        assert!(err.contains(msg), "{}", err);
+    }
+
+    const COUNT: &str = "coro count()() -> i32 {
+        var i = 0;
+        while true { yield i; i = i + 1 }
+    }";
+
+    #[test]
+    fn test_moves() -> Result<(), AnyError> {
+        check_src(&format!(
+            "{COUNT} fn main() -> i32 {{ let a = count(); let b = a; b() }}"
+        ))?;
+        assert_rejects(
+            &format!("{COUNT} fn main() -> i32 {{ let a = count(); let b = a; a() }}"),
+            "Use of moved variable 'a'",
+        );
+        assert_rejects(
+            &format!(
+                "{COUNT} fn main() -> i32 {{ let a = count(); while true {{ let b = a }}; 0 }}"
+            ),
+            "Use of moved variable 'a'",
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_outlives() {
+        assert_rejects(
+            "fn main() -> i32 {
+                let x = 0;
+                var r = &x;
+                if true { let y = 1; r = &y };
+                *r
+            }",
+            "Borrowed variable 'y' doesn't live long enough",
+        );
+        assert_rejects(
+            "fn main() -> i32 { *id(1) }
+            fn id(x: i32) -> &i32 { &x }",
+            "Borrowed variable 'x' doesn't live long enough",
+        );
+        assert_rejects(
+            "fn main() -> i32 { *pick(&1) }
+            fn pick(r: &i32) -> &i32 { let y = 1; if *r > 0 { return &y }; r }",
+            "Borrowed variable 'y' doesn't live long enough",
+        );
+        assert_rejects(
+            "fn main() -> i32 { let r = loop { let y = 1; break &y }; *r }",
+            "Borrowed variable 'y' doesn't live long enough",
+        );
+    }
+
+    #[test]
+    fn test_borrowed() -> Result<(), AnyError> {
+        check_src("fn main() -> i32 { var x = 1; let r = &x; let y = *r; x = 2; x - y }")?;
+        assert_rejects(
+            "fn main() -> i32 { var x = 1; let r = &x; x = 2; *r }",
+            "Cannot assign to 'x' while it's borrowed",
+        );
+        assert_rejects(
+            "fn main() -> i32 { var x = 1; let r = &x; x += 2; *r }",
+            "Cannot assign to 'x' while it's borrowed",
+        );
+        assert_rejects(
+            &format!("{COUNT} fn main() -> i32 {{ let a = count(); let r = &a; let b = a; let c = r; 0 }}"),
+            "Cannot move out of 'a' while it's borrowed",
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_last_use() -> Result<(), AnyError> {
+        check_src("fn main() -> i32 { var t = (1, 2); let r = &t.0; let z = *r; t.1 = z; t.1 }")?;
+        // A shadowed variable can't be used anymore
+        check_src("fn main() -> i32 { var x = 1; let r = &mut x; let r = 2; x = r; x }")?;
+        // The next iteration might still use the borrow
+        assert_rejects(
+            "fn main() -> i32 { var x = 1; let r = &x; var i = 0; while i < 2 { x = 2; i = *r }; 0 }",
+            "Cannot assign to 'x' while it's borrowed",
+        );
+        // A borrow stored through a reference lasts as long as the reference is used
+        assert_rejects(
+            "fn main() -> i32 { var x = 1; var h = &0; let p = &mut h; *p = &x; x = 2; *(*p) }",
+            "Cannot assign to 'x' while it's borrowed",
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_mutable_borrows() -> Result<(), AnyError> {
+        check_src("fn main() -> i32 { var x = 1; let r = &mut x; *r = 2; let q = &(*r); *q - 2 }")?;
+        assert_rejects(
+            "fn main() -> i32 { var x = 1; let r = &mut x; let q = &x; *r = 2; *q }",
+            "Conflicting borrows of 'x'",
+        );
+        assert_rejects(
+            "fn main() -> i32 { var x = 1; let q = &x; let r = &mut x; *q }",
+            "Conflicting borrows of 'x'",
+        );
+        assert_rejects(
+            "fn main() -> i32 { var x = 1; let r = &mut x; let s = r; *r }",
+            "Use of moved variable 'r'",
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_closures() -> Result<(), AnyError> {
+        check_src("fn main() -> i32 { var n = 0; let inc = || n = n + 1; inc(); inc(); n }")?;
+        check_src("fn add(n: i32) -> (i32) -> i32 { move |x: i32| x + n } fn main() -> i32 { 0 }")?;
+        assert_rejects(
+            "fn add(n: i32) -> (i32) -> i32 { |x: i32| x + n } fn main() -> i32 { 0 }",
+            "Borrowed variable 'n' doesn't live long enough",
+        );
+        assert_rejects(
+            "fn main() -> i32 { var x = 1; let f = || x; x = 2; f() }",
+            "Cannot assign to 'x' while it's borrowed",
+        );
+        assert_rejects(
+            "fn main() -> i32 { var x = 1; let f = || x = 2; let y = x; f(); y }",
+            "Conflicting borrows of 'x'",
+        );
+        assert_rejects(
+            &format!("{COUNT} fn main() -> i32 {{ let a = count(); let f = move || a(); a() }}"),
+            "Use of moved variable 'a'",
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_generics() -> Result<(), AnyError> {
+        check_src("fn first<T>(xs: &[T; 2]) -> &T { &(*xs)[0] } fn main() -> i32 { let xs = [1, 2]; *first(&xs) }")?;
+        // A type parameter could be filled in with a type that's moved
+        assert_rejects(
+            "fn dup<T>(x: T) -> (T, T) { (x, x) } fn main() -> i32 { 0 }",
+            "Use of moved variable 'x'",
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_methods() -> Result<(), AnyError> {
+        let decls = "struct P { x: i32 } impl P { fn get(&self) -> &i32 { &(*self).x } fn take(self) -> i32 { self.x } }";
+        check_src(&format!(
+            "{decls} fn main() -> i32 {{ let p = P {{ x: 1 }}; let x = *p.get(); p.take() - x }}"
+        ))?;
+        // A method taking `self` moves its receiver
+        assert_rejects(
+            &format!("{decls} fn main() -> i32 {{ let p = P {{ x: 1 }}; p.take() - p.take() }}"),
+            "Use of moved variable 'p'",
+        );
+        // The result keeps its receiver borrowed
+        assert_rejects(
+            &format!(
+                "{decls} fn main() -> i32 {{ let p = P {{ x: 1 }}; let r = p.get(); p.take() - *r }}"
+            ),
+            "'p'",
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_deferred() -> Result<(), AnyError> {
+        check_src("fn main() -> i32 { let x; if true { x = 1 } else { x = 2 }; x }")?;
+        check_src("fn main() -> i32 { var x; x = 1; x = 2; x }")?;
+        assert_rejects(
+            "fn main() -> i32 { let x; if true { x = 1 }; x }",
+            "Use of possibly uninitialized variable 'x'",
+        );
+        assert_rejects(
+            "fn main() -> i32 { let x; x = 1; x = 2; x }",
+            "Variable 'x' is immutable, but it might be assigned more than once",
+        );
+        assert_rejects(
+            "fn main() -> i32 { var x; x += 1; x }",
+            "Use of possibly uninitialized variable 'x'",
+        );
+        assert_rejects(
+            "fn main() -> i32 { let x; while false { x = 1 }; 0 }",
+            "might be assigned more than once",
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_statics() -> Result<(), AnyError> {
+        let point = "struct P { x: i32 } static S: P = P { x: 1 }; const C: P = P { x: 2 };";
+        // A static can be borrowed for as long as any function needs it
+        check_src(&format!(
+            "{point} fn get() -> &P {{ &S }} fn main() -> i32 {{ let c = C; let d = C; S.x - (*get()).x }}"
+        ))?;
+        assert_rejects(
+            &format!("{point} fn main() -> i32 {{ let s = S; 0 }}"),
+            "Cannot move out of static 'S'",
+        );
+        Ok(())
+    }
+}
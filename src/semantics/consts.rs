@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+
+use crate::ast::tree::{Expr, Expression, Item, Operator, Value};
+use anyhow::{Context, Error as AnyError};
+
+use super::{
+    error::Error,
+    types::{Size, Type},
+};
+
+// Works out the value of each constant, in the order type inference put the globals in, so one
+// that overflows or divides by zero is caught before the program runs. A static is still
+// evaluated when the program starts, but its value is worked out too, for the constants using it.
+pub fn fold_globals(items: &mut [Item<Type>]) -> Result<(), AnyError> {
+    let mut values = HashMap::new();
+    for item in items {
+        match item {
+            Item::Const(def) => {
+                fold(&mut def.value, &values).context(format!("In constant '{}'", def.name))?;
+                values.insert(def.name.clone(), def.value.clone());
+            }
+            Item::Static(def) => {
+                let mut value = def.value.clone();
+                fold(&mut value, &values).context(format!("In static '{}'", def.name))?;
+                values.insert(def.name.clone(), value);
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+// Works out the value of an integer constant from the ones already known, so it can be the length
+// of an array type, as long as it's built from literals and arithmetic
+pub fn fold_length(
+    mut value: Expression<Type>,
+    known: &HashMap<String, Expression<Type>>,
+) -> Option<Expression<Type>> {
+    fold(&mut value, known).ok()?;
+    matches!(value.expr, Expr::Value(Value::Number(..))).then_some(value)
+}
+
+// Replaces each part of the expression whose operands are known with its value, leaving the
+// rest, like a function used as a value, to be evaluated when the program runs
+fn fold(
+    expr: &mut Expression<Type>,
+    values: &HashMap<String, Expression<Type>>,
+) -> Result<(), Error> {
+    for child in expr.expr.children_mut() {
+        fold(child, values)?;
+    }
+    let folded = match &expr.expr {
+        Expr::Reference(name) => values.get(name).filter(|value| is_literal(value)).cloned(),
+        Expr::Unary {
+            op: Operator::TupleIndex(i),
+            rhs,
+        } => match &rhs.expr {
+            Expr::Tuple(elems) => elems.get(*i).cloned(),
+            _ => None,
+        },
+        Expr::Unary { op, rhs } => unary(op, rhs, &expr.ty)?,
+        // Indexing past the end is left for the program to report when it runs
+        Expr::Binary {
+            lhs,
+            op: Operator::ArrayIndex,
+            rhs,
+        } => match (&lhs.expr, &rhs.expr) {
+            (Expr::Array(elems), Expr::Value(Value::Number(i, _))) => {
+                usize::try_from(*i).ok().and_then(|i| elems.get(i)).cloned()
+            }
+            _ => None,
+        },
+        Expr::Binary { lhs, op, rhs } => binary(lhs, op, rhs, &expr.ty)?,
+        Expr::Field { expr: inner, field } => match &inner.expr {
+            Expr::Struct { fields, .. } => fields
+                .iter()
+                .find(|(name, _)| name == field)
+                .map(|(_, value)| value.clone()),
+            _ => None,
+        },
+        _ => None,
+    };
+    if let Some(folded) = folded {
+        *expr = folded;
+    }
+    Ok(())
+}
+
+// Whether the expression is a value that's completely known
+fn is_literal(expr: &Expression<Type>) -> bool {
+    match &expr.expr {
+        Expr::Value(_)
+        | Expr::Tuple(_)
+        | Expr::Array(_)
+        | Expr::Struct { .. }
+        | Expr::Variant { .. } => expr.expr.children().into_iter().all(is_literal),
+        _ => false,
+    }
+}
+
+fn unary(
+    op: &Operator,
+    rhs: &Expression<Type>,
+    ty: &Type,
+) -> Result<Option<Expression<Type>>, Error> {
+    let value = match (op, &rhs.expr) {
+        (Operator::Sub, Expr::Value(Value::Number(x, _))) => number(Some(-x), op, ty)?,
+        (Operator::Sub, Expr::Value(Value::Float(x, _))) => Value::Float(-x, Some(ty.unaliased())),
+        (Operator::Not, Expr::Value(Value::Bool(x))) => Value::Bool(!x),
+        _ => return Ok(None),
+    };
+    Ok(Some(literal(value, ty)))
+}
+
+fn binary(
+    lhs: &Expression<Type>,
+    op: &Operator,
+    rhs: &Expression<Type>,
+    ty: &Type,
+) -> Result<Option<Expression<Type>>, Error> {
+    let (Expr::Value(lhs), Expr::Value(rhs)) = (&lhs.expr, &rhs.expr) else {
+        return Ok(None);
+    };
+    let value = match (lhs, rhs) {
+        (Value::Number(x, _), Value::Number(y, _)) => match compare(x, op, y) {
+            Some(result) => Some(Value::Bool(result)),
+            None => integer(*x, op, *y, ty)?,
+        },
+        (Value::Float(x, _), Value::Float(y, _)) => match compare(x, op, y) {
+            Some(result) => Some(Value::Bool(result)),
+            None => float(*x, op, *y, ty),
+        },
+        (Value::Bool(x), Value::Bool(y)) => match op {
+            Operator::And | Operator::BitAnd => Some(Value::Bool(x & y)),
+            Operator::Or | Operator::BitOr => Some(Value::Bool(x | y)),
+            Operator::BitXor | Operator::Neq => Some(Value::Bool(x ^ y)),
+            Operator::Eq => Some(Value::Bool(x == y)),
+            _ => None,
+        },
+        (Value::String(x), Value::String(y)) if *op == Operator::Add => {
+            Some(Value::String(format!("{}{}", x, y)))
+        }
+        (Value::String(x), Value::String(y)) => compare(x, op, y).map(Value::Bool),
+        (Value::Char(x), Value::Char(y)) => compare(x, op, y).map(Value::Bool),
+        _ => None,
+    };
+    Ok(value.map(|value| literal(value, ty)))
+}
+
+// Integers of the same type, where going past what the type holds is an error, like it is when
+// the program runs
+fn integer(x: i128, op: &Operator, y: i128, ty: &Type) -> Result<Option<Value>, Error> {
+    let result = match op {
+        Operator::Add => x.checked_add(y),
+        Operator::Sub => x.checked_sub(y),
+        Operator::Mul => x.checked_mul(y),
+        Operator::Div | Operator::Rem if y == 0 => Err(Error::DivisionByZero)?,
+        Operator::Div => x.checked_div(y),
+        Operator::Rem => x.checked_rem(y),
+        Operator::BitAnd => Some(x & y),
+        Operator::BitOr => Some(x | y),
+        Operator::BitXor => Some(x ^ y),
+        Operator::Shl | Operator::Shr => shift(x, op, y, ty),
+        _ => return Ok(None),
+    };
+    number(result, op, ty).map(Some)
+}
+
+// Shifts by at least the number of bits in the type overflow, while bits shifted past the top
+// are dropped, so the result is sign extended from the type's width
+fn shift(x: i128, op: &Operator, y: i128, ty: &Type) -> Option<i128> {
+    let (Type::Signed(size) | Type::Unsigned(size)) = ty.unalias() else {
+        return None;
+    };
+    let bits = size.bits();
+    let y = u32::try_from(y).ok().filter(|&y| y < bits)?;
+    let unused = 128 - bits;
+    Some(match (op, ty.unalias()) {
+        (Operator::Shl, Type::Signed(_)) => (x << y) << unused >> unused,
+        (Operator::Shl, _) => (((x << y) as u128) << unused >> unused) as i128,
+        _ => x >> y,
+    })
+}
+
+// Rounding the f64 result gives the same result as doing it in an f32
+fn float(x: f64, op: &Operator, y: f64, ty: &Type) -> Option<Value> {
+    let result = match op {
+        Operator::Add => x + y,
+        Operator::Sub => x - y,
+        Operator::Mul => x * y,
+        Operator::Div => x / y,
+        Operator::Rem => x % y,
+        _ => return None,
+    };
+    let result = match ty.unalias() {
+        Type::Float(Size::ThirtyTwo) => result as f32 as f64,
+        _ => result,
+    };
+    Some(Value::Float(result, Some(ty.unaliased())))
+}
+
+fn compare<X: PartialOrd>(x: X, op: &Operator, y: X) -> Option<bool> {
+    match op {
+        Operator::Gt => Some(x > y),
+        Operator::Lt => Some(x < y),
+        Operator::Gte => Some(x >= y),
+        Operator::Lte => Some(x <= y),
+        Operator::Eq => Some(x == y),
+        Operator::Neq => Some(x != y),
+        _ => None,
+    }
+}
+
+// An integer of the type, unless the result didn't fit in it
+fn number(x: Option<i128>, op: &Operator, ty: &Type) -> Result<Value, Error> {
+    match (x, ty.bounds()) {
+        (Some(x), Some((min, max))) if min <= x && x <= max => {
+            Ok(Value::Number(x, Some(ty.unaliased())))
+        }
+        _ => Err(Error::Overflow(*op, ty.clone())),
+    }
+}
+
+fn literal(value: Value, ty: &Type) -> Expression<Type> {
+    Expression {
+        expr: Expr::Value(value),
+        ty: ty.clone(),
+    }
+}
@@ -1,16 +1,27 @@
 use super::types::Type;
+use crate::ast::tree::Operator;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("Unexpected Type: expected '{0}' but found '{1}'")]
     UnexpectedType(Type, Type),
+    #[error("Expected an integer, but found '{0}'")]
+    NotInteger(Type),
+    #[error("Expected a number, but found '{0}'")]
+    NotNumeric(Type),
+    #[error("Literal '{0}' doesn't fit in '{1}'")]
+    OutOfRange(i128, Type),
+    #[error("Can't negate '{0}', since it's unsigned")]
+    UnsignedNegation(Type),
     #[error("Not mutable: '{0}' is not mutable")]
-    NotMutable(Type),
+    NotMutable(String),
     #[error("Redeclaration of function '{2}' with types: '{0}' and {1}")]
     Redeclaration(Type, Type, String),
-    #[error("Redeclaration of variable '{0}'")]
-    RedeclarationVariable(String),
+    #[error("Redeclaration of '{0}', which is already the name of another item")]
+    RedeclaredGlobal(String),
+    #[error("Redeclaration of '{0}', which is a builtin function")]
+    BuiltinRedeclaration(String),
     #[error("Function '{0}' not found")]
     FunctionNotFound(String),
     #[error("Variable '{0}' not found")]
@@ -23,4 +34,102 @@ pub enum Error {
     InvalidTupleIndex(Type),
     #[error("Invalid Array Index: '{0}' is not an array")]
     InvalidArrayIndex(Type),
+    #[error("Type '{0}' not found")]
+    TypeNotFound(String),
+    #[error("Redeclaration of type '{0}'")]
+    RedeclaredType(String),
+    #[error("Field '{1}' appears more than once in struct '{0}'")]
+    DuplicateField(String, String),
+    #[error("Missing field '{1}' in literal of struct '{0}'")]
+    MissingField(String, String),
+    #[error("No field '{1}' on type '{0}'")]
+    NoSuchField(Type, String),
+    #[error("Type '{0}' contains itself, and would have an infinite size")]
+    RecursiveType(String),
+    #[error("Variant '{1}' appears more than once in enum '{0}'")]
+    DuplicateVariant(String, String),
+    #[error("No variant '{1}' in enum '{0}'")]
+    VariantNotFound(String, String),
+    #[error("Variant '{0}' holds {1} values, but {2} were given")]
+    VariantArity(String, usize, usize),
+    #[error("Only an option or a result can be unwrapped with '?', not '{0}'")]
+    NotTryable(Type),
+    #[error("Type parameter '{0}' appears more than once")]
+    DuplicateParam(String),
+    #[error("Can't infer type parameter '{0}', it has to be given explicitly with '::<>'")]
+    CantInfer(String),
+    #[error("Can't infer the type of '{0}', it has to be given explicitly")]
+    UnknownType(String),
+    #[error("'{0}' can't be evaluated before the program runs, so it can't be a global's value")]
+    NotConstant(String),
+    #[error("'{0}' is evaluated from its own value")]
+    RecursiveGlobal(String),
+    #[error("Overflow: the result of '{0}' doesn't fit in '{1}'")]
+    Overflow(Operator, Type),
+    #[error("Division by zero")]
+    DivisionByZero,
+    #[error("'{0}' can't be the length of an array, it isn't a non-negative integer constant")]
+    InvalidLength(String),
+    #[error("'{0}' takes {1} type parameters, but {2} were given")]
+    TypeArgs(String, usize, usize),
+    #[error("Trait '{0}' not found")]
+    TraitNotFound(String),
+    #[error("Redeclaration of trait '{0}'")]
+    RedeclaredTrait(String),
+    #[error("Method '{1}' appears more than once in trait '{0}'")]
+    DuplicateMethod(String, String),
+    #[error("Trait '{0}' is implemented more than once for '{1}'")]
+    DuplicateImpl(String, Type),
+    #[error("Missing method '{1}' of trait '{0}'")]
+    MissingMethod(String, String),
+    #[error("Method '{1}' is not a member of trait '{0}'")]
+    NotInTrait(String, String),
+    #[error("Trait '{0}' is not implemented for '{1}'")]
+    NotImplemented(String, Type),
+    #[error("No method '{1}' on type '{0}'")]
+    NoSuchMethod(Type, String),
+    #[error("Method '{1}' on type '{0}' is ambiguous, since more than one of its traits has it")]
+    AmbiguousMethod(Type, String),
+    #[error("'{0}' doesn't take 'self', so it can't be called as a method")]
+    NotAMethod(String),
+    #[error("'{0}' takes {1} arguments, but {2} were given")]
+    ArgumentCount(String, usize, usize),
+    #[error("'{0}' is private, so it can't be used outside of its module")]
+    Private(String),
+    #[error("Can't find '{0}' to import it")]
+    UnresolvedImport(String),
+    #[error("'{0}' is imported, but it's already declared in the same module")]
+    ImportConflict(String),
+    #[error("Module '{0}' has to be loaded from its file before it's checked")]
+    ModuleNotLoaded(String),
+    #[error("Non-exhaustive match on '{0}': not every value of type '{1}' is covered")]
+    NonExhaustive(String, Type),
+    #[error("Can't loop over '{0}': only ranges and arrays can be iterated")]
+    NotIterable(Type),
+    #[error("'break' or 'continue' used outside of a loop")]
+    OutsideLoop,
+    #[error("Unknown loop label: '{0}")]
+    UnknownLabel(String),
+    #[error("Only 'loop' can be broken out of with a value, not 'while' or 'for'")]
+    BreakWithValue,
+    #[error("Yield used outside of a coroutine")]
+    YieldOutsideCoroutine,
+    #[error("Function '{0}' yields into '{1}', which is not the current frame")]
+    ForeignFrame(String, Type),
+    #[error("Use of moved variable '{0}'")]
+    UseOfMoved(String),
+    #[error("Cannot move out of '{0}' while it's borrowed")]
+    MoveOutOfBorrowed(String),
+    #[error("Cannot move out of static '{0}', which can only be copied or borrowed")]
+    MoveOutOfStatic(String),
+    #[error("Cannot assign to '{0}' while it's borrowed")]
+    AssignToBorrowed(String),
+    #[error("Conflicting borrows of '{0}': a mutable borrow can't overlap with any other use")]
+    ConflictingBorrow(String),
+    #[error("Borrowed variable '{0}' doesn't live long enough")]
+    OutlivesReferent(String),
+    #[error("Use of possibly uninitialized variable '{0}'")]
+    Uninitialized(String),
+    #[error("Variable '{0}' is immutable, but it might be assigned more than once")]
+    AssignedTwice(String),
 }
@@ -1,3 +1,7 @@
+pub mod borrowck;
+mod consts;
 mod error;
+mod modules;
+pub mod prelude;
 pub mod typeinfer;
 pub mod types;
@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use crate::ast::tree::{Item, Program};
+
+use super::{error::Error, prelude::Intrinsic};
+
+// Where each item of a program is declared, so names can be looked up from inside any module.
+// Items go by their full path, like `geo::Point`, where the root module's path is empty.
+#[derive(Debug, Clone, Default)]
+pub struct Modules {
+    // Each item by its path, along with the module it's declared in and whether it's public
+    items: HashMap<String, (String, bool)>,
+    // The names each module imports, along with the paths they stand for
+    imports: HashMap<String, HashMap<String, String>>,
+}
+
+// The path of a name declared in a module
+pub fn join(module: &str, name: &str) -> String {
+    if module.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{}::{}", module, name)
+    }
+}
+
+// The name an item is declared with, for the items that can be used by name
+fn declared(item: &Item<()>) -> Option<(&str, bool)> {
+    match item {
+        Item::Func(def) => Some((&def.name, def.public)),
+        Item::Struct(def) => Some((&def.name, def.public)),
+        Item::Enum(def) => Some((&def.name, def.public)),
+        Item::Alias(def) => Some((&def.name, def.public)),
+        Item::Trait(def) => Some((&def.name, def.public)),
+        Item::Const(def) | Item::Static(def) => Some((&def.name, def.public)),
+        _ => None,
+    }
+}
+
+// Whether code in the module can see the private items of the other one, which it can when it's
+// that module or inside of it
+fn within(module: &str, other: &str) -> bool {
+    other.is_empty() || module == other || module.starts_with(&format!("{}::", other))
+}
+
+impl Modules {
+    // Flattens the tree of modules into its items, each paired with the module it's in,
+    // once their imports have been resolved
+    pub fn flatten(&mut self, prgm: Program<()>) -> Result<Vec<(String, Item<()>)>, Error> {
+        let mut items = vec![];
+        let mut uses = vec![];
+        self.collect("", prgm.0, &mut items, &mut uses)?;
+        for (module, path) in uses {
+            let full = self.path(&module, &path);
+            if !self.items.contains_key(&full) {
+                Err(Error::UnresolvedImport(path.clone()))?
+            }
+            self.check(&module, &full)?;
+            let name = path.rsplit("::").next().unwrap_or(&path).to_owned();
+            if self.items.contains_key(&join(&module, &name)) {
+                Err(Error::ImportConflict(name.clone()))?
+            }
+            self.imports.entry(module).or_default().insert(name, full);
+        }
+        Ok(items)
+    }
+
+    fn collect(
+        &mut self,
+        module: &str,
+        items: Vec<Item<()>>,
+        flat: &mut Vec<(String, Item<()>)>,
+        uses: &mut Vec<(String, String)>,
+    ) -> Result<(), Error> {
+        for item in items {
+            match item {
+                Item::Mod(def) => {
+                    let path = join(module, &def.name);
+                    let items = def
+                        .items
+                        .ok_or_else(|| Error::ModuleNotLoaded(path.clone()))?;
+                    self.declare(&path, module, def.public);
+                    self.collect(&path, items, flat, uses)?;
+                }
+                Item::Use(path) => uses.push((module.to_owned(), path)),
+                item => {
+                    if let Some((name, public)) = declared(&item) {
+                        self.declare(&join(module, name), module, public);
+                    }
+                    flat.push((module.to_owned(), item));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn declare(&mut self, path: &str, module: &str, public: bool) {
+        self.items
+            .insert(path.to_owned(), (module.to_owned(), public));
+    }
+
+    // The full path a name written inside a module refers to. It starts from one of the module's
+    // imports or items, from the root with `crate::`, or from the parent module with `super::`.
+    // A name that isn't found is kept inside the module, so it's reported by its full path.
+    pub fn path(&self, module: &str, name: &str) -> String {
+        let mut segments = name.split("::").peekable();
+        let mut path = module.to_owned();
+        match segments.peek() {
+            Some(&"crate") => {
+                segments.next();
+                path = String::new();
+            }
+            Some(&"super") => {
+                while segments.next_if_eq(&"super").is_some() {
+                    path = path
+                        .rsplit_once("::")
+                        .map_or("", |(parent, _)| parent)
+                        .to_owned();
+                }
+            }
+            Some(first) => {
+                if let Some(import) = self.imports.get(module).and_then(|names| names.get(*first)) {
+                    segments.next();
+                    path = import.clone();
+                } else if Intrinsic::from_name(first).is_some() {
+                    // The prelude is seen from every module
+                    path = String::new();
+                }
+            }
+            None => (),
+        }
+        segments.fold(path, |path, segment| join(&path, segment))
+    }
+
+    // Ensures each item along the path can be used from the module
+    pub fn check(&self, module: &str, path: &str) -> Result<(), Error> {
+        let mut prefix = String::new();
+        for segment in path.split("::") {
+            prefix = join(&prefix, segment);
+            if let Some((declared, false)) = self.items.get(&prefix) && !within(module, declared) {
+                Err(Error::Private(prefix.clone()))?
+            }
+        }
+        Ok(())
+    }
+
+    // Resolves a name written inside a module, as long as it can be used from there
+    pub fn resolve(&self, module: &str, name: &str) -> Result<String, Error> {
+        let path = self.path(module, name);
+        self.check(module, &path)?;
+        Ok(path)
+    }
+}
@@ -0,0 +1,69 @@
+use super::types::{Size, Type, TypeParam};
+
+// Every type that can be printed implements the builtin `Display`, which bounds what the
+// printing functions of the prelude take
+pub const DISPLAY: &str = "Display";
+
+// The functions every program can call without declaring them, which the interpreter runs on
+// the host. They're seen from every module, and can't be redeclared.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Intrinsic {
+    Print,
+    Println,
+    ReadLine,
+    Assert,
+    AssertEq,
+    Exit,
+}
+
+impl Intrinsic {
+    pub const ALL: [Intrinsic; 6] = [
+        Intrinsic::Print,
+        Intrinsic::Println,
+        Intrinsic::ReadLine,
+        Intrinsic::Assert,
+        Intrinsic::AssertEq,
+        Intrinsic::Exit,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Intrinsic::Print => "print",
+            Intrinsic::Println => "println",
+            Intrinsic::ReadLine => "read_line",
+            Intrinsic::Assert => "assert",
+            Intrinsic::AssertEq => "assert_eq",
+            Intrinsic::Exit => "exit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|intrinsic| intrinsic.name() == name)
+    }
+
+    pub fn get_type(&self) -> Type {
+        let function = |args, ret| Type::Function {
+            args,
+            ret: Box::new(ret),
+        };
+        // Generic over any printable type, like `fn println<T: Display>(x: T)`
+        let printable = |args| Type::Generic {
+            params: vec![TypeParam {
+                name: "T".to_owned(),
+                bounds: vec![DISPLAY.to_owned()],
+            }],
+            ty: Box::new(function(args, Type::unit())),
+        };
+        let param = || Type::Param("T".to_owned());
+        match self {
+            Intrinsic::Print | Intrinsic::Println => printable(vec![param()]),
+            Intrinsic::AssertEq => printable(vec![param(), param()]),
+            Intrinsic::Assert => function(vec![Type::Bool], Type::unit()),
+            // A line without its newline, or `None` once the input has ended
+            Intrinsic::ReadLine => function(vec![], Type::Option(Box::new(Type::String))),
+            Intrinsic::Exit => function(vec![Type::Signed(Size::ThirtyTwo)], Type::Never),
+        }
+    }
+}
@@ -1,26 +1,1022 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    iter, mem,
+};
 
 use crate::ast::tree::*;
 use anyhow::{anyhow, Context, Error as AnyError};
 
 use super::{
+    consts::{fold_globals, fold_length},
     error::Error,
-    types::{Size, Type},
+    modules::{join, Modules},
+    prelude::{Intrinsic, DISPLAY},
+    types::{Size, Type, TypeParam},
 };
 
-type TypeEnv = HashMap<String, Type>;
+#[derive(Debug, Clone)]
+struct TypeEnv {
+    // Innermost scope last, the first scope holds the functions
+    scopes: Vec<HashMap<String, (bool, Type)>>,
+    // The frame that yields hand their values to, if we're inside a coroutine
+    frame: Option<Type>,
+    // Functions that yield, along with the frame they yield into
+    yielding: HashMap<String, Type>,
+    // The fields of each struct, in declaration order
+    structs: HashMap<String, Vec<(String, Type)>>,
+    // The variants of each enum, along with the types they carry
+    enums: HashMap<String, Vec<(String, Vec<Type>)>>,
+    // The loops around the current expression, innermost last
+    loops: Vec<LoopScope>,
+    // What returns must give back
+    ret: Returns,
+    // The closures around the current expression, innermost last
+    closures: Vec<ClosureScope>,
+    // The type parameters of the function being checked
+    generics: Vec<TypeParam>,
+    // The type an impl is for, which `Self` stands for inside of it
+    this: Option<Type>,
+    // The methods each trait requires
+    traits: HashMap<String, Vec<Signature>>,
+    // Each trait along with a type it's implemented for
+    impls: Vec<(String, Type)>,
+    // Where every item is declared, and the module the code being checked is in
+    modules: Modules,
+    module: String,
+    // The type each alias stands for, by its path
+    aliases: HashMap<String, Type>,
+    // The values of the constants that can be the lengths of arrays, by their paths, which are
+    // only known once the aliases have been declared
+    lengths: Option<HashMap<String, i128>>,
+    // What's been found out about each type variable of the function being checked
+    vars: Vec<Unknown>,
+    // The variables declared without a value, along with the scope they're in, which can still
+    // be assigned once even when they're immutable
+    deferred: HashSet<(String, usize)>,
+    // Binary operators used on a type variable, along with the types of their operands and the
+    // expression they're in, which are checked once the function has been inferred
+    pending: Vec<(Operator, Type, Type, String)>,
+}
+
+// A type variable, until it's found out what it stands for
+#[derive(Debug, Clone)]
+enum Unknown {
+    Any,
+    // The type of an empty array literal, which can turn out to be an array of any length
+    Array,
+    // The type of number literals, which is an i32 or an f64 unless they're used as another
+    // integer or float
+    Int,
+    Float,
+    Known(Type),
+}
+
+#[derive(Debug, Clone)]
+enum Returns {
+    // A coroutine's return type is the type it yields, so its body can end in anything
+    Any,
+    Declared(Type),
+    // A closure without a return type gets it from its returns and the end of its body
+    Inferred(Option<Type>),
+}
+
+// A closure that's being checked, along with the variables it uses from outside of it
+#[derive(Debug, Clone)]
+struct ClosureScope {
+    // The number of scopes around the closure, so its own variables are the ones deeper than that
+    depth: usize,
+    moves: bool,
+    captures: Vec<Capture>,
+}
+
+// A loop that's being checked, along with the type of the values it's broken out of with
+#[derive(Debug, Clone)]
+struct LoopScope {
+    label: Option<String>,
+    // Only `loop` can be broken out of with a value, since `while` and `for` can also just finish
+    valued: bool,
+    // The type of the breaks seen so far, if there are any
+    ty: Option<Type>,
+}
+
+// What a pattern checks for, besides binding names: a single variant or literal
+#[derive(Debug, Clone, PartialEq)]
+enum Ctor {
+    Variant(String),
+    Value(Value),
+}
+
+impl Ctor {
+    fn of(pattern: &Pattern) -> Option<Ctor> {
+        match pattern {
+            Pattern::Wildcard | Pattern::Binding(_) => None,
+            Pattern::Value(value) => Some(Ctor::Value(value.clone())),
+            Pattern::Variant { variant, .. } => Some(Ctor::Variant(variant.clone())),
+        }
+    }
+
+    // The row after the pattern in front of it is matched against the constructor,
+    // or nothing if it can't match
+    fn specialize(&self, row: &[Pattern], arity: usize) -> Option<Vec<Pattern>> {
+        let (head, rest) = row.split_first()?;
+        let mut args = match head {
+            Pattern::Wildcard | Pattern::Binding(_) => vec![Pattern::Wildcard; arity],
+            Pattern::Variant { variant, args, .. } if *self == Ctor::Variant(variant.clone()) => {
+                args.clone()
+            }
+            Pattern::Value(value) if *self == Ctor::Value(value.clone()) => vec![],
+            _ => return None,
+        };
+        args.extend_from_slice(rest);
+        Some(args)
+    }
+}
+
+impl TypeEnv {
+    fn new() -> Self {
+        let mut env = Self {
+            scopes: vec![HashMap::new()],
+            frame: None,
+            yielding: HashMap::new(),
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            loops: vec![],
+            ret: Returns::Any,
+            closures: vec![],
+            generics: vec![],
+            this: None,
+            traits: HashMap::new(),
+            impls: vec![],
+            modules: Modules::default(),
+            module: String::new(),
+            aliases: HashMap::new(),
+            lengths: None,
+            vars: vec![],
+            deferred: HashSet::new(),
+            pending: vec![],
+        };
+        for intrinsic in Intrinsic::ALL {
+            env.insert(intrinsic.name(), intrinsic.get_type(), false);
+        }
+        env
+    }
+
+    // Finds the loop that a break or continue refers to
+    fn target(&mut self, label: &Option<String>) -> Result<&mut LoopScope, Error> {
+        let Some(label) = label else {
+            return self.loops.last_mut().ok_or(Error::OutsideLoop);
+        };
+        self.loops
+            .iter_mut()
+            .rev()
+            .find(|scope| scope.label.as_ref() == Some(label))
+            .ok_or_else(|| Error::UnknownLabel(label.clone()))
+    }
+
+    // Checks the body of a loop, giving back the loop's scope once it's done
+    fn infer_loop(
+        &mut self,
+        body: Sequence<()>,
+        label: &Option<String>,
+        valued: bool,
+    ) -> Result<(Sequence<Type>, LoopScope), AnyError> {
+        self.loops.push(LoopScope {
+            label: label.clone(),
+            valued,
+            ty: None,
+        });
+        let body = infer_seq(body, self);
+        let scope = self.loops.pop().expect("loop scope was pushed");
+        Ok((body?, scope))
+    }
+
+    // Checks the body of a closure, giving back its return type and what it captures
+    fn infer_closure(
+        &mut self,
+        params: &[(String, Type)],
+        ret: Option<Type>,
+        body: Sequence<()>,
+        moves: bool,
+    ) -> Result<(Sequence<Type>, Type, Vec<Capture>), AnyError> {
+        self.closures.push(ClosureScope {
+            depth: self.scopes.len(),
+            moves,
+            captures: vec![],
+        });
+        // Loops, returns and yields can't reach outside of the closure
+        let loops = std::mem::take(&mut self.loops);
+        let frame = self.frame.take();
+        let returns = match ret {
+            Some(ret) => Returns::Declared(ret),
+            None => Returns::Inferred(None),
+        };
+        let returns = std::mem::replace(&mut self.ret, returns);
+        self.enter();
+        for (name, ty) in params {
+            self.insert(name, ty.clone(), false);
+        }
+        let body = infer_seq(body, self).and_then(|mut body| {
+            let ret = self
+                .finish(&mut body)
+                .context("Return type mismatch for closure")?;
+            Ok((body, ret))
+        });
+        self.exit();
+        self.ret = returns;
+        self.frame = frame;
+        self.loops = loops;
+        let scope = self.closures.pop().expect("closure scope was pushed");
+        let (body, ret) = body?;
+        Ok((body, ret, scope.captures))
+    }
+
+    // The scope that a name is bound in, counting from the outermost one
+    fn depth(&self, name: &str) -> Option<usize> {
+        self.scopes
+            .iter()
+            .rposition(|scope| scope.contains_key(name))
+    }
+
+    // Records a variable used by the closures it's declared outside of
+    fn capture(&mut self, name: &str, ty: &Type) {
+        // The outermost scope only holds functions, which don't need capturing
+        let Some(depth) = self.depth(name).filter(|&depth| depth > 0) else {
+            return;
+        };
+        let closures = self
+            .closures
+            .iter_mut()
+            .filter(|closure| closure.depth > depth);
+        for closure in closures {
+            if !closure.captures.iter().any(|capture| capture.name == name) {
+                closure.captures.push(Capture {
+                    name: name.to_owned(),
+                    ty: ty.clone(),
+                    mutable: false,
+                });
+            }
+        }
+    }
+
+    // Marks the variable at the root of a place as mutated by the closures that captured it,
+    // which they can only do when capturing by reference
+    fn capture_mut(&mut self, place: &Expression<Type>) -> Result<(), Error> {
+        let Some(name) = root(place) else {
+            return Ok(());
+        };
+        let Some(depth) = self.depth(name) else {
+            return Ok(());
+        };
+        let closures = self
+            .closures
+            .iter_mut()
+            .filter(|closure| closure.depth > depth);
+        for closure in closures {
+            if closure.moves {
+                Err(Error::NotMutable(name.to_owned()))?
+            }
+            for capture in closure
+                .captures
+                .iter_mut()
+                .filter(|capture| capture.name == name)
+            {
+                capture.mutable = true;
+            }
+        }
+        Ok(())
+    }
+
+    // The type of a variable or function that's used by name
+    fn reference(&mut self, name: &str) -> Result<Type, Error> {
+        let ty = self
+            .get(name)
+            .ok_or_else(|| Error::VariableNotFound(name.to_owned()))?
+            .clone();
+        // Functions that yield can only be used from within the frame they yield into
+        if let Some(frame) = self.yielding.get(name) && self.is_global(name) && self.frame.as_ref() != Some(frame) {
+            Err(Error::ForeignFrame(name.to_owned(), frame.clone()))?
+        }
+        self.capture(name, &ty);
+        Ok(ty)
+    }
+
+    // Fills in the names that a type written inside of a function can use: its type parameters,
+    // the structs and enums its module can see, and `Self` when it's in an impl
+    fn resolve(&self, ty: &Type) -> Result<Type, Error> {
+        let ty = ty
+            .resolve(&self.generics)
+            .rename(&|name| self.modules.path(&self.module, name));
+        for name in ty.names().into_iter().chain(ty.lengths()) {
+            self.modules.check(&self.module, name)?;
+        }
+        let ty = self.size(ty.expand(&self.aliases))?;
+        Ok(match &self.this {
+            Some(this) => ty.replace_self(this),
+            None => ty,
+        })
+    }
+
+    // Puts in the values of the constants used as the lengths of arrays, which are left in
+    // aliases until they're known
+    fn size(&self, ty: Type) -> Result<Type, Error> {
+        let Some(values) = &self.lengths else {
+            return Ok(ty);
+        };
+        let mut lengths = HashMap::new();
+        for name in ty.lengths() {
+            let len = values
+                .get(name)
+                .and_then(|&len| usize::try_from(len).ok())
+                .ok_or_else(|| Error::InvalidLength(name.to_owned()))?;
+            lengths.insert(name.to_owned(), len);
+        }
+        Ok(ty.size(&lengths))
+    }
+
+    // The path of an item that's used by name from the current module
+    fn path(&self, name: &str) -> Result<String, Error> {
+        self.modules.resolve(&self.module, name)
+    }
+
+    fn implements(&self, ty: &Type, trait_: &str) -> bool {
+        match ty.unalias() {
+            Type::Param(name) => self
+                .generics
+                .iter()
+                .any(|param| param.name == *name && param.bounds.iter().any(|b| b == trait_)),
+            _ if trait_ == DISPLAY => ty.is_printable(),
+            _ => self
+                .impls
+                .iter()
+                .any(|(name, impl_ty)| name == trait_ && impl_ty.same(ty)),
+        }
+    }
+
+    // The type of a generic function once it's called with arguments of these types,
+    // with its type parameters filled in from them
+    fn instantiate(
+        &mut self,
+        params: &[TypeParam],
+        ty: &Type,
+        args: &[Type],
+    ) -> Result<Type, Error> {
+        let arg_tys = match ty {
+            Type::Function { args, .. } | Type::Coro { args, .. } => &args[..],
+            _ => &[],
+        };
+        let mut bindings = HashMap::new();
+        for (ty, arg) in arg_tys.iter().zip(args) {
+            ty.bind(params, arg, &mut bindings);
+        }
+        if let Some(param) = params
+            .iter()
+            .find(|param| !bindings.contains_key(&param.name))
+        {
+            Err(Error::CantInfer(param.name.clone()))?
+        }
+        self.check_bounds(params, &bindings)?;
+        Ok(ty.substitute(&bindings))
+    }
+
+    // Ensures the types given for the type parameters implement the traits they're bound by
+    fn check_bounds(
+        &mut self,
+        params: &[TypeParam],
+        bindings: &HashMap<String, Type>,
+    ) -> Result<(), Error> {
+        for param in params.iter().filter(|param| !param.bounds.is_empty()) {
+            // A number literal is given its type by now, to find the traits it implements
+            let ty = self.known(&bindings[&param.name]);
+            if let Some(bound) = param.bounds.iter().find(|b| !self.implements(&ty, b)) {
+                Err(Error::NotImplemented(bound.clone(), ty.clone()))?
+            }
+        }
+        Ok(())
+    }
+
+    // Finds the function a method call refers to, from an impl of the receiver's type or of a type
+    // it references, or from the traits a type parameter is bound by, along with the trait it's from.
+    // Inherent methods come first, and a method from a trait can only be called if it's the only one.
+    fn method(&self, ty: &Type, name: &str) -> Result<(Type, Option<String>), Error> {
+        let mut found = ty;
+        loop {
+            if let Type::Param(param) = found {
+                let mut sigs = self.bound_methods(param, name);
+                if let Some((trait_, sig)) = sigs.next() {
+                    if sigs.next().is_some() {
+                        Err(Error::AmbiguousMethod(found.clone(), name.to_owned()))?
+                    }
+                    return Ok((sig.get_type(found), Some(trait_.clone())));
+                }
+            }
+            let path = method_path(&found.unaliased(), name);
+            if let Some((_, func)) = self.scopes[0].get(&path) {
+                self.modules.check(&self.module, &path)?;
+                return Ok((func.clone(), None));
+            }
+            if let Some((trait_, path)) = self.trait_method(found, name)? {
+                self.modules.check(&self.module, &path)?;
+                return Ok((self.scopes[0][&path].1.clone(), Some(trait_)));
+            }
+            if let Some(func) = found.builtin_method(name) {
+                return Ok((func, None));
+            }
+            match found.unalias() {
+                Type::Reference(ty, _) => found = ty,
+                _ => Err(Error::NoSuchMethod(ty.clone(), name.to_owned()))?,
+            }
+        }
+    }
+
+    fn bound_methods<'a>(
+        &'a self,
+        param: &'a str,
+        name: &'a str,
+    ) -> impl Iterator<Item = (&'a String, &'a Signature)> {
+        self.generics
+            .iter()
+            .filter(move |p| p.name == param)
+            .flat_map(|p| &p.bounds)
+            .filter_map(|bound| Some(bound).zip(self.traits.get(bound)))
+            .flat_map(|(bound, sigs)| sigs.iter().map(move |sig| (bound, sig)))
+            .filter(move |(_, sig)| sig.name == name)
+    }
+
+    // The function from an impl of a trait for the type with this name, and the trait it's from,
+    // as long as no other trait the type implements has one too
+    fn trait_method(&self, ty: &Type, name: &str) -> Result<Option<(String, String)>, Error> {
+        let mut found = self
+            .impls
+            .iter()
+            .filter(|(_, impl_ty)| impl_ty.same(ty))
+            .map(|(trait_, impl_ty)| (trait_.clone(), trait_method_path(impl_ty, trait_, name)))
+            .filter(|(_, path)| self.scopes[0].contains_key(path));
+        match (found.next(), found.next()) {
+            (Some(_), Some(_)) => Err(Error::AmbiguousMethod(ty.clone(), name.to_owned())),
+            (method, _) => Ok(method),
+        }
+    }
+
+    // Dereferences or borrows the receiver of a method call, until it's what the method takes as `self`.
+    // A mutable reference gets borrowed again, so it isn't moved into the call.
+    fn adjust(
+        &mut self,
+        mut receiver: Expression<Type>,
+        this: &Type,
+    ) -> Result<Expression<Type>, Error> {
+        let unary = |op, rhs, ty| Expression {
+            expr: Expr::Unary {
+                op,
+                rhs: Box::new(rhs),
+            },
+            ty,
+        };
+        loop {
+            receiver = match (this.unalias(), receiver.ty.unalias().clone()) {
+                (Type::Reference(ty, mutable), _) if ty.same(&receiver.ty) => {
+                    if *mutable {
+                        if !self.is_mutable(&receiver) {
+                            Err(Error::NotMutable(receiver.to_string()))?
+                        }
+                        self.capture_mut(&receiver)?;
+                    }
+                    let op = if *mutable {
+                        Operator::RefMut
+                    } else {
+                        Operator::Ref
+                    };
+                    return Ok(unary(op, receiver, this.clone()));
+                }
+                (_, Type::Reference(_, false)) if receiver.ty.coerces_to(this) => {
+                    return Ok(receiver)
+                }
+                (_, Type::Reference(ty, _)) => unary(Operator::Mul, receiver, *ty),
+                (_, ty) if ty.coerces_to(this) => return Ok(receiver),
+                (_, ty) => Err(Error::UnexpectedType(this.clone(), ty))?,
+            };
+        }
+    }
+
+    fn field(&self, ty: &Type, field: &str) -> Result<Type, Error> {
+        let no_field = || Error::NoSuchField(ty.clone(), field.to_owned());
+        let Some(fields) = ty.name().and_then(|name| self.structs.get(name)) else {
+            return Err(no_field());
+        };
+        fields
+            .iter()
+            .find(|(name, _)| name == field)
+            .map(|(_, ty)| ty.clone())
+            .ok_or_else(no_field)
+    }
+
+    // Whether the struct of this path is a tuple struct, which is built like a function call
+    fn is_tuple_struct(&self, path: &str) -> bool {
+        self.structs
+            .get(path)
+            .is_some_and(|fields| is_tuple(fields))
+    }
+
+    // The types carried by a variant
+    fn variant(&self, enum_: &str, variant: &str) -> Result<Vec<Type>, Error> {
+        self.enums
+            .get(enum_)
+            .and_then(|variants| variants.iter().find(|(name, _)| name == variant))
+            .map(|(_, tys)| tys.clone())
+            .ok_or_else(|| Error::VariantNotFound(enum_.to_owned(), variant.to_owned()))
+    }
+
+    // The types a struct or enum holds inline
+    fn inline_types(&self, name: &str) -> Vec<&Type> {
+        match self.structs.get(name) {
+            Some(fields) => fields.iter().map(|(_, ty)| ty).collect(),
+            None => self.enums[name].iter().flat_map(|(_, tys)| tys).collect(),
+        }
+    }
+
+    // Ensures every struct or enum named in a type has been declared
+    fn check_type(&self, ty: &Type) -> Result<(), Error> {
+        match ty {
+            Type::Signed(_)
+            | Type::Unsigned(_)
+            | Type::Float(_)
+            | Type::Bool
+            | Type::Char
+            | Type::String
+            | Type::Never
+            | Type::Param(_)
+            | Type::Var(_) => Ok(()),
+            Type::Named(name) if self.structs.contains_key(name) => Ok(()),
+            Type::Named(name) if self.enums.contains_key(name) => Ok(()),
+            Type::Named(name) => Err(Error::TypeNotFound(name.clone())),
+            Type::ArrayLen(_, len) => Err(Error::InvalidLength(len.clone())),
+            Type::Array(ty, _) | Type::Reference(ty, _) | Type::Range(ty) | Type::Option(ty) => {
+                self.check_type(ty)
+            }
+            Type::Result(ty, err) => self.check_type(ty).and(self.check_type(err)),
+            Type::Generic { ty, .. } | Type::Alias(_, ty) => self.check_type(ty),
+            Type::Tuple(tys) => tys.iter().try_for_each(|ty| self.check_type(ty)),
+            Type::Function { args, ret } => args
+                .iter()
+                .chain([ret.as_ref()])
+                .try_for_each(|ty| self.check_type(ty)),
+            Type::Coro {
+                args,
+                resume,
+                yields,
+            } => args
+                .iter()
+                .chain(resume)
+                .chain([yields.as_ref()])
+                .try_for_each(|ty| self.check_type(ty)),
+            Type::Frame { resume, yields } => resume
+                .iter()
+                .chain([yields.as_ref()])
+                .try_for_each(|ty| self.check_type(ty)),
+        }
+    }
+
+    // Whether a value of the type holds a struct or enum inline, rather than behind a reference
+    fn contains(&self, ty: &Type, name: &str, seen: &mut HashSet<String>) -> bool {
+        match ty {
+            Type::Named(x) if x == name => true,
+            Type::Named(x) => {
+                seen.insert(x.clone())
+                    && self
+                        .inline_types(x)
+                        .into_iter()
+                        .any(|ty| self.contains(ty, name, seen))
+            }
+            Type::Array(ty, _) | Type::Alias(_, ty) => self.contains(ty, name, seen),
+            Type::Tuple(tys) => tys.iter().any(|ty| self.contains(ty, name, seen)),
+            _ => false,
+        }
+    }
+
+    // Finds the innermost binding of a name
+    fn get(&self, name: &str) -> Option<&Type> {
+        self.lookup(name).map(|(_, ty)| ty)
+    }
+
+    fn lookup(&self, name: &str) -> Option<&(bool, Type)> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    // Binds a name in the innermost scope, shadowing any earlier binding
+    fn insert(&mut self, name: &str, ty: Type, mutable: bool) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_owned(), (mutable, ty));
+        }
+        self.deferred
+            .remove(&(name.to_owned(), self.scopes.len() - 1));
+    }
+
+    // Whether the expression is a variable that was declared without a value, so it can be
+    // assigned its first value
+    fn is_deferred(&self, expr: &Expression<Type>) -> bool {
+        let Expr::Reference(name) = &expr.expr else {
+            return false;
+        };
+        self.depth(name)
+            .is_some_and(|depth| self.deferred.contains(&(name.clone(), depth)))
+    }
+
+    // Checks that a place can be assigned to, which a variable declared without a value can be
+    // given one through, even when it's immutable
+    fn assign(&mut self, place: &Expression<Type>) -> Result<(), Error> {
+        if !self.is_mutable(place) && !self.is_deferred(place) {
+            Err(Error::NotMutable(place.to_string()))?
+        }
+        self.capture_mut(place)
+    }
+
+    // Whether the expression is a place that can be assigned to, or borrowed mutably
+    fn is_mutable(&self, expr: &Expression<Type>) -> bool {
+        match &expr.expr {
+            Expr::Reference(name) => matches!(self.lookup(name), Some((true, _))),
+            Expr::Unary {
+                op: Operator::TupleIndex(_),
+                rhs,
+            } => self.is_mutable(rhs),
+            // The characters of a string can't be changed
+            Expr::Binary {
+                lhs,
+                op: Operator::ArrayIndex,
+                ..
+            } => lhs.ty != Type::String && self.is_mutable(lhs),
+            Expr::Field { expr, .. } => self.is_mutable(expr),
+            Expr::Unary {
+                op: Operator::Mul,
+                rhs,
+            } => matches!(rhs.ty.unalias(), Type::Reference(_, true)),
+            _ => false,
+        }
+    }
+
+    // Every constructor of a type, if there's a finite number of them
+    fn ctors(&self, ty: &Type) -> Option<Vec<Ctor>> {
+        match ty.unalias() {
+            Type::Bool => Some(vec![
+                Ctor::Value(Value::Bool(true)),
+                Ctor::Value(Value::Bool(false)),
+            ]),
+            Type::Named(name) => self.enums.get(name).map(|variants| {
+                variants
+                    .iter()
+                    .map(|(variant, _)| Ctor::Variant(variant.clone()))
+                    .collect()
+            }),
+            ty => ty.variants().map(|variants| {
+                variants
+                    .into_iter()
+                    .map(|(variant, _)| Ctor::Variant(variant))
+                    .collect()
+            }),
+        }
+    }
+
+    // The types of the values a constructor holds
+    fn ctor_args(&self, ctor: &Ctor, ty: &Type) -> Vec<Type> {
+        match (ctor, ty.unalias()) {
+            (Ctor::Variant(variant), Type::Named(name)) => {
+                self.variant(name, variant).unwrap_or_default()
+            }
+            (Ctor::Variant(variant), ty) => builtin_variant(ty, variant).unwrap_or_default(),
+            _ => vec![],
+        }
+    }
+
+    // Whether some values of these types match the row of patterns, but none of the rows in the matrix.
+    // A match is exhaustive when a wildcard isn't useful after all of its unguarded arms.
+    fn useful(&self, matrix: &[Vec<Pattern>], row: &[Pattern], tys: &[Type]) -> bool {
+        let (Some(head), Some(ty)) = (row.first(), tys.first()) else {
+            return matrix.is_empty();
+        };
+        let ctors = match Ctor::of(head) {
+            Some(ctor) => vec![ctor],
+            None => {
+                let used = matrix
+                    .iter()
+                    .filter_map(|row| Ctor::of(&row[0]))
+                    .collect::<Vec<_>>();
+                match self.ctors(ty) {
+                    Some(all) if all.iter().all(|ctor| used.contains(ctor)) => all,
+                    // Some constructor is missing, so only the rows that match anything can cover it
+                    _ => {
+                        let matrix = matrix
+                            .iter()
+                            .filter(|row| Ctor::of(&row[0]).is_none())
+                            .map(|row| row[1..].to_vec())
+                            .collect::<Vec<_>>();
+                        return self.useful(&matrix, &row[1..], &tys[1..]);
+                    }
+                }
+            }
+        };
+        ctors.iter().any(|ctor| {
+            let mut arg_tys = self.ctor_args(ctor, ty);
+            let arity = arg_tys.len();
+            arg_tys.extend_from_slice(&tys[1..]);
+            let matrix = matrix
+                .iter()
+                .filter_map(|row| ctor.specialize(row, arity))
+                .collect::<Vec<_>>();
+            ctor.specialize(row, arity)
+                .is_some_and(|row| self.useful(&matrix, &row, &arg_tys))
+        })
+    }
+
+    // Whether a name refers to a function, rather than a variable that shadows it
+    fn is_global(&self, name: &str) -> bool {
+        self.scopes[1..]
+            .iter()
+            .all(|scope| !scope.contains_key(name))
+    }
+
+    fn enter(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn exit(&mut self) {
+        self.scopes.pop();
+        let depth = self.scopes.len();
+        self.deferred.retain(|(_, scope)| *scope < depth);
+    }
+
+    // A new type variable, which stands for a type that isn't known yet
+    fn fresh(&mut self, unknown: Unknown) -> Type {
+        self.vars.push(unknown);
+        Type::Var(self.vars.len() - 1)
+    }
+
+    // The type with each of its type variables that's been found out replaced by its type
+    fn subst(&self, ty: &Type) -> Type {
+        ty.fill(&|var| match &self.vars[var] {
+            Unknown::Known(ty) => Some(ty.clone()),
+            _ => None,
+        })
+    }
+
+    // Finds out what the type variables in two types stand for, so that the types are the same.
+    // Whatever doesn't fit is left for the check that follows, which says what was expected.
+    fn unify(&mut self, expected: &Type, found: &Type) {
+        let (expected, found) = (self.subst(expected), self.subst(found));
+        let (expected, found) = (expected.unalias(), found.unalias());
+        match (expected, found) {
+            (Type::Var(a), Type::Var(b)) if a == b => (),
+            // The variable that's already known to be an array is the one that's kept
+            (Type::Var(a), Type::Var(b)) => match self.vars[*b] {
+                Unknown::Any => self.vars[*b] = Unknown::Known(expected.clone()),
+                _ => self.bind(*a, found),
+            },
+            (Type::Var(var), ty) | (ty, Type::Var(var)) => self.bind(*var, ty),
+            (Type::Generic { .. }, _) => (),
+            _ if mem::discriminant(expected) == mem::discriminant(found) => {
+                let (expected, found) = (expected.children(), found.children());
+                if expected.len() == found.len() {
+                    for (expected, found) in expected.into_iter().zip(found) {
+                        self.unify(expected, found);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn bind(&mut self, var: usize, ty: &Type) {
+        // A value that never finishes fits any type, so it says nothing about the variable,
+        // and a type can't contain itself
+        if *ty == Type::Never || ty.mentions(var) {
+            return;
+        }
+        let fits = match (&self.vars[var], ty) {
+            (Unknown::Any, _) => true,
+            (unknown, Type::Var(other)) => {
+                mem::discriminant(unknown) == mem::discriminant(&self.vars[*other])
+            }
+            (Unknown::Array, ty) => matches!(ty.unalias(), Type::Array(..)),
+            (Unknown::Int, ty) => ty.is_integer(),
+            (Unknown::Float, ty) => ty.is_float(),
+            (Unknown::Known(_), _) => false,
+        };
+        if fits {
+            self.vars[var] = Unknown::Known(ty.clone());
+        } else {
+            // Literals used as anything else are reported as the type they'd have on their own
+            self.fallback(var);
+        }
+    }
+
+    // The type with the number literals in it that have to be known right away, like the
+    // receiver of a method, given their usual type
+    fn known(&mut self, ty: &Type) -> Type {
+        let ty = self.subst(ty);
+        for var in 0..self.vars.len() {
+            if ty.mentions(var) {
+                self.fallback(var);
+            }
+        }
+        self.subst(&ty)
+    }
+
+    // Number literals whose type is still unknown are an i32 or an f64
+    fn fallback(&mut self, var: usize) {
+        self.vars[var] = match self.vars[var] {
+            Unknown::Int => Unknown::Known(Type::Signed(Size::ThirtyTwo)),
+            Unknown::Float => Unknown::Known(Type::Float(Size::SixtyFour)),
+            ref unknown => unknown.clone(),
+        }
+    }
+
+    // Gives the number literals in an expression the type they're used as, which can be a type
+    // that isn't known yet, as long as it stays a number
+    fn settle(&mut self, expr: &mut Expression<Type>, ty: &Type) -> Result<(), Error> {
+        let ty = self.subst(ty).unalias().clone();
+        let Type::Var(var) = ty else {
+            return settle(expr, &ty);
+        };
+        if !is_literal(expr) {
+            return Ok(());
+        }
+        let number = match expr.ty.is_float() {
+            true => Unknown::Float,
+            false => Unknown::Int,
+        };
+        match &self.vars[var] {
+            Unknown::Any => self.vars[var] = number,
+            unknown if mem::discriminant(unknown) == mem::discriminant(&number) => (),
+            _ => return Ok(()),
+        }
+        retype(expr, &ty, false)
+    }
+
+    // Checks that an expression can be used where the type is expected, finding out the
+    // type variables in either of them
+    fn expect(&mut self, expr: &mut Expression<Type>, ty: &Type) -> Result<(), AnyError> {
+        self.settle(expr, ty)?;
+        self.unify(ty, &expr.ty);
+        expr.ty = self.subst(&expr.ty);
+        expr.expect(&self.subst(ty))
+    }
+
+    // Joins the types of two branches, after finding out the type variables in them
+    fn join(&mut self, ty: &Type, other: &Type) -> Result<Type, Error> {
+        self.unify(ty, other);
+        let (ty, other) = (self.subst(ty), self.subst(other));
+        ty.join(&other).ok_or(Error::UnexpectedType(ty, other))
+    }
+
+    // Checks the type of a value that's returned
+    fn returns(&mut self, ty: &Type) -> Result<(), AnyError> {
+        let mut ret = mem::replace(&mut self.ret, Returns::Any);
+        if let Returns::Declared(expected) | Returns::Inferred(Some(expected)) = &mut ret {
+            self.unify(expected, ty);
+            *expected = self.subst(expected);
+        }
+        let checked = ret.check(self.subst(ty));
+        self.ret = ret;
+        checked
+    }
+
+    // Checks the value a body ends with, giving back the type of everything it returns
+    fn finish(&mut self, body: &mut Sequence<Type>) -> Result<Type, AnyError> {
+        if let Some(last) = body.0.last_mut() {
+            self.ret.settle(last)?;
+        }
+        let ty = body.0.last().map_or(Type::unit(), |expr| expr.ty.clone());
+        self.returns(&ty)?;
+        match &self.ret {
+            Returns::Declared(ret) | Returns::Inferred(Some(ret)) => Ok(ret.clone()),
+            _ => Ok(self.subst(&ty)),
+        }
+    }
+
+    // Settles the type variables that are left once a function or global has been inferred,
+    // and checks the operators that were used on them
+    fn complete(&mut self, body: &mut [Expression<Type>]) -> Result<(), AnyError> {
+        // Literals that weren't used as anything in particular keep their usual type
+        for var in 0..self.vars.len() {
+            self.fallback(var);
+        }
+        for (op, lhs, rhs, shown) in &self.pending {
+            infer_binary(*op, &self.subst(lhs), &self.subst(rhs))
+                .context(format!("On binary expression: {}", shown))?;
+        }
+        for expr in body {
+            self.fill(expr)?;
+        }
+        Ok(())
+    }
+
+    // Replaces the type variables in an expression and the ones inside of it with the types
+    // they were found to be, which have to be known by the end of the function
+    fn fill(&self, expr: &mut Expression<Type>) -> Result<(), Error> {
+        let fill = |ty: &mut Type, name: &dyn ToString| {
+            *ty = self.subst(ty);
+            match ty.is_open() {
+                true => Err(Error::UnknownType(name.to_string())),
+                false => Ok(()),
+            }
+        };
+        // A literal that kept its usual type or was given an unknown one has to fit in it too,
+        // which a negated one only has to once it's negative. There's nothing else to fill in.
+        if let Expr::Unary { op: Operator::Sub, rhs } = &mut expr.expr && let Expr::Value(Value::Number(x, suffix)) = &mut rhs.expr && suffix.as_ref().is_none_or(Type::is_open) {
+            if let Some(ty) = suffix {
+                *ty = self.subst(ty);
+            }
+            rhs.ty = self.subst(&rhs.ty);
+            expr.ty = self.subst(&expr.ty);
+            return check_range(-*x, &rhs.ty);
+        }
+        match &mut expr.expr {
+            Expr::Let {
+                name, ty: Some(ty), ..
+            } => fill(ty, name)?,
+            // A literal that was given an unknown type still has to fit in it
+            Expr::Value(Value::Number(x, Some(ty))) if ty.is_open() => {
+                *ty = self.subst(ty);
+                check_range(*x, ty)?;
+            }
+            Expr::Value(Value::Number(x, None)) => check_range(*x, &expr.ty)?,
+            Expr::Value(Value::Float(_, Some(ty))) => *ty = self.subst(ty),
+            Expr::Closure {
+                params,
+                ret,
+                captures,
+                ..
+            } => {
+                for (name, ty) in params {
+                    if let Some(ty) = ty {
+                        fill(ty, name)?;
+                    }
+                }
+                for capture in captures {
+                    fill(&mut capture.ty, &capture.name)?;
+                }
+                if let Some(ret) = ret {
+                    *ret = self.subst(ret);
+                }
+            }
+            _ => (),
+        }
+        let shown = expr.to_string();
+        fill(&mut expr.ty, &shown)?;
+        expr.expr
+            .children_mut()
+            .into_iter()
+            .try_for_each(|expr| self.fill(expr))
+    }
+}
 
 pub fn infer(prgm: Program<()>) -> Result<Program<Type>, AnyError> {
-    let funcs = prgm.0;
     let mut env = TypeEnv::new();
-    // Add the function types to the environment
-    for func in funcs.iter() {
+    let flat = env.modules.flatten(prgm)?;
+    declare_aliases(&flat, &mut env)?;
+    declare_lengths(&flat, &mut env);
+    // From here on each item goes by its path, and is checked from inside the module it's in
+    let (modules, items): (Vec<_>, Vec<_>) = flat
+        .into_iter()
+        .map(|(module, item)| {
+            env.module = module.clone();
+            Ok((module, resolve_item(item, &mut env)?))
+        })
+        .collect::<Result<Vec<_>, AnyError>>()?
+        .into_iter()
+        .unzip();
+    let prgm = Program(items);
+    declare_types(&prgm, &mut env)?;
+    declare_traits(&prgm, &mut env)?;
+    // The prelude is seen from every module, so its functions can't be declared in any of them
+    for func in prgm.funcs() {
+        let name = func.name.rsplit("::").next().unwrap_or(&func.name);
+        if Intrinsic::from_name(name).is_some() {
+            Err(Error::BuiltinRedeclaration(func.name.clone()))?
+        }
+    }
+    // Add the function types to the environment, where functions from impls go by their paths
+    for func in prgm.funcs().cloned().chain(prgm.methods()) {
         let ty = func.get_type();
+        env.check_type(&ty)
+            .context(format!("In function '{}'", func.name))?;
         // Ensure that the function names are unique
-        if let Some(ty1) = env.remove(func.name.as_str()) {
-            return Err(Error::Redeclaration(ty, ty1, func.name.clone()).into());
+        if let Some(ty1) = env.get(&func.name) {
+            return Err(Error::Redeclaration(ty, ty1.clone(), func.name.clone()).into());
+        }
+        env.insert(&func.name, ty, false);
+        if let FuncKind::Yields(frame) = &func.kind {
+            env.yielding.insert(func.name.clone(), frame.clone());
+        }
+    }
+    // Constants and statics are used by name just like functions
+    for def in prgm.globals() {
+        env.check_type(&def.ty)
+            .context(format!("In '{}'", def.name))?;
+        if env.get(&def.name).is_some() {
+            Err(Error::RedeclaredGlobal(def.name.clone()))?
         }
-        env.insert(func.name.clone(), ty);
+        env.insert(&def.name, def.ty.clone(), false);
+    }
+    for def in prgm.impls() {
+        check_impl(def, &mut env).context(format!("In impl for '{}'", def.ty))?;
     }
     // Ensure there's a main function
     if let Some(f) = env.get("main") {
@@ -34,38 +1030,588 @@ pub fn infer(prgm: Program<()>) -> Result<Program<Type>, AnyError> {
             .context("You need to declare a main() function")?
     }
     // Infer the types of the functions, one by one
-    let new_funcs: Vec<Function<Type>> = funcs
+    let new_items = prgm
+        .0
         .into_iter()
-        .map(|f| {
-            let name = f.name.clone();
-            infer_function(f, &mut env).context(format!("In function '{}'", name))
+        .zip(modules)
+        .map(|(item, module)| {
+            env.module = module;
+            match item {
+                Item::Func(f) => {
+                    let name = f.name.clone();
+                    infer_function(f, &None, &mut env)
+                        .map(Item::Func)
+                        .context(format!("In function '{}'", name))
+                }
+                Item::Impl(def) => infer_impl(def, &mut env).map(Item::Impl),
+                Item::Struct(def) => Ok(Item::Struct(def)),
+                Item::Enum(def) => Ok(Item::Enum(def)),
+                Item::Alias(def) => Ok(Item::Alias(def)),
+                Item::Trait(def) => Ok(Item::Trait(def)),
+                Item::Const(def) => {
+                    let name = def.name.clone();
+                    infer_global(def, &mut env)
+                        .map(Item::Const)
+                        .context(format!("In constant '{}'", name))
+                }
+                Item::Static(def) => {
+                    let name = def.name.clone();
+                    infer_global(def, &mut env)
+                        .map(Item::Static)
+                        .context(format!("In static '{}'", name))
+                }
+                Item::Mod(_) | Item::Use(_) => {
+                    unreachable!("modules are flattened before inference")
+                }
+            }
         })
-        .collect::<Result<Vec<Function<Type>>, AnyError>>()?;
-    Ok(Program(new_funcs))
+        .collect::<Result<Vec<Item<Type>>, AnyError>>()?;
+    let mut items = order_globals(new_items)?;
+    fold_globals(&mut items)?;
+    Ok(Program(items))
 }
 
-fn infer_function(func: Function<()>, env: &mut TypeEnv) -> Result<Function<Type>, AnyError> {
+// Aliases are filled in wherever a type is resolved, so they're known before any other item is.
+// An alias can stand for another one, as long as it doesn't end up standing for itself.
+fn declare_aliases(items: &[(String, Item<()>)], env: &mut TypeEnv) -> Result<(), AnyError> {
+    let mut aliases = HashMap::new();
+    for (module, item) in items {
+        if let Item::Alias(def) = item {
+            env.module = module.clone();
+            let name = join(module, &def.name);
+            let ty = env
+                .resolve(&def.ty)
+                .context(format!("In type '{}'", name))?;
+            aliases.insert(name, ty);
+        }
+    }
+    for name in aliases.keys() {
+        if refers_to(&aliases, &aliases[name], name, &mut HashSet::new()) {
+            Err(Error::RecursiveType(name.clone()))?
+        }
+    }
+    env.aliases = aliases;
+    env.module = String::new();
+    Ok(())
+}
+
+// Constants can be the lengths of array types, so the integer ones built from literals and each
+// other are worked out before any other type is resolved. The rest are left to be reported once
+// they're inferred along with everything else.
+fn declare_lengths(items: &[(String, Item<()>)], env: &mut TypeEnv) {
+    let defs = items
+        .iter()
+        .filter_map(|(module, item)| match item {
+            Item::Const(def) => Some((module, def)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let mut scratch = env.clone();
+    let mut known = HashMap::new();
+    // A constant can use the ones declared after it, so this goes on until no more are found
+    loop {
+        let before = known.len();
+        for (module, def) in &defs {
+            let path = join(module, &def.name);
+            if known.contains_key(&path) {
+                continue;
+            }
+            scratch.module = module.to_string();
+            let Ok(ty) = scratch.resolve(&def.ty) else {
+                continue;
+            };
+            if !ty.is_integer() {
+                continue;
+            }
+            let def = Global {
+                ty: ty.clone(),
+                ..(*def).clone()
+            };
+            if let Ok(global) = infer_global(def, &mut scratch)
+                && let Some(value) = fold_length(global.value, &known)
+            {
+                scratch.insert(&path, ty, false);
+                known.insert(path, value);
+            }
+        }
+        if known.len() == before {
+            break;
+        }
+    }
+    let lengths = known
+        .into_iter()
+        .filter_map(|(path, value)| match value.expr {
+            Expr::Value(Value::Number(x, _)) => Some((path, x)),
+            _ => None,
+        })
+        .collect();
+    env.lengths = Some(lengths);
+}
+
+// Whether a type names the alias, directly or through other aliases
+fn refers_to(
+    aliases: &HashMap<String, Type>,
+    ty: &Type,
+    name: &str,
+    seen: &mut HashSet<String>,
+) -> bool {
+    ty.names().into_iter().any(|other| {
+        other == name
+            || (seen.insert(other.to_owned())
+                && aliases
+                    .get(other)
+                    .is_some_and(|ty| refers_to(aliases, ty, name, seen)))
+    })
+}
+
+// Structs, enums and aliases share a namespace, since they're all used as types by name
+fn declare_types(prgm: &Program<()>, env: &mut TypeEnv) -> Result<(), AnyError> {
+    let mut names = HashSet::new();
+    for def in prgm.structs() {
+        if !names.insert(&def.name) {
+            Err(Error::RedeclaredType(def.name.clone()))?
+        }
+        let mut fields = HashSet::new();
+        if let Some((field, _)) = def.fields.iter().find(|(field, _)| !fields.insert(field)) {
+            Err(Error::DuplicateField(def.name.clone(), field.clone()))?
+        }
+        env.structs.insert(def.name.clone(), def.fields.clone());
+    }
+    for def in prgm.enums() {
+        if !names.insert(&def.name) {
+            Err(Error::RedeclaredType(def.name.clone()))?
+        }
+        let mut variants = HashSet::new();
+        if let Some((variant, _)) = def.variants.iter().find(|(name, _)| !variants.insert(name)) {
+            Err(Error::DuplicateVariant(def.name.clone(), variant.clone()))?
+        }
+        env.enums.insert(def.name.clone(), def.variants.clone());
+    }
+    let mut aliases = HashSet::new();
+    for def in prgm.aliases() {
+        if names.contains(&def.name) || !aliases.insert(&def.name) {
+            Err(Error::RedeclaredType(def.name.clone()))?
+        }
+        env.check_type(&def.ty)
+            .context(format!("In type '{}'", def.name))?;
+    }
+    // The types they hold can only be checked once every name is known
+    for name in names {
+        for ty in env.inline_types(name) {
+            env.check_type(ty).context(format!("In type '{}'", name))?;
+            if env.contains(ty, name, &mut HashSet::new()) {
+                Err(Error::RecursiveType(name.clone()))?
+            }
+        }
+    }
+    Ok(())
+}
+
+fn declare_traits(prgm: &Program<()>, env: &mut TypeEnv) -> Result<(), AnyError> {
+    for def in prgm.traits() {
+        if env.traits.contains_key(&def.name) {
+            Err(Error::RedeclaredTrait(def.name.clone()))?
+        }
+        let mut methods = HashSet::new();
+        for sig in &def.methods {
+            if !methods.insert(&sig.name) {
+                Err(Error::DuplicateMethod(def.name.clone(), sig.name.clone()))?
+            }
+            // `Self` could be any type, just like a type parameter
+            env.check_type(&sig.get_type(&Type::Param("Self".into())))
+                .context(format!("In trait '{}'", def.name))?;
+        }
+        env.traits.insert(def.name.clone(), def.methods.clone());
+    }
+    Ok(())
+}
+
+// Names an item by its path, and turns the names in the types it's declared with into the types
+// they stand for: type parameters, `Self`, and the paths of structs and enums. That way the types
+// of functions can be compared.
+fn resolve_item(item: Item<()>, env: &mut TypeEnv) -> Result<Item<()>, AnyError> {
+    let resolve_all = |tys: Vec<Type>, env: &TypeEnv| {
+        tys.iter()
+            .map(|ty| env.resolve(ty))
+            .collect::<Result<Vec<_>, Error>>()
+    };
+    let resolve_params = |params: Vec<(String, Type)>, env: &TypeEnv| {
+        params
+            .into_iter()
+            .map(|(name, ty)| Ok((name, env.resolve(&ty)?)))
+            .collect::<Result<Vec<_>, Error>>()
+    };
+    match item {
+        Item::Func(f) => {
+            let name = join(&env.module, &f.name);
+            let f = Function {
+                name: name.clone(),
+                ..f
+            };
+            f.resolve(env)
+                .map(Item::Func)
+                .context(format!("In function '{}'", name))
+        }
+        Item::Struct(def) => {
+            let name = join(&env.module, &def.name);
+            let fields = resolve_params(def.fields, env).context(format!("In type '{}'", name))?;
+            Ok(Item::Struct(Struct {
+                name,
+                fields,
+                ..def
+            }))
+        }
+        Item::Enum(def) => {
+            let name = join(&env.module, &def.name);
+            let variants = def
+                .variants
+                .into_iter()
+                .map(|(variant, tys)| Ok((variant, resolve_all(tys, env)?)))
+                .collect::<Result<Vec<_>, Error>>()
+                .context(format!("In type '{}'", name))?;
+            Ok(Item::Enum(Enum {
+                name,
+                variants,
+                ..def
+            }))
+        }
+        Item::Trait(def) => {
+            let name = join(&env.module, &def.name);
+            let methods = def
+                .methods
+                .into_iter()
+                .map(|sig| {
+                    Ok(Signature {
+                        args: resolve_params(sig.args, env)?,
+                        ret: env.resolve(&sig.ret)?,
+                        ..sig
+                    })
+                })
+                .collect::<Result<Vec<_>, Error>>()
+                .context(format!("In trait '{}'", name))?;
+            Ok(Item::Trait(Trait {
+                name,
+                methods,
+                ..def
+            }))
+        }
+        Item::Alias(def) => {
+            let name = join(&env.module, &def.name);
+            let ty = env
+                .resolve(&def.ty)
+                .context(format!("In type '{}'", name))?;
+            Ok(Item::Alias(Alias { name, ty, ..def }))
+        }
+        Item::Const(def) => {
+            let name = join(&env.module, &def.name);
+            let ty = env
+                .resolve(&def.ty)
+                .context(format!("In constant '{}'", name))?;
+            Ok(Item::Const(Global { name, ty, ..def }))
+        }
+        Item::Static(def) => {
+            let name = join(&env.module, &def.name);
+            let ty = env
+                .resolve(&def.ty)
+                .context(format!("In static '{}'", name))?;
+            Ok(Item::Static(Global { name, ty, ..def }))
+        }
+        Item::Impl(Impl {
+            trait_,
+            ty,
+            methods,
+        }) => {
+            // Methods go by the type an alias stands for, so they're found under either name
+            let ty = env.resolve(&ty)?.unaliased();
+            let trait_ = trait_.map(|trait_| env.path(&trait_)).transpose()?;
+            let def = Impl {
+                trait_,
+                ty,
+                methods: vec![],
+            };
+            // The methods of a trait can be used wherever the trait can
+            for method in &methods {
+                let public = method.public || def.trait_.is_some();
+                env.modules
+                    .declare(&def.path(&method.name), &env.module.clone(), public);
+            }
+            env.this = Some(def.ty.clone());
+            let methods = methods
+                .into_iter()
+                .map(|f| {
+                    let path = def.path(&f.name);
+                    f.resolve(env).context(format!("In function '{}'", path))
+                })
+                .collect::<Result<Vec<_>, AnyError>>();
+            env.this = None;
+            Ok(Item::Impl(Impl {
+                methods: methods?,
+                ..def
+            }))
+        }
+        item => Ok(item),
+    }
+}
+
+// Ensures an impl of a trait has exactly the methods the trait requires
+fn check_impl(def: &Impl<()>, env: &mut TypeEnv) -> Result<(), AnyError> {
+    env.check_type(&def.ty)?;
+    let Some(trait_) = &def.trait_ else {
+        return Ok(());
+    };
+    let sigs = env
+        .traits
+        .get(trait_)
+        .ok_or_else(|| Error::TraitNotFound(trait_.clone()))?;
+    if env.implements(&def.ty, trait_) {
+        Err(Error::DuplicateImpl(trait_.clone(), def.ty.clone()))?
+    }
+    for sig in sigs {
+        let method = def
+            .methods
+            .iter()
+            .find(|method| method.name == sig.name)
+            .ok_or_else(|| Error::MissingMethod(trait_.clone(), sig.name.clone()))?;
+        method
+            .get_type()
+            .expect(&sig.get_type(&def.ty))
+            .context(format!(
+                "Method '{}' doesn't match its signature in trait '{}'",
+                sig.name, trait_
+            ))?;
+    }
+    if let Some(method) = def
+        .methods
+        .iter()
+        .find(|method| sigs.iter().all(|sig| sig.name != method.name))
+    {
+        Err(Error::NotInTrait(trait_.clone(), method.name.clone()))?
+    }
+    env.impls.push((trait_.clone(), def.ty.clone()));
+    Ok(())
+}
+
+fn infer_impl(mut def: Impl<()>, env: &mut TypeEnv) -> Result<Impl<Type>, AnyError> {
+    let this = Some(def.ty.clone());
+    let methods = std::mem::take(&mut def.methods)
+        .into_iter()
+        .map(|method| {
+            let path = def.path(&method.name);
+            infer_function(method, &this, env).context(format!("In function '{}'", path))
+        })
+        .collect::<Result<Vec<_>, AnyError>>()?;
+    Ok(Impl {
+        trait_: def.trait_,
+        ty: def.ty,
+        methods,
+    })
+}
+
+fn infer_function(
+    func: Function<()>,
+    this: &Option<Type>,
+    env: &mut TypeEnv,
+) -> Result<Function<Type>, AnyError> {
+    let bounds = func.generics.iter().flat_map(|param| &param.bounds);
+    for bound in bounds {
+        if !env.traits.contains_key(bound) {
+            Err(Error::TraitNotFound(bound.clone()))?
+        }
+    }
     let mut new_env = env.clone();
+    new_env.generics = func.generics.clone();
+    new_env.this = this.clone();
+    new_env.enter();
     for (name, ty) in &func.args {
-        new_env.insert(name.into(), ty.clone());
+        new_env.insert(name, ty.clone(), false);
     }
-    let new_body = infer_seq(func.body, &mut new_env)?;
-    let default = Type::unit();
-    let ret_ty = new_body.0.last().map_or(&default, |f| &f.ty);
-    ret_ty
-        .expect(&func.ret)
+    new_env.ret = match &func.kind {
+        FuncKind::Coro(_) => Returns::Any,
+        _ => Returns::Declared(func.ret.clone()),
+    };
+    new_env.frame = match &func.kind {
+        FuncKind::Func => None,
+        FuncKind::Yields(frame) => Some(frame.clone()),
+        FuncKind::Coro(resume) => {
+            for (name, ty) in resume {
+                new_env.insert(name, ty.clone(), false);
+            }
+            Some(Type::Frame {
+                resume: resume.iter().map(|(_, ty)| ty).cloned().collect(),
+                yields: Box::new(func.ret.clone()),
+            })
+        }
+    };
+    let mut new_body = infer_seq(func.body, &mut new_env)?;
+    new_env
+        .finish(&mut new_body)
         .context(format!("Return type mismatch for function {}", func.name))?;
+    new_env.complete(&mut new_body.0)?;
     Ok(Function {
         name: func.name,
+        public: func.public,
+        generics: func.generics,
         args: func.args,
         ret: func.ret,
         body: new_body,
+        kind: func.kind,
+    })
+}
+
+// Checks the value of a constant or static against its type. It's evaluated before the
+// program runs, so it can only be built from literals, operators and other items.
+fn infer_global(def: Global<()>, env: &mut TypeEnv) -> Result<Global<Type>, AnyError> {
+    let mut new_env = env.clone();
+    new_env.enter();
+    let mut value = infer_expr(def.value, &mut new_env)?;
+    new_env
+        .expect(&mut value, &def.ty)
+        .context(format!("Type mismatch for '{}'", def.name))?;
+    new_env.complete(std::slice::from_mut(&mut value))?;
+    check_const(&mut value)?;
+    Ok(Global {
+        name: def.name,
+        public: def.public,
+        ty: def.ty,
+        value,
     })
 }
 
+fn check_const(expr: &mut Expression<Type>) -> Result<(), Error> {
+    let constant = match &expr.expr {
+        Expr::Unary { op, .. } => !matches!(op, Operator::Ref | Operator::RefMut | Operator::Mul),
+        Expr::Binary { op, .. } => *op != Operator::Assign,
+        Expr::Value(_)
+        | Expr::Reference(_)
+        | Expr::Instance { .. }
+        | Expr::Tuple(_)
+        | Expr::Array(_)
+        | Expr::Struct { .. }
+        | Expr::Variant { .. }
+        | Expr::Field { .. } => true,
+        _ => false,
+    };
+    if !constant {
+        Err(Error::NotConstant(expr.to_string()))?
+    }
+    expr.expr
+        .children_mut()
+        .into_iter()
+        .try_for_each(check_const)
+}
+
+// Puts the constants and statics first, each after the ones its value uses, so they can be
+// evaluated in order before the program runs
+fn order_globals(items: Vec<Item<Type>>) -> Result<Vec<Item<Type>>, Error> {
+    let (mut globals, rest): (Vec<_>, Vec<_>) = items
+        .into_iter()
+        .partition(|item| matches!(item, Item::Const(_) | Item::Static(_)));
+    let mut defs = globals
+        .iter_mut()
+        .filter_map(|item| match item {
+            Item::Const(def) | Item::Static(def) => Some(def),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let names = defs.iter().map(|def| def.name.clone()).collect::<Vec<_>>();
+    let uses = defs
+        .iter_mut()
+        .map(|def| {
+            let mut uses = vec![];
+            references(&mut def.value, &names, &mut uses);
+            uses
+        })
+        .collect::<Vec<_>>();
+    let mut order = vec![];
+    for index in 0..names.len() {
+        visit(index, &names, &uses, &mut vec![], &mut order)?;
+    }
+    let mut globals = globals.into_iter().map(Some).collect::<Vec<_>>();
+    let ordered = order.into_iter().filter_map(|index| globals[index].take());
+    Ok(ordered.chain(rest).collect())
+}
+
+// Adds a global to the order after the ones it uses, which can't lead back to itself
+fn visit(
+    index: usize,
+    names: &[String],
+    uses: &[Vec<usize>],
+    visiting: &mut Vec<usize>,
+    order: &mut Vec<usize>,
+) -> Result<(), Error> {
+    if order.contains(&index) {
+        return Ok(());
+    }
+    if visiting.contains(&index) {
+        Err(Error::RecursiveGlobal(names[index].clone()))?
+    }
+    visiting.push(index);
+    for &used in &uses[index] {
+        visit(used, names, uses, visiting, order)?;
+    }
+    visiting.pop();
+    order.push(index);
+    Ok(())
+}
+
+// Collects which of the names an expression refers to
+fn references(expr: &mut Expression<Type>, names: &[String], found: &mut Vec<usize>) {
+    if let Expr::Reference(name) = &expr.expr {
+        found.extend(names.iter().position(|other| other == name));
+    }
+    for child in expr.expr.children_mut() {
+        references(child, names, found);
+    }
+}
+
+// The variable a place is part of, unless it's behind a reference
+fn root(place: &Expression<Type>) -> Option<&str> {
+    match &place.expr {
+        Expr::Reference(name) => Some(name),
+        Expr::Unary {
+            op: Operator::TupleIndex(_),
+            rhs,
+        } => root(rhs),
+        Expr::Binary {
+            lhs,
+            op: Operator::ArrayIndex,
+            ..
+        } => root(lhs),
+        Expr::Field { expr, .. } => root(expr),
+        _ => None,
+    }
+}
+
+impl Returns {
+    fn check(&mut self, ty: Type) -> Result<(), AnyError> {
+        match self {
+            Returns::Any => (),
+            Returns::Declared(ret) => ty.expect(ret)?,
+            Returns::Inferred(None) => *self = Returns::Inferred(Some(ty)),
+            Returns::Inferred(Some(ret)) => {
+                *ret = ret
+                    .join(&ty)
+                    .ok_or_else(|| Error::UnexpectedType(ret.clone(), ty))?
+            }
+        }
+        Ok(())
+    }
+
+    // Gives a returned literal the declared return type
+    fn settle(&self, value: &mut Expression<Type>) -> Result<(), Error> {
+        match self {
+            Returns::Declared(ret) => settle(value, ret),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Each sequence is a block, and its bindings are dropped at the end of it
 fn infer_seq(seq: Sequence<()>, env: &mut TypeEnv) -> Result<Sequence<Type>, AnyError> {
-    let new_exprs = infer_exprs(seq.0, env)?;
-    Ok(Sequence(new_exprs))
+    env.enter();
+    let new_exprs = infer_exprs(seq.0, env);
+    env.exit();
+    Ok(Sequence(new_exprs?))
 }
 
 fn infer_exprs(
@@ -79,7 +1625,7 @@ fn infer_exprs(
 }
 
 fn infer_unary(op: Operator, rhs: &Expression<Type>) -> Result<Type, AnyError> {
-    let ty = &rhs.ty;
+    let ty = rhs.ty.unalias();
     match op {
         Operator::Not => {
             ty.expect(&Type::Bool)
@@ -87,75 +1633,165 @@ fn infer_unary(op: Operator, rhs: &Expression<Type>) -> Result<Type, AnyError> {
             Ok(Type::Bool)
         }
         Operator::Sub => {
-            ty.expect(&Type::Signed(Size::ThirtyTwo))
-                .context("Unary negation on non-integer")?;
-            Ok(Type::Signed(Size::ThirtyTwo))
-        }
-        Operator::TupleIndex(i) => ty.map_mut(|ty| {
-            if let Type::Tuple(tys) = ty {
-                if i >= tys.len() {
-                    Err(Error::InvalidTupleIndex(ty.clone()))?
-                } else {
-                    Ok(tys[i].clone())
-                }
+            if let Type::Unsigned(_) = ty {
+                Err(Error::UnsignedNegation(ty.clone()))?
+            }
+            ty.expect_numeric()
+                .context("Unary negation on non-number")?;
+            Ok(ty.clone())
+        }
+        Operator::TupleIndex(i) => {
+            if let Type::Tuple(tys) = ty && i < tys.len() {
+                Ok(tys[i].clone())
             } else {
                 Err(Error::InvalidTupleIndex(ty.clone()))?
             }
-        }),
-        Operator::Ref => ty.map_mut(|ty| Ok(Type::Reference(Box::new(ty.clone())))),
-        Operator::Mul => ty.map_mut(|ty| {
-            if let Type::Reference(ty) = ty {
+        }
+        Operator::Ref => Ok(Type::Reference(Box::new(ty.clone()), false)),
+        Operator::RefMut => Ok(Type::Reference(Box::new(ty.clone()), true)),
+        Operator::Mul => {
+            if let Type::Reference(ty, _) = ty {
                 Ok(*ty.clone())
             } else {
                 Err(Error::InvalidDereference(ty.clone()))?
             }
-        }),
+        }
         _ => Err(anyhow!("Invalid unary operator {:?}", op)),
     }
 }
 
-fn infer_binary(
+// The type of a binary operator's result, once the types of its operands have been found from each other
+fn infer_operands(
     op: Operator,
     lhs: &mut Expression<Type>,
-    rhs: &Expression<Type>,
+    rhs: &mut Expression<Type>,
+    env: &mut TypeEnv,
 ) -> Result<Type, AnyError> {
-    let lhs_ty = &lhs.ty;
-    let rhs_ty = &rhs.ty;
+    // An integer literal takes the type of the operand on the other side, except for a
+    // shift, whose amount can be of any integer type
+    if !op.is_shift() {
+        if !is_literal(lhs) {
+            env.settle(rhs, &lhs.ty)?;
+        } else if !is_literal(rhs) {
+            env.settle(lhs, &rhs.ty)?;
+        }
+    }
+    // Both sides of these have the same type, so either can tell what the other is
+    if op == Operator::Assign
+        || op == Operator::Range
+        || op.is_arith()
+        || op.is_bitwise()
+        || op.is_comparison()
+    {
+        env.unify(&lhs.ty, &rhs.ty);
+        lhs.ty = env.subst(&lhs.ty);
+        rhs.ty = env.subst(&rhs.ty);
+    }
+    // An index is used right away, so a number literal in it gets its usual type
+    if op == Operator::ArrayIndex {
+        rhs.ty = env.known(&rhs.ty);
+    }
+    let shown = format!("({} {} {})", lhs, op, rhs);
+    // An operator on a type that isn't known yet is checked once it is, by the end of
+    // the function, and meanwhile gives back what it would for any type
+    let checked = op.is_arith()
+        || op.is_bitwise()
+        || op.is_shift()
+        || op.is_comparison()
+        || op == Operator::Range;
+    if matches!(lhs.ty, Type::Var(_)) && checked {
+        let pending = (op, lhs.ty.clone(), rhs.ty.clone(), shown);
+        env.pending.push(pending);
+        match op {
+            Operator::Range => Ok(Type::Range(Box::new(lhs.ty.clone()))),
+            op if op.is_comparison() => Ok(Type::Bool),
+            _ => Ok(lhs.ty.clone()),
+        }
+    } else {
+        infer_binary(op, &lhs.ty, &rhs.ty).context(format!("On binary expression: {}", shown))
+    }
+}
+
+fn infer_binary(op: Operator, lhs_ty: &Type, rhs_ty: &Type) -> Result<Type, AnyError> {
+    let (lhs_ty, rhs_ty) = (lhs_ty.unalias(), rhs_ty.unalias());
     match op {
         Operator::Assign => {
-            if let Type::Mutable(ty) = lhs_ty {
-                rhs_ty
-                    .expect(ty)
-                    .context("Invalid operand types for assignment")?;
-                lhs.ty = *ty.clone();
-                Ok(Type::unit())
-            } else {
-                Err(Error::NotMutable(lhs_ty.clone()))?
-            }
+            rhs_ty
+                .expect(lhs_ty)
+                .context("Invalid operand types for assignment")?;
+            Ok(Type::unit())
         }
-        Operator::ArrayIndex => lhs_ty.map_mut(|ty| {
-            if let Type::Array(ty, _) = ty {
+        Operator::ArrayIndex => {
+            if let Type::Array(ty, _) = lhs_ty {
                 rhs_ty
-                    .expect(&Type::Signed(Size::ThirtyTwo))
+                    .expect_integer()
                     .context("Invalid index type for array")?;
                 Ok(*ty.clone())
+            } else if *lhs_ty == Type::String {
+                // A string gives a character for an index, and a substring for a range
+                match rhs_ty {
+                    Type::Range(_) => Ok(Type::String),
+                    _ => {
+                        rhs_ty
+                            .expect_integer()
+                            .context("Invalid index type for string")?;
+                        Ok(Type::Char)
+                    }
+                }
             } else {
-                Err(Error::InvalidArrayIndex(ty.clone()))?
+                Err(Error::InvalidArrayIndex(lhs_ty.clone()))?
             }
-        }),
+        }
+        Operator::Range => {
+            lhs_ty.expect_integer().context("Invalid start for range")?;
+            rhs_ty
+                .expect(lhs_ty)
+                .context("Range bounds must have the same type")?;
+            Ok(Type::Range(Box::new(lhs_ty.clone())))
+        }
+        Operator::Add if *lhs_ty == Type::String => {
+            rhs_ty
+                .expect(lhs_ty)
+                .context("Invalid operand types for concatenation")?;
+            Ok(Type::String)
+        }
         op if op.is_arith() => {
             lhs_ty
                 .expect(rhs_ty)
                 .context("Invalid operand types for arithmetic operator")?;
             lhs_ty
-                .expect(&Type::Signed(Size::ThirtyTwo))
+                .expect_numeric()
                 .context("Invalid operand type for arithmetic operator")?;
-            Ok(Type::Signed(Size::ThirtyTwo))
+            Ok(lhs_ty.clone())
+        }
+        op if op.is_shift() => {
+            lhs_ty
+                .expect_integer()
+                .context("Invalid operand type for shift operator")?;
+            rhs_ty.expect_integer().context("Invalid shift amount")?;
+            Ok(lhs_ty.clone())
+        }
+        op if op.is_bitwise() => {
+            lhs_ty
+                .expect(rhs_ty)
+                .context("Invalid operand types for bitwise operator")?;
+            if *lhs_ty != Type::Bool {
+                lhs_ty
+                    .expect_integer()
+                    .context("Invalid operand type for bitwise operator")?;
+            }
+            Ok(lhs_ty.clone())
         }
         op if op.is_comparison() => {
             lhs_ty
-                .expect(&Type::Signed(Size::ThirtyTwo))
-                .context("Invalid operand type for comparison operator")?;
+                .expect(rhs_ty)
+                .context("Invalid operand types for comparison operator")?;
+            // Characters are ordered by their code points, and strings by their characters
+            if !matches!(lhs_ty, Type::Char | Type::String) {
+                lhs_ty
+                    .expect_numeric()
+                    .context("Invalid operand type for comparison operator")?;
+            }
             Ok(Type::Bool)
         }
         op if op.is_logical() => {
@@ -172,7 +1808,39 @@ fn infer_expr(expr: Expression<()>, env: &mut TypeEnv) -> Result<Expression<Type
     let mut ty = Type::unit();
     let expr = match expr.expr {
         Expr::Unary { op, rhs } => {
-            let rhs = infer_expr(*rhs, env)?;
+            let rhs = match *rhs {
+                // A negated literal only has to fit in its type once it's negative, like `-128i8`
+                Expression {
+                    expr: Expr::Value(Value::Number(x, Some(suffix))),
+                    ..
+                } if op == Operator::Sub => {
+                    check_range(-x, &suffix)?;
+                    Expression {
+                        expr: Expr::Value(Value::Number(x, Some(suffix.clone()))),
+                        ty: suffix,
+                    }
+                }
+                rhs => infer_expr(rhs, env)?,
+            };
+            // The fields of a tuple struct are read by their positions, just like a tuple's
+            match (op, rhs.ty.name()) {
+                (Operator::TupleIndex(i), Some(name)) if env.is_tuple_struct(name) => {
+                    let field = i.to_string();
+                    let ty = env.field(&rhs.ty, &field)?;
+                    let expr = Expr::Field {
+                        expr: Box::new(rhs),
+                        field,
+                    };
+                    return Ok(Expression { expr, ty });
+                }
+                _ => (),
+            }
+            if op == Operator::RefMut {
+                if !env.is_mutable(&rhs) {
+                    Err(Error::NotMutable(rhs.to_string()))?
+                }
+                env.capture_mut(&rhs)?;
+            }
             ty = infer_unary(op, &rhs).context(format!("On unary expression: ({} {})", op, rhs))?;
             Expr::Unary {
                 op,
@@ -181,19 +1849,43 @@ fn infer_expr(expr: Expression<()>, env: &mut TypeEnv) -> Result<Expression<Type
         }
         Expr::Binary { op, lhs, rhs } => {
             let mut lhs = infer_expr(*lhs, env)?;
-            let rhs = infer_expr(*rhs, env)?;
-            ty = infer_binary(op, &mut lhs, &rhs)
-                .context(format!("On binary expression: ({} {} {})", lhs, op, rhs))?;
+            let mut rhs = infer_expr(*rhs, env)?;
+            if op == Operator::Assign {
+                env.assign(&lhs)?;
+            }
+            ty = infer_operands(op, &mut lhs, &mut rhs, env)?;
             Expr::Binary {
                 op,
                 lhs: Box::new(lhs),
                 rhs: Box::new(rhs),
             }
         }
+        Expr::CompoundAssign { lhs, op, rhs } => {
+            let mut lhs = infer_expr(*lhs, env)?;
+            let mut rhs = infer_expr(*rhs, env)?;
+            env.assign(&lhs)?;
+            // The result is assigned back to the place, so it has to be of the same type
+            let result = infer_operands(op, &mut lhs, &mut rhs, env)?;
+            infer_binary(Operator::Assign, &lhs.ty, &result)
+                .context(format!("On compound assignment: {} {}= {}", lhs, op, rhs))?;
+            Expr::CompoundAssign {
+                lhs: Box::new(lhs),
+                op,
+                rhs: Box::new(rhs),
+            }
+        }
         Expr::Value(val) => match val {
-            Value::Number(x) => {
-                ty = Type::Signed(Size::ThirtyTwo);
-                Expr::Value(Value::Number(x))
+            // Without a suffix, it's an i32 until the context it's used in says otherwise
+            Value::Number(x, suffix) => {
+                if let Some(suffix) = &suffix {
+                    check_range(x, suffix)?;
+                }
+                ty = suffix.clone().unwrap_or(Type::Signed(Size::ThirtyTwo));
+                Expr::Value(Value::Number(x, suffix))
+            }
+            Value::Float(x, suffix) => {
+                ty = suffix.clone().unwrap_or(Type::Float(Size::SixtyFour));
+                Expr::Value(Value::Float(x, suffix))
             }
             Value::Bool(b) => {
                 ty = Type::Bool;
@@ -204,7 +1896,7 @@ fn infer_expr(expr: Expression<()>, env: &mut TypeEnv) -> Result<Expression<Type
                 Expr::Value(Value::Char(c))
             }
             Value::String(s) => {
-                ty = Type::Array(Box::new(Type::Char), s.len());
+                ty = Type::String;
                 Expr::Value(Value::String(s))
             }
         },
@@ -214,53 +1906,300 @@ fn infer_expr(expr: Expression<()>, env: &mut TypeEnv) -> Result<Expression<Type
             Expr::Tuple(exprs)
         }
         Expr::Array(exprs) => {
-            let exprs = infer_exprs(exprs, env)?;
-            ty = exprs
-                .first()
-                .map(|e| e.ty.clone())
-                .ok_or_else(|| anyhow!("Empty array"))?; // TODO: Make this an unknown type
-            for e in exprs.iter() {
-                e.ty.expect(&ty).context("Array element type mismatch")?;
-            }
-            ty = Type::Array(Box::new(ty), exprs.len());
+            let mut exprs = infer_exprs(exprs, env)?;
+            // Literals take the type of the first element that isn't one, or else stay open
+            let typed = exprs.iter().find(|e| !is_literal(e)).map(|e| e.ty.clone());
+            // An empty array is an array of some type and length, which comes from how it's used
+            let Some(elem) = typed.clone().or(exprs.first().map(|e| e.ty.clone())) else {
+                ty = env.fresh(Unknown::Array);
+                return Ok(Expression {
+                    expr: Expr::Array(exprs),
+                    ty,
+                });
+            };
+            for e in exprs.iter_mut() {
+                if let Some(typed) = &typed {
+                    settle(e, typed)?;
+                }
+                env.unify(&elem, &e.ty);
+                e.ty = env.subst(&e.ty);
+                e.ty.expect(&env.subst(&elem))
+                    .context("Array element type mismatch")?;
+            }
+            ty = Type::Array(Box::new(env.subst(&elem)), exprs.len());
             Expr::Array(exprs)
         }
+        Expr::Struct { name, fields } => {
+            let name = env.path(&name)?;
+            let Some(decl) = env.structs.get(&name).cloned() else {
+                Err(Error::TypeNotFound(name))?
+            };
+            let mut fields = fields
+                .into_iter()
+                .map(|(field, value)| Ok((field, infer_expr(value, env)?)))
+                .collect::<Result<Vec<_>, AnyError>>()?;
+            ty = Type::Named(name.clone());
+            let mut names = HashSet::new();
+            for (field, value) in &mut fields {
+                if !names.insert(field.clone()) {
+                    Err(Error::DuplicateField(name.clone(), field.clone()))?
+                }
+                let field_ty = env.field(&ty, field)?;
+                env.expect(value, &field_ty)
+                    .context(format!("In field '{}' of struct '{}'", field, name))?;
+            }
+            if let Some((field, _)) = decl.iter().find(|(field, _)| !names.contains(field)) {
+                Err(Error::MissingField(name.clone(), field.clone()))?
+            }
+            Expr::Struct { name, fields }
+        }
+        // The builtin enums hold values of any type, so their type comes from what they hold
+        Expr::Variant {
+            enum_,
+            variant,
+            args,
+        } if is_builtin_enum(&enum_) => {
+            let args = args.map(|args| infer_exprs(args, env)).transpose()?;
+            let tys = args
+                .iter()
+                .flatten()
+                .map(|arg| arg.ty.clone())
+                .collect::<Vec<_>>();
+            let Some(variant_ty) = Type::of_variant(&enum_, &variant, &tys) else {
+                Err(Error::VariantNotFound(enum_, variant))?
+            };
+            let held = builtin_variant(&variant_ty, &variant).unwrap_or_default();
+            if tys.len() != held.len() {
+                let path = format!("{}::{}", enum_, variant);
+                Err(Error::VariantArity(path, held.len(), tys.len()))?
+            }
+            ty = variant_ty;
+            Expr::Variant {
+                enum_,
+                variant,
+                args,
+            }
+        }
+        Expr::Variant {
+            enum_,
+            variant,
+            args,
+        } => {
+            // Functions from impls and modules are called by their paths, which look just like variants
+            let (owner, enum_) = match (&env.this, enum_.as_str()) {
+                (Some(this), "Self") => (this.clone(), enum_),
+                _ => {
+                    let enum_ = env.path(&enum_)?;
+                    (Type::Named(enum_.clone()), enum_)
+                }
+            };
+            // `crate::` and `super::` can lead to the root module, whose items have no prefix
+            let mut path = match enum_.is_empty() {
+                true => variant.clone(),
+                false => method_path(&owner, &variant),
+            };
+            if !env.enums.contains_key(&enum_) && env.is_tuple_struct(&path) {
+                return infer_expr(tuple_struct(&path, args.unwrap_or_default()), env);
+            }
+            // A function from an impl of a trait can be called by the type's path when it's the only one
+            let found = env.enums.contains_key(&enum_) || env.scopes[0].contains_key(&path);
+            if !found && let Some((_, trait_path)) = env.trait_method(&owner, &variant)? {
+                path = trait_path;
+            }
+            if !env.enums.contains_key(&enum_) && env.scopes[0].contains_key(&path) {
+                env.modules.check(&env.module, &path)?;
+                // The path is already resolved, so it's looked up from the root
+                let func = Expr::Reference(format!("crate::{}", path)).into();
+                // Without parentheses, the path is the function or global itself
+                let Some(args) = args else {
+                    return infer_expr(func, env);
+                };
+                let func = Box::new(func);
+                return infer_expr(Expr::Call { func, args }.into(), env);
+            }
+            if !env.enums.contains_key(&enum_) {
+                Err(Error::FunctionNotFound(path))?
+            }
+            let tys = env.variant(&enum_, &variant)?;
+            let mut args = args.map(|args| infer_exprs(args, env)).transpose()?;
+            let given = args.as_ref().map_or(0, Vec::len);
+            if given != tys.len() {
+                let path = format!("{}::{}", enum_, variant);
+                Err(Error::VariantArity(path, tys.len(), given))?
+            }
+            for (arg, ty) in args.iter_mut().flatten().zip(&tys) {
+                env.expect(arg, ty).context("Variant value type mismatch")?;
+            }
+            ty = Type::Named(enum_.clone());
+            Expr::Variant {
+                enum_,
+                variant,
+                args,
+            }
+        }
+        Expr::Match { expr, arms } => {
+            let mut expr = infer_expr(*expr, env)?;
+            let arms = arms
+                .into_iter()
+                .map(|arm| {
+                    // The names bound by the pattern are only visible in its arm
+                    env.enter();
+                    let arm = infer_arm(arm, &expr.ty, env);
+                    env.exit();
+                    arm
+                })
+                .collect::<Result<Vec<_>, AnyError>>()?;
+            // Arms that diverge fit with any other arm, and a match without arms never finishes
+            ty = Type::Never;
+            for arm in arms.iter() {
+                let arm_ty = arm.body.0.last().map_or(Type::unit(), |e| e.ty.clone());
+                ty = env
+                    .join(&ty, &arm_ty)
+                    .context("Match arms must have the same type")?;
+            }
+            // The patterns can tell what's being matched on
+            expr.ty = env.subst(&expr.ty);
+            // A guard might fail, so guarded arms don't count towards covering every value
+            let matrix = arms
+                .iter()
+                .filter(|arm| arm.guard.is_none())
+                .map(|arm| vec![arm.pattern.clone()])
+                .collect::<Vec<_>>();
+            let wildcard = [Pattern::Wildcard];
+            if env.useful(&matrix, &wildcard, std::slice::from_ref(&expr.ty)) {
+                Err(Error::NonExhaustive(expr.to_string(), expr.ty.clone()))?
+            }
+            Expr::Match {
+                expr: Box::new(expr),
+                arms,
+            }
+        }
+        Expr::Field { expr, field } => {
+            let expr = infer_expr(*expr, env)?;
+            ty = env.field(&expr.ty, &field)?;
+            Expr::Field {
+                expr: Box::new(expr),
+                field,
+            }
+        }
+        Expr::Method {
+            receiver,
+            method,
+            args,
+            ..
+        } => {
+            let mut receiver = infer_expr(*receiver, env)?;
+            receiver.ty = env.known(&receiver.ty);
+            let mut args = infer_exprs(args, env)?;
+            let (mut func_ty, trait_) = env.method(&receiver.ty, &method)?;
+            // The receiver gets adjusted to fit `self`, so only the other arguments fill in type parameters
+            if let Type::Generic { params, ty } = func_ty {
+                let arg_tys = iter::once(Type::Never)
+                    .chain(args.iter().map(|arg| arg.ty.clone()))
+                    .collect::<Vec<_>>();
+                func_ty = env.instantiate(&params, &ty, &arg_tys)?;
+            }
+            let Type::Function { args: params, ret } = func_ty else {
+                Err(Error::NotAMethod(method))?
+            };
+            let Some((this, params)) = params.split_first() else {
+                Err(Error::NotAMethod(method))?
+            };
+            if params.len() != args.len() {
+                Err(Error::ArgumentCount(
+                    method.clone(),
+                    params.len(),
+                    args.len(),
+                ))?
+            }
+            let receiver = env
+                .adjust(receiver, this)
+                .context(format!("Receiver type mismatch for method '{}'", method))?;
+            for (arg, ty) in args.iter_mut().zip(params) {
+                env.expect(arg, ty).context("Argument type mismatch")?;
+            }
+            ty = *ret;
+            Expr::Method {
+                receiver: Box::new(receiver),
+                method,
+                args,
+                trait_,
+            }
+        }
         Expr::Reference(x) => {
-            ty = env
-                .get(x.as_str())
-                .ok_or_else(|| Error::VariableNotFound(x.clone()))?
-                .clone();
+            // Variables shadow the items of the module
+            let x = if env.is_global(&x) { env.path(&x)? } else { x };
+            ty = env.reference(&x)?;
             Expr::Reference(x)
         }
+        Expr::Instance { func, types } => {
+            let func = if env.is_global(&func) {
+                env.path(&func)?
+            } else {
+                func
+            };
+            let types = types
+                .iter()
+                .map(|ty| env.resolve(ty))
+                .collect::<Result<Vec<_>, _>>()?;
+            for ty in &types {
+                env.check_type(ty)?;
+            }
+            let (params, generic) = match env.reference(&func)? {
+                Type::Generic { params, ty } => (params, *ty),
+                _ => (vec![], Type::Never),
+            };
+            if params.len() != types.len() {
+                Err(Error::TypeArgs(func.clone(), params.len(), types.len()))?
+            }
+            let bindings = params
+                .iter()
+                .map(|param| param.name.clone())
+                .zip(types.clone())
+                .collect();
+            env.check_bounds(&params, &bindings)?;
+            ty = generic.substitute(&bindings);
+            Expr::Instance { func, types }
+        }
         Expr::Let {
             name,
             value,
             ty: opt_ty,
             mutable,
         } => {
-            let value = infer_expr(*value, env)?;
-            let value_ty = value.ty.pop_mut().clone();
-            let ty = if let Some(ty) = opt_ty {
-                ty.expect(&value_ty)
-                    .context(format!("Type mismatch for variable '{}'", name))?;
-                ty
-            } else {
-                value_ty
+            let mut value = value.map(|value| infer_expr(*value, env)).transpose()?;
+            let opt_ty = opt_ty.map(|ty| env.resolve(&ty)).transpose()?;
+            let ty = match (&opt_ty, &mut value) {
+                (Some(ty), Some(value)) => {
+                    env.check_type(ty)?;
+                    env.expect(value, ty)
+                        .context(format!("Type mismatch for variable '{}'", name))?;
+                    ty.clone()
+                }
+                (Some(ty), None) => {
+                    env.check_type(ty)?;
+                    ty.clone()
+                }
+                // A number literal gets its type from how the variable is used, falling back
+                // to an i32 or an f64 like a literal on its own does
+                (None, Some(value)) if is_literal(value) => {
+                    let ty = env.fresh(Unknown::Any);
+                    env.settle(value, &ty)?;
+                    ty
+                }
+                (None, Some(value)) => value.ty.clone(),
+                // Without either, the type comes from the value it's assigned later
+                (None, None) => env.fresh(Unknown::Any),
             };
-            if env.contains_key(name.as_str()) {
-                Err(Error::RedeclarationVariable(name.clone()))?
+            // Rebinding a name shadows the earlier binding, until the end of this block
+            env.insert(&name, ty.clone(), mutable);
+            if value.is_none() {
+                let depth = env.scopes.len() - 1;
+                env.deferred.insert((name.clone(), depth));
             }
-            // Wrap this in a mutable if it can be reassigned later
-            let ty = if mutable {
-                Type::Mutable(Box::new(ty))
-            } else {
-                ty
-            };
-
-            env.insert(name.clone(), ty.clone());
             Expr::Let {
                 name,
-                value: Box::new(value),
+                value: value.map(Box::new),
                 ty: Some(ty),
                 mutable,
             }
@@ -274,8 +2213,13 @@ fn infer_expr(expr: Expression<()>, env: &mut TypeEnv) -> Result<Expression<Type
                 .context("If condition must be a boolean")?;
             ty = then.0.last().map_or(&Type::unit(), |e| &e.ty).clone();
             if let Some(else_) = &else_ {
-                ty.expect(else_.0.last().map_or(&Type::unit(), |e| &e.ty))
+                let else_ty = else_.0.last().map_or(Type::unit(), |e| e.ty.clone());
+                ty = env
+                    .join(&ty, &else_ty)
                     .context("If-else branches must have the same type")?;
+            } else if ty == Type::Never {
+                // Without an else, the if finishes whenever the condition is false
+                ty = Type::unit();
             }
             Expr::If {
                 cond: Box::new(cond),
@@ -284,42 +2228,306 @@ fn infer_expr(expr: Expression<()>, env: &mut TypeEnv) -> Result<Expression<Type
             }
         }
         Expr::Call { func, args } => {
+            // A tuple struct is built by calling it with its fields
+            if let Expr::Reference(name) = &func.expr {
+                match env.path(name) {
+                    Ok(path) if env.is_global(name) && env.is_tuple_struct(&path) => {
+                        return infer_expr(tuple_struct(&path, args), env);
+                    }
+                    _ => (),
+                }
+            }
             let func = infer_expr(*func, env)?;
-            let args = infer_exprs(args, env)?;
-            let func_ty = func.ty.clone();
-
-            if let Type::Function {
-                args: arg_tys,
-                ret: ret_ty,
-            } = func_ty
-            {
-                for (arg, ty) in args.iter().zip(arg_tys) {
-                    arg.ty.expect(&ty).context("Argument type mismatch")?;
+            let mut args = infer_exprs(args, env)?;
+            let func_ty = match func.ty.unalias().clone() {
+                Type::Generic { params, ty } => {
+                    let arg_tys = args.iter().map(|arg| arg.ty.clone()).collect::<Vec<_>>();
+                    env.instantiate(&params, &ty, &arg_tys)?
                 }
-                ty = *ret_ty;
-            } else {
-                Err(Error::ExpectedFunction(func_ty))?;
+                ty => ty,
+            };
+            // Every parameter has to be given a value, or the call would run without it
+            let (shown, given) = (func.to_string(), args.len());
+            let arity = |expected: usize| match expected == given {
+                true => Ok(()),
+                false => Err(Error::ArgumentCount(shown.clone(), expected, given)),
+            };
+            match func_ty {
+                Type::Function {
+                    args: arg_tys,
+                    ret: ret_ty,
+                } => {
+                    arity(arg_tys.len())?;
+                    for (arg, ty) in args.iter_mut().zip(arg_tys) {
+                        env.expect(arg, &ty).context("Argument type mismatch")?;
+                    }
+                    ty = *ret_ty;
+                }
+                // Calling a coroutine starts it, giving back a frame to resume
+                Type::Coro {
+                    args: arg_tys,
+                    resume,
+                    yields,
+                } => {
+                    arity(arg_tys.len())?;
+                    for (arg, ty) in args.iter_mut().zip(arg_tys) {
+                        env.expect(arg, &ty).context("Argument type mismatch")?;
+                    }
+                    ty = Type::Frame { resume, yields };
+                }
+                // Calling a frame resumes it, until it yields again
+                Type::Frame { resume, yields } => {
+                    arity(resume.len())?;
+                    for (arg, ty) in args.iter_mut().zip(resume) {
+                        env.expect(arg, &ty).context("Resume type mismatch")?;
+                    }
+                    ty = *yields;
+                }
+                _ => Err(Error::ExpectedFunction(func_ty))?,
             }
             Expr::Call {
                 func: Box::new(func),
                 args,
             }
         }
-        Expr::While { cond, body } => {
+        Expr::While { cond, body, label } => {
             let cond = infer_expr(*cond, env)?;
-            let body = infer_seq(body, env)?;
+            let (body, _) = env.infer_loop(body, &label, false)?;
             cond.ty
                 .expect(&Type::Bool)
                 .context("While condition must be a boolean")?;
             Expr::While {
                 cond: Box::new(cond),
                 body,
+                label,
+            }
+        }
+        Expr::For {
+            var,
+            iter,
+            body,
+            label,
+        } => {
+            let iter = infer_expr(*iter, env)?;
+            let elem = match iter.ty.unalias() {
+                Type::Range(ty) | Type::Array(ty, _) => *ty.clone(),
+                ty => Err(Error::NotIterable(ty.clone()))?,
+            };
+            // The loop variable is only in scope for the body
+            env.enter();
+            env.insert(&var, elem, false);
+            let body = env.infer_loop(body, &label, false);
+            env.exit();
+            Expr::For {
+                var,
+                iter: Box::new(iter),
+                body: body?.0,
+                label,
+            }
+        }
+        Expr::Loop { body, label } => {
+            let (body, scope) = env.infer_loop(body, &label, true)?;
+            // Without a break, the loop never finishes
+            ty = scope.ty.unwrap_or(Type::Never);
+            Expr::Loop { body, label }
+        }
+        Expr::Break { label, value } => {
+            let value = value.map(|value| infer_expr(*value, env)).transpose()?;
+            let value_ty = value
+                .as_ref()
+                .map_or(Type::unit(), |value| value.ty.clone());
+            let target = env.target(&label)?;
+            if value.is_some() && !target.valued {
+                Err(Error::BreakWithValue)?
+            }
+            // Every break out of a loop needs to give back the same type
+            let joined = match target.ty.clone() {
+                Some(ty) => env
+                    .join(&ty, &value_ty)
+                    .context("Breaks out of a loop must have the same type")?,
+                None => value_ty,
+            };
+            env.target(&label)?.ty = Some(joined);
+            ty = Type::Never;
+            Expr::Break {
+                label,
+                value: value.map(Box::new),
+            }
+        }
+        Expr::Continue(label) => {
+            env.target(&label)?;
+            ty = Type::Never;
+            Expr::Continue(label)
+        }
+        Expr::Return(value) => {
+            let mut value = value.map(|value| infer_expr(*value, env)).transpose()?;
+            if let Some(value) = &mut value {
+                env.ret.settle(value)?;
+            }
+            let value_ty = value
+                .as_ref()
+                .map_or(Type::unit(), |value| value.ty.clone());
+            env.returns(&value_ty)
+                .context("Returned value must match the function's return type")?;
+            ty = Type::Never;
+            Expr::Return(value.map(Box::new))
+        }
+        Expr::Closure {
+            params,
+            ret,
+            body,
+            moves,
+            ..
+        } => {
+            // A parameter without a type gets it from how it's used
+            let params = params
+                .into_iter()
+                .map(|(name, ty)| match ty {
+                    Some(ty) => Ok((name, env.resolve(&ty)?)),
+                    None => Ok((name, env.fresh(Unknown::Any))),
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            let ret = ret.map(|ret| env.resolve(&ret)).transpose()?;
+            for (_, ty) in &params {
+                env.check_type(ty)?;
+            }
+            if let Some(ret) = &ret {
+                env.check_type(ret)?;
+            }
+            let (body, ret, captures) = env.infer_closure(&params, ret, body, moves)?;
+            ty = Type::Function {
+                args: params.iter().map(|(_, ty)| ty.clone()).collect(),
+                ret: Box::new(ret.clone()),
+            };
+            Expr::Closure {
+                params: params
+                    .into_iter()
+                    .map(|(name, ty)| (name, Some(ty)))
+                    .collect(),
+                ret: Some(ret),
+                body,
+                moves,
+                captures,
             }
         }
+        Expr::Yield(value) => {
+            let mut value = infer_expr(*value, env)?;
+            let Some(Type::Frame { resume, yields }) = env.frame.clone() else {
+                Err(Error::YieldOutsideCoroutine)?
+            };
+            env.expect(&mut value, &yields)
+                .context("Yielded value must match the coroutine's yield type")?;
+            ty = Type::resumed(&resume);
+            Expr::Yield(Box::new(value))
+        }
+        Expr::Try(value) => {
+            let value = infer_expr(*value, env)?;
+            // A `None` or `Err` is returned as it is, so it has to fit the return type
+            let (held, early) = match value.ty.unalias() {
+                Type::Option(ty) => (*ty.clone(), Type::Option(Box::new(Type::Never))),
+                Type::Result(ty, err) => (
+                    *ty.clone(),
+                    Type::Result(Box::new(Type::Never), err.clone()),
+                ),
+                ty => Err(Error::NotTryable(ty.clone()))?,
+            };
+            env.returns(&early)
+                .context("Propagated value must match the function's return type")?;
+            ty = held;
+            Expr::Try(Box::new(value))
+        }
     };
+    let ty = env.subst(&ty);
     Ok(Expression { expr, ty })
 }
 
+fn infer_arm(arm: Arm<()>, ty: &Type, env: &mut TypeEnv) -> Result<Arm<Type>, AnyError> {
+    infer_pattern(&arm.pattern, ty, env).context(format!("In pattern '{}'", arm.pattern))?;
+    let guard = arm.guard.map(|guard| infer_expr(guard, env)).transpose()?;
+    if let Some(guard) = &guard {
+        guard
+            .ty
+            .expect(&Type::Bool)
+            .context("Match guard must be a boolean")?;
+    }
+    Ok(Arm {
+        pattern: arm.pattern,
+        guard,
+        body: infer_seq(arm.body, env)?,
+    })
+}
+
+// Checks that a pattern can match a value of the type, binding the names in it
+fn infer_pattern(pattern: &Pattern, ty: &Type, env: &mut TypeEnv) -> Result<(), AnyError> {
+    let ty = &env.subst(ty);
+    match pattern {
+        Pattern::Wildcard => (),
+        Pattern::Binding(name) => env.insert(name, ty.clone(), false),
+        Pattern::Value(value) => {
+            let value_ty = match value {
+                // Without a suffix, a number matches any integer it fits in
+                Value::Number(x, suffix) => {
+                    let value_ty = match suffix {
+                        Some(suffix) => suffix.clone(),
+                        None if ty.is_integer() => ty.clone(),
+                        None => Type::Signed(Size::ThirtyTwo),
+                    };
+                    check_range(*x, &value_ty)?;
+                    value_ty
+                }
+                Value::Float(_, suffix) => match suffix {
+                    Some(suffix) => suffix.clone(),
+                    None if ty.is_float() => ty.clone(),
+                    None => Type::Float(Size::SixtyFour),
+                },
+                Value::Bool(_) => Type::Bool,
+                Value::Char(_) => Type::Char,
+                Value::String(_) => Type::String,
+            };
+            env.unify(ty, &value_ty);
+            value_ty.expect(&env.subst(ty))?;
+        }
+        Pattern::Variant {
+            enum_,
+            variant,
+            args,
+        } if is_builtin_enum(enum_) => {
+            let Some(variant_ty) = Type::of_variant(enum_, variant, &[]) else {
+                Err(Error::VariantNotFound(enum_.clone(), variant.clone()))?
+            };
+            env.unify(ty, &variant_ty);
+            let ty = &env.subst(ty);
+            variant_ty.expect(ty)?;
+            let tys = builtin_variant(ty, variant).unwrap_or_default();
+            if args.len() != tys.len() {
+                let path = format!("{}::{}", enum_, variant);
+                Err(Error::VariantArity(path, tys.len(), args.len()))?
+            }
+            for (arg, ty) in args.iter().zip(&tys) {
+                infer_pattern(arg, ty, env)?;
+            }
+        }
+        Pattern::Variant {
+            enum_,
+            variant,
+            args,
+        } => {
+            let enum_ = &env.path(enum_)?;
+            env.unify(ty, &Type::Named(enum_.clone()));
+            Type::Named(enum_.clone()).expect(&env.subst(ty))?;
+            let tys = env.variant(enum_, variant)?;
+            if args.len() != tys.len() {
+                let path = format!("{}::{}", enum_, variant);
+                Err(Error::VariantArity(path, tys.len(), args.len()))?
+            }
+            for (arg, ty) in args.iter().zip(&tys) {
+                infer_pattern(arg, ty, env)?;
+            }
+        }
+    }
+    Ok(())
+}
+
 impl<T: TypeBound> Function<T> {
     fn expect(&self, ty: &Type) -> Result<(), AnyError> {
         self.get_type()
@@ -327,58 +2535,925 @@ impl<T: TypeBound> Function<T> {
             .context(format!("In function '{}'", self.name))
     }
 
-    fn get_type(&self) -> Type {
+    pub fn get_type(&self) -> Type {
+        let args = self.args.iter().map(|(_, ty)| ty).cloned().collect();
+        let ty = match &self.kind {
+            FuncKind::Coro(resume) => Type::Coro {
+                args,
+                resume: resume.iter().map(|(_, ty)| ty).cloned().collect(),
+                yields: Box::new(self.ret.clone()),
+            },
+            _ => Type::Function {
+                args,
+                ret: Box::new(self.ret.clone()),
+            },
+        };
+        match &self.generics[..] {
+            [] => ty,
+            params => Type::Generic {
+                params: params.to_vec(),
+                ty: Box::new(ty),
+            },
+        }
+    }
+}
+
+impl Function<()> {
+    // Turns the names in the signature into the types they stand for, as seen from the module and
+    // impl the environment is in, along with the type parameters and the traits they're bound by
+    fn resolve(mut self, env: &TypeEnv) -> Result<Self, Error> {
+        let mut seen = HashSet::new();
+        if let Some(param) = self.generics.iter().find(|param| !seen.insert(&param.name)) {
+            Err(Error::DuplicateParam(param.name.clone()))?
+        }
+        for param in &mut self.generics {
+            param.bounds = param
+                .bounds
+                .iter()
+                .map(|bound| env.path(bound))
+                .collect::<Result<_, _>>()?;
+        }
+        let env = TypeEnv {
+            generics: self.generics.clone(),
+            ..env.clone()
+        };
+        let resolve_all = |tys: &mut Vec<(String, Type)>| {
+            for (_, ty) in tys {
+                *ty = env.resolve(ty)?;
+            }
+            Ok::<_, Error>(())
+        };
+        resolve_all(&mut self.args)?;
+        self.ret = env.resolve(&self.ret)?;
+        self.kind = match self.kind {
+            FuncKind::Func => FuncKind::Func,
+            FuncKind::Yields(frame) => FuncKind::Yields(env.resolve(&frame)?),
+            FuncKind::Coro(mut resume) => {
+                resolve_all(&mut resume)?;
+                FuncKind::Coro(resume)
+            }
+        };
+        Ok(self)
+    }
+}
+
+impl Signature {
+    // The type of the functions that implement the method, for the type that `Self` stands for
+    fn get_type(&self, this: &Type) -> Type {
         Type::Function {
-            args: self.args.iter().map(|(_, ty)| ty).cloned().collect(),
-            ret: Box::new(self.ret.clone()),
+            args: self
+                .args
+                .iter()
+                .map(|(_, ty)| ty.replace_self(this))
+                .collect(),
+            ret: Box::new(self.ret.replace_self(this)),
         }
     }
 }
 
 impl Type {
-    // A strict equality check
+    // A strict equality check, besides using a mutable reference as a shared one
     fn expect(&self, ty: &Type) -> Result<(), AnyError> {
-        if self.pop_mut() == ty.pop_mut() {
+        if self.coerces_to(ty) {
             Ok(())
         } else {
-            Err(Error::UnexpectedType(self.clone(), ty.clone()).into())
+            Err(Error::UnexpectedType(ty.clone(), self.clone()).into())
         }
     }
 
-    // Unpacks mutable values
-    fn pop_mut(&self) -> &Type {
+    fn expect_integer(&self) -> Result<(), AnyError> {
         match self {
-            Type::Mutable(ty) => ty,
-            _ => self,
+            ty if ty.is_integer() || *ty == Type::Never => Ok(()),
+            ty => Err(Error::NotInteger(ty.clone()).into()),
         }
     }
 
-    // Uses just one reference
-    fn pop_ref(&self) -> &Type {
+    fn expect_numeric(&self) -> Result<(), AnyError> {
         match self {
-            Type::Reference(ty) => ty,
-            _ => self,
+            ty if ty.is_numeric() || *ty == Type::Never => Ok(()),
+            ty => Err(Error::NotNumeric(ty.clone()).into()),
         }
     }
+}
 
-    // Wraps the type in a mutable reference if the type is mutable
-    fn map_mut(&self, f: impl FnOnce(&Type) -> Result<Type, AnyError>) -> Result<Type, AnyError> {
-        match self {
-            Type::Mutable(ty) => {
-                let ty = f(ty)?;
-
-                match ty {
-                    // if the type is a composite type, we lose the mutability
-                    // This fix prevents mutable nested values from being created
-                    // To support this properly, we would need to add a new type for non-mutable values
-                    Type::Array(_, _) | Type::Tuple(_) => Ok(ty),
-                    // if the type is already mutable, we don't need to wrap it again
-                    Type::Mutable(_) => Ok(ty),
-                    _ => Ok(Type::Mutable(Box::new(ty))),
-                }
+impl Expression<Type> {
+    // Checks that the expression can be used where the type is expected, which is what
+    // decides the type of the integer literals in it
+    fn expect(&mut self, ty: &Type) -> Result<(), AnyError> {
+        settle(self, ty)?;
+        self.ty.expect(ty)
+    }
+}
+
+// Whether the expression is made of number literals without a suffix, so its type still comes
+// from the context it's used in
+fn is_literal(expr: &Expression<Type>) -> bool {
+    match &expr.expr {
+        Expr::Value(Value::Number(_, None) | Value::Float(_, None)) => true,
+        Expr::Unary {
+            op: Operator::Sub,
+            rhs,
+        } => is_literal(rhs),
+        Expr::Binary { lhs, op, .. } if op.is_shift() => is_literal(lhs),
+        Expr::Binary { lhs, op, rhs } if op.is_arith() || op.is_bitwise() => {
+            is_literal(lhs) && is_literal(rhs)
+        }
+        Expr::If {
+            then,
+            else_: Some(else_),
+            ..
+        } => [then, else_]
+            .iter()
+            .all(|seq| seq.0.last().is_some_and(is_literal)),
+        _ => false,
+    }
+}
+
+// Gives the number literals in an expression the type they're used as, which includes the ones
+// inside of arrays and tuples. Integers only become other integers, and floats other floats.
+fn settle(expr: &mut Expression<Type>, ty: &Type) -> Result<(), Error> {
+    let ty = ty.unalias();
+    if ty.is_numeric() && is_literal(expr) && expr.ty.is_integer() == ty.is_integer() {
+        return retype(expr, ty, false);
+    }
+    match (&mut expr.expr, ty) {
+        (Expr::Array(exprs), Type::Array(elem, len)) => {
+            for expr in exprs.iter_mut() {
+                settle(expr, elem)?;
+            }
+            if let Some(first) = exprs.first() {
+                expr.ty = Type::Array(Box::new(first.ty.clone()), exprs.len());
+            }
+        }
+        (
+            Expr::Variant {
+                enum_,
+                variant,
+                args,
+            },
+            ty,
+        ) if is_builtin_enum(enum_) => {
+            let Some(tys) = builtin_variant(ty, variant) else {
+                return Ok(());
+            };
+            for (arg, ty) in args.iter_mut().flatten().zip(&tys) {
+                settle(arg, ty)?;
+            }
+            let tys = args
+                .iter()
+                .flatten()
+                .map(|arg| arg.ty.clone())
+                .collect::<Vec<_>>();
+            if let Some(ty) = Type::of_variant(enum_, variant, &tys) {
+                expr.ty = ty;
+            }
+        }
+        (Expr::Tuple(exprs), Type::Tuple(tys)) if exprs.len() == tys.len() => {
+            for (expr, ty) in exprs.iter_mut().zip(tys) {
+                settle(expr, ty)?;
             }
+            expr.ty = Type::Tuple(exprs.iter().map(|expr| expr.ty.clone()).collect());
+        }
+        _ => (),
+    }
+    Ok(())
+}
 
-            _ => f(self),
+// Sets the type of each literal, which has to fit in it once it's negated
+fn retype(expr: &mut Expression<Type>, ty: &Type, negated: bool) -> Result<(), Error> {
+    match &mut expr.expr {
+        Expr::Value(Value::Number(x, suffix)) => {
+            check_range(if negated { -*x } else { *x }, ty)?;
+            *suffix = Some(ty.clone());
+        }
+        Expr::Value(Value::Float(_, suffix)) => *suffix = Some(ty.clone()),
+        Expr::Unary { rhs, .. } => {
+            if let Type::Unsigned(_) = ty {
+                Err(Error::UnsignedNegation(ty.clone()))?
+            }
+            retype(rhs, ty, !negated)?;
+        }
+        Expr::Binary { lhs, op, .. } if op.is_shift() => retype(lhs, ty, false)?,
+        Expr::Binary { lhs, rhs, .. } => {
+            retype(lhs, ty, false)?;
+            retype(rhs, ty, false)?;
+        }
+        Expr::If { then, else_, .. } => {
+            for seq in iter::once(then).chain(else_) {
+                if let Some(last) = seq.0.last_mut() {
+                    retype(last, ty, false)?;
+                }
+            }
         }
+        _ => (),
+    }
+    expr.ty = ty.clone();
+    Ok(())
+}
+
+// Builds the tuple struct of the resolved path out of the values of its fields
+fn tuple_struct(path: &str, args: Vec<Expression<()>>) -> Expression<()> {
+    let fields = args
+        .into_iter()
+        .enumerate()
+        .map(|(i, arg)| (i.to_string(), arg))
+        .collect();
+    // The path is already resolved, so it's looked up from the root
+    let name = format!("crate::{}", path);
+    Expr::Struct { name, fields }.into()
+}
+
+fn is_builtin_enum(name: &str) -> bool {
+    name == "Option" || name == "Result"
+}
+
+// The types of the values a variant of a builtin enum type holds
+fn builtin_variant(ty: &Type, variant: &str) -> Option<Vec<Type>> {
+    ty.variants()?
+        .into_iter()
+        .find(|(name, _)| name == variant)
+        .map(|(_, tys)| tys)
+}
+
+fn check_range(x: i128, ty: &Type) -> Result<(), Error> {
+    match ty.bounds() {
+        Some((min, max)) if x < min || x > max => Err(Error::OutOfRange(x, ty.clone())),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::infer;
+    use crate::{ast::tree::Program, parse, semantics::types::Type};
+    use anyhow::Error as AnyError;
+
+    fn infer_src(src: &str) -> Result<Program<Type>, AnyError> {
+        infer(parse(src)?)
+    }
+
+    // A program made of the items, and a main function with the body
+    fn with_main(items: &str, body: &str) -> String {
+        format!("{items} fn main() -> i32 {{ {body} }}")
+    }
+
+    fn infer_main(body: &str) -> Result<Program<Type>, AnyError> {
+        infer_src(&with_main("", body))
+    }
+
+    fn assert_rejects(src: &str, msg: &str) {
+        let err = format!("{:?}", infer_src(src).unwrap_err());
+        assert!(err.contains(msg), "{}", err);
+    }
+
+    fn assert_rejects_main(body: &str, msg: &str) {
+        assert_rejects(&with_main("", body), msg);
+    }
+
+    #[test]
+    fn test_scope_exit() {
+        assert_rejects_main(
+            "if true { let y = 1; y } else { 2 };
+            y",
+            "Variable 'y' not found",
+        );
+    }
+
+    #[test]
+    fn test_coroutines() {
+        let echo = "coro echo(n: i32)(x: i32) -> i32 { yield x + n }";
+        assert!(infer_src(&with_main(echo, "let f = echo(1); f(2)")).is_ok());
+        // Every parameter has to be given, both when starting and when resuming
+        assert_rejects(
+            &with_main(echo, "let f = echo(); f(2)"),
+            "'echo' takes 1 arguments, but 0 were given",
+        );
+        assert_rejects(
+            &with_main(echo, "let f = echo(1); f()"),
+            "'f' takes 1 arguments, but 0 were given",
+        );
+        assert_rejects(
+            &with_main("fn f(x: i32) -> i32 { x }", "f(1, 2)"),
+            "'f' takes 1 arguments, but 2 were given",
+        );
+        assert_rejects_main("yield 1; 0", "Yield used outside of a coroutine");
+        // A function that yields can only be called from the kind of frame it yields into
+        assert_rejects(
+            "fn emit(x: i32) -> i32 yield frame(i32) -> i32 { yield x }
+            coro other()() -> bool { emit(1); yield true }
+            fn main() -> i32 { 0 }",
+            "Function 'emit' yields into 'frame(i32) -> i32', which is not the current frame",
+        );
+    }
+
+    #[test]
+    fn test_for_loops() {
+        assert_rejects_main("for i in 0..3 { i }; i", "Variable 'i' not found");
+        assert_rejects_main(
+            "for i in 0..true { i }; 0",
+            "Range bounds must have the same type",
+        );
+        assert_rejects_main("for x in (1, 2) { x }; 0", "Can't loop over '(i32, i32)'");
+    }
+
+    #[test]
+    fn test_mutable_references() {
+        assert_rejects_main(
+            "var x = 1;
+            let r: &i32 = &mut x;
+            let q = &x;
+            *q = 2;
+            *r",
+            "Not mutable: '(*q)' is not mutable",
+        );
+        assert_rejects_main(
+            "let x = 1; let r = &mut x; *r",
+            "Not mutable: 'x' is not mutable",
+        );
+    }
+
+    #[test]
+    fn test_structs() {
+        let rejects = |body: &str, msg: &str| {
+            assert_rejects(&with_main("struct Point { x: i32, y: i32 }", body), msg)
+        };
+        rejects("let p = Point { x: 1 }; 0", "Missing field 'y'");
+        rejects(
+            "let p = Point { x: 1, y: 2 }; p.z",
+            "No field 'z' on type 'Point'",
+        );
+        rejects(
+            "let p = Point { x: 1, y: 2 }; p.x = 3; 0",
+            "Not mutable: 'p.x' is not mutable",
+        );
+        rejects("let p: Line = 0; 0", "Type 'Line' not found");
+    }
+
+    #[test]
+    fn test_match() {
+        let enums = "enum Opt { Some(i32), None } enum Pair { Two(Opt, bool) }";
+        let infer_body = |body: &str| infer_src(&with_main(enums, body));
+        let rejects = |body: &str, msg: &str| assert_rejects(&with_main(enums, body), msg);
+        assert!(infer_body("match Opt::Some(1) { Opt::Some(x) => x, Opt::None => 0 }").is_ok());
+        assert!(infer_body(
+            "match Pair::Two(Opt::None, true) {
+                Pair::Two(Opt::Some(x), _) => x,
+                Pair::Two(Opt::None, true) => 1,
+                Pair::Two(_, false) => 2,
+            }"
+        )
+        .is_ok());
+        rejects(
+            "match Opt::None { Opt::Some(x) => x }",
+            "Non-exhaustive match",
+        );
+        rejects(
+            "match Opt::None { Opt::Some(x) if x > 0 => x, Opt::None => 0 }",
+            "Non-exhaustive match",
+        );
+        rejects(
+            "match Pair::Two(Opt::None, true) {
+                Pair::Two(Opt::Some(x), _) => x,
+                Pair::Two(Opt::None, true) => 1,
+            }",
+            "Non-exhaustive match",
+        );
+        rejects("match 3 { 0 => 1, 1 => 0 }", "Non-exhaustive match");
+        rejects("match true { true => 1, false => false }", "same type");
+        rejects(
+            "match Opt::None { Opt::Some(x, y) => x, _ => 0 }",
+            "holds 1 values",
+        );
+    }
+
+    #[test]
+    fn test_loops() {
+        assert!(infer_main("var i = 0; loop { i = i + 1; if i > 3 { break i } }").is_ok());
+        assert!(infer_main("let x: i32 = if true { 1 } else { loop { continue } }; x").is_ok());
+        assert!(infer_main("'a: loop { for i in 0..3 { continue 'a }; break 'a 1 }").is_ok());
+        // A loop with a break finishes, so it has the break's type rather than any type
+        assert_rejects_main(
+            "let x: i32 = loop { break }; x",
+            "Type mismatch for variable 'x'",
+        );
+        assert_rejects_main("loop { if true { break 1 }; break true }", "same type");
+        assert_rejects_main("break; 0", "outside of a loop");
+        assert_rejects_main("'a: while true { break 'b }; 0", "Unknown loop label: 'b");
+        assert_rejects_main(
+            "while true { break 1 }; 0",
+            "Only 'loop' can be broken out of with a value",
+        );
+    }
+
+    #[test]
+    fn test_returns() {
+        let infer_func = |func: &str| infer_src(&with_main(func, "0"));
+        assert!(infer_func("fn f(x: i32) -> i32 { if x > 0 { return x } else { 0 } }").is_ok());
+        assert!(infer_func("fn f(x: i32) -> bool { while true { return x > 0 }; false }").is_ok());
+        assert!(infer_func("fn f() -> () { return }").is_ok());
+        assert!(infer_func("coro c()() -> i32 { yield 1; return true }").is_ok());
+        assert_rejects(
+            &with_main("fn f() -> i32 { if true { return false }; 1 }", "0"),
+            "Returned value must match",
+        );
+    }
+
+    #[test]
+    fn test_closures() {
+        assert!(infer_main("let y = 1; let f: (i32) -> i32 = |x: i32| x + y; f(2)").is_ok());
+        assert!(infer_main("let f = |x: i32| { if x > 0 { return x }; 0 }; f(1)").is_ok());
+        assert_rejects_main(
+            "let f = |x: i32| x > 0; f(1)",
+            "Return type mismatch for function main",
+        );
+        assert_rejects_main(
+            "let f = |x: i32| -> bool x; 0",
+            "Return type mismatch for closure",
+        );
+        assert_rejects_main("var y = 1; let f = move || y = 2; 0", "Not mutable: 'y'");
+        assert_rejects_main("let f = || x; let x = 1; 0", "Variable 'x' not found");
+        assert_rejects_main("loop { let f = || { continue }; 0 }", "outside of a loop");
+    }
+
+    #[test]
+    fn test_generics() {
+        let generic = "fn id<T>(x: T) -> T { x } fn pick<T>(a: T, b: T, first: bool) -> T { if first { a } else { b } }";
+        let infer_main = |body: &str| infer_src(&with_main(generic, body));
+        assert!(infer_main("let b: bool = id(true); id(1)").is_ok());
+        assert!(infer_main("let f: (i32) -> i32 = id; id::<i32>(f(2))").is_ok());
+        assert!(infer_main("let x = 1; *id(&x)").is_ok());
+        assert!(infer_main("pick(1, 2, true)").is_ok());
+        assert!(infer_main("pick(1, true, true)").is_err());
+        assert!(infer_main("id::<bool>(1)").is_err());
+        assert!(infer_main("id(true)").is_err());
+        assert_rejects(
+            "fn none<T>() -> i32 { 0 } fn main() -> i32 { none() }",
+            "Can't infer type parameter 'T'",
+        );
+        assert_rejects(
+            "fn id<T>(x: T) -> T { x } fn main() -> i32 { id::<i32, i32>(0) }",
+            "'id' takes 1 type parameters, but 2 were given",
+        );
+        assert_rejects(
+            "fn main() -> i32 { main::<i32>() }",
+            "'main' takes 0 type parameters, but 1 were given",
+        );
+        assert_rejects(
+            "fn f<T, T>(x: T) -> T { x } fn main() -> i32 { 0 }",
+            "Type parameter 'T' appears more than once",
+        );
+        // The body can't assume anything about the type it's given
+        assert_rejects(
+            "fn f<T>(x: T) -> T { 5 } fn main() -> i32 { 0 }",
+            "Return type mismatch for function f",
+        );
+        assert_rejects(
+            "fn f<T>(x: T) -> i32 { x + 1 } fn main() -> i32 { 0 }",
+            "In function 'f'",
+        );
+    }
+
+    #[test]
+    fn test_traits() {
+        let traits = "struct P { x: i32 } trait Get { fn get(&self) -> i32; fn set(&mut self, x: i32); } \
+            impl P { fn new(x: i32) -> Self { P { x: x } } fn zero() -> i32 { 0 } } \
+            impl Get for P { fn get(&self) -> i32 { (*self).x } fn set(&mut self, x: i32) { (*self).x = x; } } \
+            fn twice<T: Get>(t: &T) -> i32 { t.get() + t.get() }";
+        let infer_main = |body: &str| infer_src(&with_main(traits, body));
+        let rejects = |body: &str, msg: &str| assert_rejects(&with_main(traits, body), msg);
+        assert!(infer_main("var p = P::new(1); p.set(2); p.get() + twice(&p)").is_ok());
+        assert!(infer_main("let p = P::new(1); let r = &p; r.get() + P::zero()").is_ok());
+        // Without parentheses, a path is the function itself, to be called later
+        assert!(infer_main("let f = P::zero; let g = P::new; f() + g(1).x").is_ok());
+        rejects(
+            "let x: i32 = P::zero; x",
+            "expected 'i32' but found '() -> i32'",
+        );
+        rejects("let p = P::new(1); p.set(2); 0", "'p' is not mutable");
+        rejects(
+            "let p = P::new(1); p.size()",
+            "No method 'size' on type 'P'",
+        );
+        rejects("let p = P::new(1); p.zero()", "'zero' doesn't take 'self'");
+        rejects(
+            "let p = P::new(1); p.get(1)",
+            "'get' takes 0 arguments, but 1 were given",
+        );
+        rejects(
+            "let x = 1; twice(&x)",
+            "Trait 'Get' is not implemented for 'i32'",
+        );
+        assert_rejects(
+            "trait A { fn a(&self) -> i32; } impl A for i32 { } fn main() -> i32 { 0 }",
+            "Missing method 'a' of trait 'A'",
+        );
+        assert_rejects(
+            "trait A { } impl A for i32 { fn a(&self) -> i32 { 0 } } fn main() -> i32 { 0 }",
+            "Method 'a' is not a member of trait 'A'",
+        );
+        assert_rejects(
+            "impl B for i32 { } fn main() -> i32 { 0 }",
+            "Trait 'B' not found",
+        );
+        assert_rejects(
+            "trait A { } impl A for i32 { } impl A for i32 { } fn main() -> i32 { 0 }",
+            "Trait 'A' is implemented more than once for 'i32'",
+        );
+        // The body can only use what the bounds provide
+        assert_rejects(
+            "trait A { } fn f<T: A>(t: &T) -> i32 { t.a() } fn main() -> i32 { 0 }",
+            "No method 'a' on type '&T'",
+        );
+    }
+
+    #[test]
+    fn test_method_names() {
+        let traits = "struct P { x: i32 } trait A { fn get(&self) -> i32; } trait B { fn get(&self) -> bool; } \
+            impl A for P { fn get(&self) -> i32 { 1 } } impl B for P { fn get(&self) -> bool { true } } \
+            fn a<T: A>(t: &T) -> i32 { t.get() }";
+        let infer_main = |body: &str| infer_src(&with_main(traits, body));
+        // Each trait has its own method, which a bound picks out
+        assert!(infer_main("let p = P { x: 0 }; a(&p)").is_ok());
+        assert_rejects(
+            &with_main(traits, "let p = P { x: 0 }; p.get()"),
+            "Method 'get' on type 'P' is ambiguous",
+        );
+        assert_rejects(
+            &with_main(traits, "let p = P { x: 0 }; P::get(&p)"),
+            "Method 'get' on type 'P' is ambiguous",
+        );
+        // An inherent method comes before the ones from traits
+        let inherent = format!("{traits} impl P {{ fn get(&self) -> u8 {{ 2 }} }}");
+        let body = "let p = P { x: 0 }; let x: u8 = p.get(); 0";
+        assert!(infer_src(&with_main(&inherent, body)).is_ok());
+        assert_rejects(
+            "trait A { fn get(&self) -> i32; } trait B { fn get(&self) -> i32; } \
+            fn f<T: A + B>(t: &T) -> i32 { t.get() } fn main() -> i32 { 0 }",
+            "Method 'get' on type 'T' is ambiguous",
+        );
+    }
+
+    #[test]
+    fn test_modules() {
+        let geo = "mod geo { pub struct P { x: i32 } fn secret() -> i32 { 1 } \
+            pub fn make() -> P { P { x: secret() } } pub mod inner { pub fn get() -> i32 { super::secret() } } }";
+        let infer_main = |body: &str| infer_src(&with_main(geo, body));
+        assert!(infer_main("let p: geo::P = geo::make(); geo::inner::get()").is_ok());
+        assert!(infer_main("let p = crate::geo::make(); 0").is_ok());
+        assert!(infer_main("let f = geo::make; let g = geo::inner::get; f().x + g()").is_ok());
+        assert!(
+            infer_src("fn g() -> i32 { 0 } mod a { pub fn f() -> i32 { super::g() } } fn main() -> i32 { a::f() }")
+                .is_ok()
+        );
+        assert!(infer_main("geo::secret()").is_err());
+        assert!(infer_main("make()").is_err());
+        // A function's name only has to be unique within its module
+        assert!(infer_src("mod a { pub fn f() -> i32 { 1 } } use a::f; fn g() -> i32 { f() } fn main() -> i32 { g() }")
+        .is_ok());
+        assert!(
+            infer_src("mod a { pub fn main() -> i32 { 1 } } fn main() -> i32 { a::main() }")
+                .is_ok()
+        );
+        assert_rejects(
+            &format!("{geo} fn main() -> i32 {{ geo::secret() }}"),
+            "'geo::secret' is private",
+        );
+        assert_rejects(
+            "mod a { struct S {} } fn main() -> i32 { let s: a::S = a::S {}; 0 }",
+            "'a::S' is private",
+        );
+        assert_rejects(
+            "mod a { pub fn f() -> i32 { g() } } fn g() -> i32 { 0 } fn main() -> i32 { a::f() }",
+            "Variable 'a::g' not found",
+        );
+        assert_rejects(
+            "mod a { pub fn f() -> i32 { super::g() } } fn main() -> i32 { a::f() }",
+            "Function 'g' not found",
+        );
+        assert_rejects(
+            "mod a { } fn main() -> i32 { a::f() }",
+            "Function 'a::f' not found",
+        );
+        assert_rejects(
+            "mod a { } use a::f; fn main() -> i32 { 0 }",
+            "Can't find 'a::f' to import it",
+        );
+        assert_rejects(
+            "mod a { pub fn f() -> i32 { 0 } } use a::f; fn f() -> i32 { 0 } fn main() -> i32 { 0 }",
+            "'f' is imported, but it's already declared",
+        );
+        assert_rejects(
+            "mod a; fn main() -> i32 { 0 }",
+            "Module 'a' has to be loaded from its file",
+        );
+    }
+
+    #[test]
+    fn test_integers() {
+        assert!(infer_main("let x: u8 = 255; let y = x + 1; 0").is_ok());
+        assert!(infer_main(
+            "let x = 5u64; let y: i8 = -128; let z = -128i8; if x > 2 { 1 } else { 0 }"
+        )
+        .is_ok());
+        assert!(
+            infer_main("let a: [u16; 2] = [1, 2]; for i in 0..a[1] { let j: u16 = i; }; 0").is_ok()
+        );
+        // The literals take the element type, but the array keeps its own length
+        assert_rejects_main(
+            "let a: [u8; 2] = [1]; 0",
+            "expected '[u8; 2]' but found '[u8; 1]'",
+        );
+        assert!(infer_src(
+            "fn f(x: i64) -> u8 { if x > 0 { 1 } else { 2 } } fn main() -> i32 { f(3); 0 }"
+        )
+        .is_ok());
+        assert_rejects_main("let x: u8 = 256; 0", "Literal '256' doesn't fit in 'u8'");
+        assert_rejects_main("let x = 128i8; 0", "Literal '128' doesn't fit in 'i8'");
+        // A literal that isn't used as anything in particular has to fit in an i32
+        assert_rejects_main(
+            "let x = 3000000000; 0",
+            "Literal '3000000000' doesn't fit in 'i32'",
+        );
+        assert!(infer_main("let x = -2147483648; let y: i64 = 3000000000; 0").is_ok());
+        assert_rejects_main(
+            "let x: u32 = -1; 0",
+            "Can't negate 'u32', since it's unsigned",
+        );
+        assert_rejects_main(
+            "let x: u8 = 1; let y: i32 = x; 0",
+            "Type mismatch for variable 'y'",
+        );
+        assert_rejects_main(
+            "let x = 1u8; let y = 2i16; x + y",
+            "Invalid operand types for arithmetic operator",
+        );
+        assert_rejects_main("true + true", "Expected a number, but found 'bool'");
+    }
+
+    #[test]
+    fn test_floats() {
+        assert!(infer_main("let x: f32 = 1.5; let y = x * 2.0; let z = -2e10; 0").is_ok());
+        assert!(infer_main(
+            "let x = 2f32; let y: [f64; 2] = [1.0, 0.5]; if x < 3.0 { 1 } else { 0 }"
+        )
+        .is_ok());
+        assert_rejects_main("let x: f32 = 1; 0", "Type mismatch for variable 'x'");
+        assert_rejects_main(
+            "let x = 1.5; let y = 2; x + y",
+            "Invalid operand types for arithmetic operator",
+        );
+        assert_rejects_main(
+            "let x = 1.5f32; let y = 2.5f64; x < y",
+            "comparison operator",
+        );
+        assert_rejects_main(
+            "let x = 1.5; let r = 0..x; 0",
+            "Range bounds must have the same type",
+        );
+    }
+
+    #[test]
+    fn test_bitwise() {
+        // A shift amount keeps its own type, while a literal being shifted takes the context's
+        assert!(infer_main("let n: u8 = 3; let x: u64 = 1 << n; let y = x >> 2; 0").is_ok());
+        assert!(infer_main("var x = 5u16; x &= 3; x ^= 1; let b = true | false; 0").is_ok());
+        assert_rejects_main(
+            "let x = 1.5 & 2.0; 0",
+            "Expected an integer, but found 'f64'",
+        );
+        assert_rejects_main("let x = 1 << 2.0; 0", "Invalid shift amount");
+        assert_rejects_main("let x = 1u8; let y = 1u16; x | y", "bitwise operator");
+        assert_rejects_main("let x = 1; x += 1; 0", "Not mutable: 'x' is not mutable");
+    }
+
+    #[test]
+    fn test_strings() {
+        // Strings of any length have the same type
+        assert!(infer_main(
+            "let s: String = \"ab\" + \"cde\"; let c: char = s[0]; let t: String = s[1..3]; 0"
+        )
+        .is_ok());
+        assert!(infer_main("let n: u64 = \"héllo\".len(); let b = \"a\" < \"b\"; 0").is_ok());
+        assert_rejects_main(
+            "let s = \"a\" + 'b'; 0",
+            "Invalid operand types for concatenation",
+        );
+        assert_rejects_main(
+            "var s = \"ab\"; s[0] = 'c'; 0",
+            "Not mutable: 's[0]' is not mutable",
+        );
+        assert_rejects_main(
+            "let s = \"ab\"; s.push('c'); 0",
+            "No method 'push' on type 'String'",
+        );
+    }
+
+    #[test]
+    fn test_prelude() {
+        // The prelude is seen from modules, and variables can still shadow it
+        assert!(infer_src(
+            "mod m { pub fn f() { println([Some(1.5)]); } } \
+            fn main() -> i32 { m::f(); let print = 0; print }"
+        )
+        .is_ok());
+        assert_rejects(
+            "fn main() -> i32 { print(main); 0 }",
+            "Trait 'Display' is not implemented for '() -> i32'",
+        );
+        assert_rejects(
+            "mod m { fn exit(x: i32) -> i32 { x } } fn main() -> i32 { 0 }",
+            "Redeclaration of 'm::exit', which is a builtin function",
+        );
+        assert_rejects(
+            "fn main() -> i32 { let line: String = read_line(); 0 }",
+            "Unexpected Type",
+        );
+    }
+
+    #[test]
+    fn test_options() {
+        let func = |ret: &str, body: &str| with_main(&format!("fn f() -> {ret} {{ {body} }}"), "0");
+        let infer_fn = |ret: &str, body: &str| infer_src(&func(ret, body));
+        let rejects = |ret: &str, body: &str, msg: &str| assert_rejects(&func(ret, body), msg);
+        // The side a variant doesn't hold is filled in by where it's used
+        assert!(infer_fn("Option<u8>", "let x: Option<u8> = None; Some(5)").is_ok());
+        assert!(infer_fn("Result<i32, bool>", "let x = Err(true)?; Ok(1)").is_ok());
+        assert!(infer_fn(
+            "i32",
+            "let x: Option<Option<i32>> = Some(None); match x { Some(Some(n)) => n, _ => 0 }"
+        )
+        .is_ok());
+        rejects("Option<u8>", "Some(300)", "doesn't fit in 'u8'");
+        rejects("Option<i32>", "Ok(1)?", "Propagated value must match");
+        rejects("i32", "let x = 5?; x", "can be unwrapped with '?'");
+        rejects("Option<i32>", "Some(1, 2)", "holds 1 values, but 2");
+        rejects(
+            "i32",
+            "let x = Some(1); match x { Some(n) => n }",
+            "Non-exhaustive",
+        );
+    }
+
+    #[test]
+    fn test_unification() {
+        // Types can come from how a value is used later on
+        assert!(infer_main("var x = []; x = [1u8]; 0").is_ok());
+        assert!(infer_main("let x; x = 5; x").is_ok());
+        assert!(infer_main("let x: u8; x = 5; 0").is_ok());
+        assert!(infer_main("let f = |x| x + 1; f(2)").is_ok());
+        assert!(
+            infer_main("let f = |x, y| x < y; let b: u8 = 1; if f(b, 2) { 0 } else { 1 }").is_ok()
+        );
+        assert_rejects_main(
+            "let f = |x| x; let y = f(true); f(1)",
+            "expected 'bool' but found 'i32'",
+        );
+        assert_rejects_main(
+            "let x: (u8, bool) = (1, 2); 0",
+            "expected '(u8, bool)' but found '(u8, i32)'",
+        );
+        // A variable holding a number literal is the type it's used as
+        assert!(infer_main("let x = 5; let y: u8 = x; 0").is_ok());
+        assert!(
+            infer_src("fn f(b: u8) -> u8 { b } fn main() -> i32 { let x = 5; f(x); 0 }").is_ok()
+        );
+        assert!(infer_main("let x = 1.5; let y: f32 = x; 0").is_ok());
+        assert!(infer_main("let x = 5; let y = x + 1; y").is_ok());
+        assert!(infer_main("let n = 3; for i in 0..n { let j = i; }; let m: u8 = n; 0").is_ok());
+        assert!(infer_main("let a = [1, 2]; let i = 1; a[i]").is_ok());
+        assert_rejects_main("let x = 300; let y: u8 = x; 0", "doesn't fit");
+        assert_rejects_main(
+            "let x = 5; let y: u8 = x; let z: i64 = x; 0",
+            "expected 'i64' but found 'u8'",
+        );
+        assert_rejects_main("let x = []; 0", "Can't infer the type of 'x'");
+        assert_rejects_main("let f = |x| x; 0", "Can't infer the type of 'f'");
+        assert_rejects_main(
+            "let f = |x, y| x * y; f(true, false); 0",
+            "Expected a number, but found 'bool'",
+        );
+        assert_rejects_main(
+            "let f = |x| x + 1; f(true); 0",
+            "expected 'i32' but found 'bool'",
+        );
+        assert_rejects_main(
+            "let x; x = 1; x = true; 0",
+            "expected 'i32' but found 'bool'",
+        );
+    }
+
+    #[test]
+    fn test_globals() {
+        // Globals are evaluated after the ones they use, wherever they're declared
+        let prgm = infer_src(
+            "const A: u8 = B + 1; static B: u8 = m::C * 2; mod m { pub const C: u8 = 3; }
+            fn main() -> i32 { let x: u8 = A + m::C; 0 }",
+        )
+        .unwrap()
+        .to_string();
+        let order = ["const m::C", "static B", "const A", "fn main"];
+        let found = order.map(|item| prgm.find(item).unwrap());
+        assert!(found.windows(2).all(|pair| pair[0] < pair[1]), "{}", prgm);
+        // Constants are worked out before the program runs, from the statics they use too
+        assert!(prgm.contains("const A: u8 = 7u8"), "{}", prgm);
+        assert!(prgm.contains("static B: u8 = (m::C * 2u8)"), "{}", prgm);
+        assert_rejects(
+            "const X: u8 = 255 + 1; fn main() -> i32 { 0 }",
+            "Overflow: the result of '+' doesn't fit in 'u8'",
+        );
+        assert_rejects(
+            "const D: i32 = 1 / 0; fn main() -> i32 { 0 }",
+            "Division by zero",
+        );
+        assert_rejects(
+            "static S: i8 = 100; const X: i8 = S + S; fn main() -> i32 { 0 }",
+            "In constant 'X'",
+        );
+        // A constant can be the length of an array, in any type that's written
+        assert!(infer_src(
+            "const N: i32 = M * 2; const M: i32 = 2; type Row = [i32; N];
+            fn first(r: Row) -> [u8; M] { [0, 1] }
+            fn main() -> i32 { let r: [i32; N] = [1, 2, 3, 4]; let f = first(r); 0 }"
+        )
+        .is_ok());
+        assert_rejects(
+            "const N: i32 = 2; fn main() -> i32 { let a: [i32; N] = [1]; 0 }",
+            "expected '[i32; 2]' but found '[i32; 1]'",
+        );
+        assert_rejects(
+            "const N: i32 = -1; fn main() -> i32 { let a: [i32; N] = []; 0 }",
+            "'N' can't be the length of an array",
+        );
+        assert_rejects(
+            "static N: i32 = 2; fn main() -> i32 { let a: [i32; N] = [1, 2]; 0 }",
+            "'N' can't be the length of an array",
+        );
+        assert_rejects(
+            "const A: i32 = B; const B: i32 = A; fn main() -> i32 { 0 }",
+            "'A' is evaluated from its own value",
+        );
+        assert_rejects(
+            "fn one() -> i32 { 1 } const A: i32 = one(); fn main() -> i32 { 0 }",
+            "'one()' can't be evaluated before the program runs",
+        );
+        assert_rejects(
+            "const A: u8 = 300; fn main() -> i32 { 0 }",
+            "Literal '300' doesn't fit in 'u8'",
+        );
+        assert_rejects(
+            "static A: i32 = 1; fn main() -> i32 { A = 2; 0 }",
+            "Not mutable: 'A' is not mutable",
+        );
+        assert_rejects(
+            "mod m { const A: i32 = 1; } fn main() -> i32 { m::A }",
+            "'m::A' is private",
+        );
+        assert_rejects(
+            "fn A() -> i32 { 1 } const A: i32 = 1; fn main() -> i32 { 0 }",
+            "Redeclaration of 'A'",
+        );
+    }
+
+    #[test]
+    fn test_aliases() {
+        // An alias is the same type as the one it stands for, and can name other aliases
+        assert!(infer_src(
+            "type Row = (i32, [char; 2]); type Rows = [Row; 1];
+            fn first(rows: Rows) -> (i32, [char; 2]) { rows[0] }
+            fn main() -> i32 { first([(1, ['a', 'b'])]).0 }"
+        )
+        .is_ok());
+        assert!(infer_src(
+            "mod m { pub type Id = u8; } impl m::Id { fn zero() -> u8 { 0 } }
+            fn main() -> i32 { let x: m::Id = u8::zero(); 0 }"
+        )
+        .is_ok());
+        // Errors show the name the type was written with
+        assert_rejects(
+            "type Row = (i32, [char; 2]); fn main() -> i32 { let r: Row = 5; 0 }",
+            "expected 'Row' but found 'i32'",
+        );
+        assert_rejects(
+            "type A = (i32, B); type B = [A; 2]; fn main() -> i32 { 0 }",
+            "contains itself",
+        );
+        assert_rejects(
+            "struct A { x: i32 } type A = i32; fn main() -> i32 { 0 }",
+            "Redeclaration of type 'A'",
+        );
+        // A tuple struct is a new type, which doesn't mix with the type it holds
+        assert!(infer_src(
+            "struct Meters(i32); fn add(a: Meters, b: Meters) -> Meters { Meters(a.0 + b.0) }
+            fn main() -> i32 { add(Meters(1), Meters(-1)).0 }"
+        )
+        .is_ok());
+        assert_rejects(
+            "struct Meters(i32); fn f(m: Meters) -> i32 { m.0 } fn main() -> i32 { f(5) }",
+            "expected 'Meters' but found 'i32'",
+        );
+        assert_rejects(
+            "struct Meters(i32); fn main() -> i32 { let m: i32 = Meters(5); m }",
+            "expected 'i32' but found 'Meters'",
+        );
     }
 }
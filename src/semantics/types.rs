@@ -1,25 +1,459 @@
+use std::{collections::HashMap, iter, mem};
+
+// The enum each variant of the builtin enums belongs to, since they're used without it
+pub fn builtin_enum(variant: &str) -> Option<&'static str> {
+    match variant {
+        "None" | "Some" => Some("Option"),
+        "Ok" | "Err" => Some("Result"),
+        _ => None,
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Type {
     Signed(Size),
+    Unsigned(Size),
+    // An IEEE floating-point number, of 32 or 64 bits
+    Float(Size),
     Bool,
     Char,
+    // An immutable string of characters, which is indexed and measured by character
+    String,
     Tuple(Vec<Type>),
     Array(Box<Type>, usize),
+    // An array whose length is given by a constant, until type inference puts in its value
+    ArrayLen(Box<Type>, String),
     Function { args: Vec<Type>, ret: Box<Type> },
-    // Reference needs to have a bool to indicate mutability
-    // This needs to be indicated by the parser, and then the type checker.
-    Reference(Box<Type>),
-    // Hidden type that's used to present a mutable value
-    Mutable(Box<Type>),
+    // Coroutine definition: the initial arguments, the arguments it's resumed with, and the yielded type
+    Coro {
+        args: Vec<Type>,
+        resume: Vec<Type>,
+        yields: Box<Type>,
+    },
+    // A started coroutine, which can be resumed until it finishes
+    Frame {
+        resume: Vec<Type>,
+        yields: Box<Type>,
+    },
+    // The bool is true for a mutable reference
+    Reference(Box<Type>, bool),
+    // A declared struct or enum, which is nominal, so two structs with the same fields are still different types
+    Named(String),
+    // The integers from a start up to an end, of the given integer type
+    Range(Box<Type>),
+    // The builtin enums, which hold a value of the type for `Some` and `Ok`, or the error for `Err`
+    Option(Box<Type>),
+    Result(Box<Type>, Box<Type>),
+    // The type of expressions that never finish, like a break, which fits wherever a value is expected
+    Never,
+    // A type parameter of the generic function it's used in
+    Param(String),
+    // A generic function, which gets its type parameters filled in wherever it's used
+    Generic {
+        params: Vec<TypeParam>,
+        ty: Box<Type>,
+    },
+    // A type that's only known once more of its function has been inferred, like the type of a
+    // closure's parameter, which comes from how the parameter is used
+    Var(usize),
+    // Another name for a type, which is the same type under either name, but is shown by the
+    // name it was written with
+    Alias(String, Box<Type>),
 }
 
+// A type parameter of a generic function, along with the traits its type has to implement
 #[derive(Debug, Clone, PartialEq)]
+pub struct TypeParam {
+    pub name: String,
+    pub bounds: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Size {
+    Eight,
+    Sixteen,
     ThirtyTwo,
+    SixtyFour,
+}
+
+impl Size {
+    pub fn bits(&self) -> u32 {
+        match self {
+            Size::Eight => 8,
+            Size::Sixteen => 16,
+            Size::ThirtyTwo => 32,
+            Size::SixtyFour => 64,
+        }
+    }
 }
 
 impl Type {
     pub fn unit() -> Self {
         Type::Tuple(vec![])
     }
+
+    // The type an alias stands for, or the type itself
+    pub fn unalias(&self) -> &Type {
+        match self {
+            Type::Alias(_, ty) => ty.unalias(),
+            ty => ty,
+        }
+    }
+
+    // The type with every alias in it replaced by the type it stands for
+    pub fn unaliased(&self) -> Type {
+        match self {
+            Type::Alias(_, ty) => ty.unaliased(),
+            ty => ty.map(Type::unaliased),
+        }
+    }
+
+    // Whether two types are the same, whatever names they're written with
+    pub fn same(&self, ty: &Type) -> bool {
+        self.unaliased() == ty.unaliased()
+    }
+
+    // Whether a value of this type can be used where the other type is expected
+    pub fn coerces_to(&self, ty: &Type) -> bool {
+        let (this, ty) = (self.unalias(), ty.unalias());
+        match (this, ty) {
+            (Type::Never, _) => true,
+            // An empty array has no elements to be of the wrong type, so it's typed by how it's
+            // used, which can make it any array
+            (Type::Array(from, 0), Type::Array(..)) if **from == Type::Never => true,
+            (Type::Reference(from, true), Type::Reference(to, false)) => from.same(to),
+            // A variant only says what it holds, like `None` fitting any option
+            (Type::Option(from), Type::Option(to)) => from.coerces_to(to),
+            (Type::Result(from, from_err), Type::Result(to, to_err)) => {
+                from.coerces_to(to) && from_err.coerces_to(to_err)
+            }
+            // A generic function can be used as any of its instances, unless
+            // its bounds would need checking against the traits in scope
+            (
+                Type::Generic {
+                    params,
+                    ty: generic,
+                },
+                _,
+            ) if !this.same(ty) && params.iter().all(|param| param.bounds.is_empty()) => {
+                let mut bindings = HashMap::new();
+                generic.bind(params, ty, &mut bindings);
+                generic.substitute(&bindings).same(ty)
+            }
+            _ => this.same(ty),
+        }
+    }
+
+    // The type of a value that comes from either of two branches, if they're compatible
+    pub fn join(&self, other: &Type) -> Option<Type> {
+        match (self.unalias(), other.unalias()) {
+            (Type::Option(ty), Type::Option(other)) => {
+                return Some(Type::Option(Box::new(ty.join(other)?)));
+            }
+            (Type::Result(ty, err), Type::Result(other, other_err)) => {
+                return Some(Type::Result(
+                    Box::new(ty.join(other)?),
+                    Box::new(err.join(other_err)?),
+                ));
+            }
+            _ => (),
+        }
+        if self.coerces_to(other) {
+            Some(other.clone())
+        } else if other.coerces_to(self) {
+            Some(self.clone())
+        } else {
+            None
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(self.unalias(), Type::Signed(_) | Type::Unsigned(_))
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self.unalias(), Type::Float(_))
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        self.is_integer() || self.is_float()
+    }
+
+    // The variants of a builtin enum, along with the types of the values they hold
+    pub fn variants(&self) -> Option<Vec<(String, Vec<Type>)>> {
+        match self.unalias() {
+            Type::Option(ty) => Some(vec![
+                ("None".to_owned(), vec![]),
+                ("Some".to_owned(), vec![*ty.clone()]),
+            ]),
+            Type::Result(ty, err) => Some(vec![
+                ("Ok".to_owned(), vec![*ty.clone()]),
+                ("Err".to_owned(), vec![*err.clone()]),
+            ]),
+            _ => None,
+        }
+    }
+
+    // The type of a builtin enum's variant holding these values, which leaves what the variant
+    // doesn't hold open as `!`, so it fits any type
+    pub fn of_variant(enum_: &str, variant: &str, args: &[Type]) -> Option<Type> {
+        let open = || Box::new(Type::Never);
+        let held = || Box::new(args.first().cloned().unwrap_or(Type::Never));
+        match (enum_, variant) {
+            ("Option", "None") => Some(Type::Option(open())),
+            ("Option", "Some") => Some(Type::Option(held())),
+            ("Result", "Ok") => Some(Type::Result(held(), open())),
+            ("Result", "Err") => Some(Type::Result(open(), held())),
+            _ => None,
+        }
+    }
+
+    // The type of a method that comes with a builtin type, taking the value as `self`
+    pub fn builtin_method(&self, name: &str) -> Option<Type> {
+        let ret = match (self.unalias(), name) {
+            // The number of characters in a string
+            (Type::String, "len") => Type::Unsigned(Size::SixtyFour),
+            _ => return None,
+        };
+        Some(Type::Function {
+            args: vec![self.clone()],
+            ret: Box::new(ret),
+        })
+    }
+
+    // The smallest and largest values of an integer type
+    pub fn bounds(&self) -> Option<(i128, i128)> {
+        match self.unalias() {
+            Type::Signed(size) => {
+                let max = (1i128 << (size.bits() - 1)) - 1;
+                Some((-max - 1, max))
+            }
+            Type::Unsigned(size) => Some((0, (1i128 << size.bits()) - 1)),
+            _ => None,
+        }
+    }
+
+    // The name of a struct or enum type
+    pub fn name(&self) -> Option<&str> {
+        match self.unalias() {
+            Type::Named(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    // Fills in the type parameters from the places they appear in, matched against a type without them.
+    // The first type found for a parameter is kept, so any conflict shows up when the result is checked.
+    pub fn bind(&self, params: &[TypeParam], actual: &Type, bindings: &mut HashMap<String, Type>) {
+        let (this, unaliased) = (self.unalias(), actual.unalias());
+        match (this, unaliased) {
+            // Expressions that never finish don't tell us anything
+            (_, Type::Never) => {}
+            (Type::Param(name), _) if params.iter().any(|param| param.name == *name) => {
+                bindings
+                    .entry(name.clone())
+                    .or_insert_with(|| actual.clone());
+            }
+            _ if mem::discriminant(this) == mem::discriminant(unaliased) => {
+                for (ty, actual) in this.children().into_iter().zip(unaliased.children()) {
+                    ty.bind(params, actual, bindings);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Replaces the type parameters that have been bound, leaving the others as they are
+    pub fn substitute(&self, bindings: &HashMap<String, Type>) -> Type {
+        match self {
+            Type::Param(name) => bindings.get(name).unwrap_or(self).clone(),
+            // The generic function's own parameters shadow the ones being substituted
+            Type::Generic { params, ty } => {
+                let mut bindings = bindings.clone();
+                bindings.retain(|name, _| params.iter().all(|param| param.name != *name));
+                Type::Generic {
+                    params: params.clone(),
+                    ty: Box::new(ty.substitute(&bindings)),
+                }
+            }
+            ty => ty.map(|ty| ty.substitute(bindings)),
+        }
+    }
+
+    // Turns the names of type parameters into parameters,
+    // since the parser can't tell them apart from struct and enum names
+    pub fn resolve(&self, params: &[TypeParam]) -> Type {
+        match self {
+            Type::Named(name) if params.iter().any(|param| param.name == *name) => {
+                Type::Param(name.clone())
+            }
+            ty => ty.map(|ty| ty.resolve(params)),
+        }
+    }
+
+    // Replaces `Self` with the type an impl is for
+    pub fn replace_self(&self, ty: &Type) -> Type {
+        match self {
+            Type::Named(name) if name == "Self" => ty.clone(),
+            other => other.map(|other| other.replace_self(ty)),
+        }
+    }
+
+    // Replaces the names of structs and enums, like a name written inside a module with its path.
+    // `Self` isn't declared in any module, so it stays as it is.
+    pub fn rename(&self, f: &impl Fn(&str) -> String) -> Type {
+        match self {
+            Type::Named(name) if name != "Self" => Type::Named(f(name)),
+            Type::ArrayLen(ty, len) => Type::ArrayLen(Box::new(ty.rename(f)), f(len)),
+            ty => ty.map(|ty| ty.rename(f)),
+        }
+    }
+
+    // Replaces the names of aliases with the types they stand for
+    pub fn expand(&self, aliases: &HashMap<String, Type>) -> Type {
+        match self {
+            Type::Named(name) => match aliases.get(name) {
+                Some(ty) => Type::Alias(name.clone(), Box::new(ty.expand(aliases))),
+                None => self.clone(),
+            },
+            ty => ty.map(|ty| ty.expand(aliases)),
+        }
+    }
+
+    // The structs and enums the type mentions
+    pub fn names(&self) -> Vec<&str> {
+        match self {
+            Type::Named(name) => vec![name],
+            ty => ty.children().into_iter().flat_map(Type::names).collect(),
+        }
+    }
+
+    // The constants the type uses as the lengths of arrays
+    pub fn lengths(&self) -> Vec<&str> {
+        let inner = self.children().into_iter().flat_map(Type::lengths);
+        match self {
+            Type::ArrayLen(_, len) => iter::once(len.as_str()).chain(inner).collect(),
+            _ => inner.collect(),
+        }
+    }
+
+    // Replaces the constants used as the lengths of arrays with their values
+    pub fn size(&self, lengths: &HashMap<String, usize>) -> Type {
+        match self {
+            Type::ArrayLen(ty, len) => match lengths.get(len) {
+                Some(len) => Type::Array(Box::new(ty.size(lengths)), *len),
+                None => Type::ArrayLen(Box::new(ty.size(lengths)), len.clone()),
+            },
+            ty => ty.map(|ty| ty.size(lengths)),
+        }
+    }
+
+    // Whether the type mentions a type parameter, which is only known once the function is called
+    pub fn is_generic(&self) -> bool {
+        matches!(self, Type::Param(_)) || self.children().into_iter().any(Type::is_generic)
+    }
+
+    // Replaces the type variables that have been filled in, leaving the others as they are
+    pub fn fill(&self, known: &impl Fn(usize) -> Option<Type>) -> Type {
+        match self {
+            Type::Var(var) => known(*var).map_or(self.clone(), |ty| ty.fill(known)),
+            ty => ty.map(|ty| ty.fill(known)),
+        }
+    }
+
+    // Whether the type variable appears anywhere in the type
+    pub fn mentions(&self, var: usize) -> bool {
+        *self == Type::Var(var) || self.children().into_iter().any(|ty| ty.mentions(var))
+    }
+
+    // Whether the type still has a type variable in it that hasn't been filled in
+    pub fn is_open(&self) -> bool {
+        matches!(self, Type::Var(_)) || self.children().into_iter().any(Type::is_open)
+    }
+
+    // Whether values of the type can be printed, which functions and coroutines can't be
+    pub fn is_printable(&self) -> bool {
+        match self {
+            Type::Function { .. }
+            | Type::Coro { .. }
+            | Type::Frame { .. }
+            | Type::Generic { .. }
+            | Type::Param(_) => false,
+            ty => ty.children().into_iter().all(Type::is_printable),
+        }
+    }
+
+    // The types directly inside of this one
+    pub fn children(&self) -> Vec<&Type> {
+        match self {
+            Type::Tuple(tys) => tys.iter().collect(),
+            Type::Array(ty, _)
+            | Type::ArrayLen(ty, _)
+            | Type::Reference(ty, _)
+            | Type::Range(ty)
+            | Type::Option(ty) => vec![ty],
+            Type::Result(ty, err) => vec![ty, err],
+            Type::Generic { ty, .. } | Type::Alias(_, ty) => vec![ty],
+            Type::Function { args, ret } => args.iter().chain([ret.as_ref()]).collect(),
+            Type::Coro {
+                args,
+                resume,
+                yields,
+            } => args.iter().chain(resume).chain([yields.as_ref()]).collect(),
+            Type::Frame { resume, yields } => resume.iter().chain([yields.as_ref()]).collect(),
+            Type::Signed(_)
+            | Type::Unsigned(_)
+            | Type::Float(_)
+            | Type::Bool
+            | Type::Char
+            | Type::String
+            | Type::Named(_)
+            | Type::Never
+            | Type::Param(_)
+            | Type::Var(_) => vec![],
+        }
+    }
+
+    // Rebuilds the type with each of the types directly inside of it replaced
+    fn map(&self, mut f: impl FnMut(&Type) -> Type) -> Type {
+        match self {
+            Type::Tuple(tys) => Type::Tuple(tys.iter().map(&mut f).collect()),
+            Type::Array(ty, len) => Type::Array(Box::new(f(ty)), *len),
+            Type::ArrayLen(ty, len) => Type::ArrayLen(Box::new(f(ty)), len.clone()),
+            Type::Reference(ty, mutable) => Type::Reference(Box::new(f(ty)), *mutable),
+            Type::Range(ty) => Type::Range(Box::new(f(ty))),
+            Type::Option(ty) => Type::Option(Box::new(f(ty))),
+            Type::Result(ty, err) => Type::Result(Box::new(f(ty)), Box::new(f(err))),
+            Type::Generic { params, ty } => Type::Generic {
+                params: params.clone(),
+                ty: Box::new(f(ty)),
+            },
+            Type::Alias(name, ty) => Type::Alias(name.clone(), Box::new(f(ty))),
+            Type::Function { args, ret } => Type::Function {
+                args: args.iter().map(&mut f).collect(),
+                ret: Box::new(f(ret)),
+            },
+            Type::Coro {
+                args,
+                resume,
+                yields,
+            } => Type::Coro {
+                args: args.iter().map(&mut f).collect(),
+                resume: resume.iter().map(&mut f).collect(),
+                yields: Box::new(f(yields)),
+            },
+            Type::Frame { resume, yields } => Type::Frame {
+                resume: resume.iter().map(&mut f).collect(),
+                yields: Box::new(f(yields)),
+            },
+            ty => ty.clone(),
+        }
+    }
+
+    // The type a yield evaluates to, once its coroutine is resumed with these types
+    pub fn resumed(resume: &[Type]) -> Self {
+        match resume {
+            [ty] => ty.clone(),
+            tys => Type::Tuple(tys.to_vec()),
+        }
+    }
 }
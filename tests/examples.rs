@@ -0,0 +1,63 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+// The examples that fail on purpose, along with what they fail with
+const FAILING: &[(&str, &str)] = &[
+    ("arrays.yk", "Index out of bounds"),
+    ("functions.yk", "Non-zero exit code: -7"),
+    (
+        "ifelse.yk",
+        "expected '() -> i32' but found '(i32, i32) -> i32'",
+    ),
+    ("refs.yk", "Non-zero exit code: 123"),
+    ("simple.yk", "expected '() -> i32' but found '() -> ()'"),
+    ("types.yk", "expected '() -> i32' but found '() -> ()'"),
+    ("while.yk", "expected '() -> i32' but found '() -> ()'"),
+];
+
+fn examples() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+    let mut paths = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "yk"))
+        .collect::<Vec<_>>();
+    paths.sort();
+    // A program split into modules is run from its main file
+    paths.push(dir.join("modules").join("main.yk"));
+    paths
+}
+
+// Runs the example, with no input, giving back whether it succeeded and what it printed as an error
+fn run(path: &Path, args: &[&str]) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_yoyok"))
+        .args(args)
+        .arg(path)
+        .stdin(Stdio::null())
+        .env("RUST_BACKTRACE", "0")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    (output.status.success(), stderr)
+}
+
+#[test]
+fn test_examples() {
+    for path in examples() {
+        let name = path.file_name().unwrap().to_str().unwrap();
+        let failing = FAILING.iter().find(|(file, _)| *file == name);
+        let (ok, stderr) = run(&path, &[]);
+        match failing {
+            Some((_, msg)) => assert!(!ok && stderr.contains(msg), "{}: {}", name, stderr),
+            None => assert!(ok, "{}: {}", name, stderr),
+        }
+        // Checking ownership at runtime shouldn't stop any of the others from running
+        if failing.is_none() {
+            let (ok, stderr) = run(&path, &["--check-ownership"]);
+            assert!(ok, "{} with --check-ownership: {}", name, stderr);
+        }
+    }
+}